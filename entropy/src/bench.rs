@@ -0,0 +1,155 @@
+use crate::format::FormatError;
+use crate::{Backend, Config, Kernel, Scheme, Simulation};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use std::time::Instant;
+
+pub const REPORT_VERSION: u32 = 1;
+const SEED: u64 = 7;
+
+// a seeded run timed step by step; the cases cover each backend and scheme on
+// the same board, so a change to one shows up against the others
+pub struct BenchCase {
+    pub name: &'static str,
+    configure: fn(&mut Config),
+}
+
+pub const CASES: &[BenchCase] = &[
+    BenchCase {
+        name: "scalar 128x128",
+        configure: |_| {},
+    },
+    BenchCase {
+        name: "parallel 128x128",
+        configure: |c| c.backend = Backend::Parallel,
+    },
+    BenchCase {
+        name: "gather 128x128",
+        configure: |c| c.scheme = Scheme::Gather,
+    },
+    BenchCase {
+        name: "lbm 128x128",
+        configure: |c| c.scheme = Scheme::Lbm,
+    },
+    BenchCase {
+        name: "gaussian r2 128x128",
+        configure: |c| {
+            c.kernel = Kernel::Gaussian {
+                radius: 2,
+                sigma: None,
+            }
+        },
+    },
+    BenchCase {
+        name: "parallel 512x512",
+        configure: |c| {
+            c.backend = Backend::Parallel;
+            c.dims = (512, 512);
+        },
+    },
+];
+
+impl BenchCase {
+    pub fn config(&self) -> Config {
+        let mut config = Config {
+            dims: (128, 128),
+            hotspots: 16,
+            seed: Some(SEED),
+            ..Config::default()
+        };
+        (self.configure)(&mut config);
+        config
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchResult {
+    pub name: String,
+    pub steps: usize,
+    // per step, in nanoseconds
+    pub median_ns: f64,
+    pub p95_ns: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchReport {
+    pub version: u32,
+    pub results: Vec<BenchResult>,
+}
+
+impl BenchReport {
+    pub fn read(path: &Path) -> Result<Self, FormatError> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), FormatError> {
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+}
+
+// times `steps` steps of each case whose name contains `filter`, after a tenth
+// as many to warm up
+pub fn run(steps: usize, filter: Option<&str>) -> BenchReport {
+    let results = CASES
+        .iter()
+        .filter(|case| filter.is_none_or(|f| case.name.contains(f)))
+        .map(|case| {
+            let mut sim = Simulation::new(case.config()).expect("the bench configs are valid");
+            for _ in 0..steps / 10 {
+                sim.step();
+            }
+            let mut times: Vec<f64> = (0..steps.max(1))
+                .map(|_| {
+                    let start = Instant::now();
+                    sim.step();
+                    start.elapsed().as_secs_f64() * 1e9
+                })
+                .collect();
+            times.sort_by(f64::total_cmp);
+            let at = |q: f64| times[((q * times.len() as f64) as usize).min(times.len() - 1)];
+            BenchResult {
+                name: case.name.to_string(),
+                steps: times.len(),
+                median_ns: at(0.5),
+                p95_ns: at(0.95),
+            }
+        })
+        .collect();
+    BenchReport {
+        version: REPORT_VERSION,
+        results,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub name: String,
+    pub baseline_ns: f64,
+    pub current_ns: f64,
+    // the fractional change in the median, positive when slower
+    pub change: f64,
+    pub regressed: bool,
+}
+
+// the cases in both reports, a case regressing when its median is slower by
+// more than `threshold`, e.g. 0.1 for 10%
+pub fn compare(baseline: &BenchReport, current: &BenchReport, threshold: f64) -> Vec<Comparison> {
+    current
+        .results
+        .iter()
+        .filter_map(|now| {
+            let then = baseline.results.iter().find(|r| r.name == now.name)?;
+            let change = now.median_ns / then.median_ns - 1.0;
+            Some(Comparison {
+                name: now.name.clone(),
+                baseline_ns: then.median_ns,
+                current_ns: now.median_ns,
+                change,
+                regressed: change > threshold,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,200 @@
+use ndarray::Array2;
+use std::collections::HashMap;
+
+// storage for an energy field, in f64 whatever the board keeps internally.
+// only the step functions (board_time_step, traced_time_step and the kernels
+// under them) are generic over it; Simulation, the renderers and the stats
+// keep an Array2<f64>, so the other storages are for driving steps directly
+// and comparing them with the dense board
+pub trait Board: Clone {
+    fn zeros(dims: (usize, usize)) -> Self;
+
+    fn dims(&self) -> (usize, usize);
+
+    fn get(&self, cell: (usize, usize)) -> f64;
+
+    fn set(&mut self, cell: (usize, usize), energy: f64);
+
+    fn add(&mut self, cell: (usize, usize), energy: f64) {
+        self.set(cell, self.get(cell) + energy);
+    }
+
+    fn clear(&mut self);
+
+    fn total(&self) -> f64 {
+        self.to_dense().sum()
+    }
+
+    fn to_dense(&self) -> Array2<f64> {
+        Array2::from_shape_fn(self.dims(), |cell| self.get(cell))
+    }
+}
+
+impl Board for Array2<f64> {
+    fn zeros(dims: (usize, usize)) -> Self {
+        Array2::zeros(dims)
+    }
+
+    fn dims(&self) -> (usize, usize) {
+        self.dim()
+    }
+
+    #[inline(always)]
+    fn get(&self, cell: (usize, usize)) -> f64 {
+        self[cell]
+    }
+
+    #[inline(always)]
+    fn set(&mut self, cell: (usize, usize), energy: f64) {
+        self[cell] = energy;
+    }
+
+    #[inline(always)]
+    fn add(&mut self, cell: (usize, usize), energy: f64) {
+        self[cell] += energy;
+    }
+
+    fn clear(&mut self) {
+        self.fill(0.0);
+    }
+
+    fn total(&self) -> f64 {
+        self.sum()
+    }
+
+    fn to_dense(&self) -> Array2<f64> {
+        self.clone()
+    }
+}
+
+impl Board for Array2<f32> {
+    fn zeros(dims: (usize, usize)) -> Self {
+        Array2::zeros(dims)
+    }
+
+    fn dims(&self) -> (usize, usize) {
+        self.dim()
+    }
+
+    #[inline(always)]
+    fn get(&self, cell: (usize, usize)) -> f64 {
+        self[cell] as f64
+    }
+
+    #[inline(always)]
+    fn set(&mut self, cell: (usize, usize), energy: f64) {
+        self[cell] = energy as f32;
+    }
+
+    #[inline(always)]
+    fn add(&mut self, cell: (usize, usize), energy: f64) {
+        self[cell] += energy as f32;
+    }
+
+    fn clear(&mut self) {
+        self.fill(0.0);
+    }
+}
+
+// only stores nonzero cells, for boards that are mostly cold
+#[derive(Debug, Clone)]
+pub struct SparseBoard {
+    dims: (usize, usize),
+    cells: HashMap<(usize, usize), f64>,
+}
+
+impl Board for SparseBoard {
+    fn zeros(dims: (usize, usize)) -> Self {
+        Self {
+            dims,
+            cells: HashMap::new(),
+        }
+    }
+
+    fn dims(&self) -> (usize, usize) {
+        self.dims
+    }
+
+    fn get(&self, cell: (usize, usize)) -> f64 {
+        self.cells.get(&cell).copied().unwrap_or(0.0)
+    }
+
+    fn set(&mut self, cell: (usize, usize), energy: f64) {
+        if energy == 0.0 {
+            self.cells.remove(&cell);
+        } else {
+            self.cells.insert(cell, energy);
+        }
+    }
+
+    fn add(&mut self, cell: (usize, usize), energy: f64) {
+        if energy != 0.0 {
+            *self.cells.entry(cell).or_insert(0.0) += energy;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    // in cell order, so the total doesn't change with the map's iteration order
+    fn total(&self) -> f64 {
+        let mut cells: Vec<_> = self.cells.iter().collect();
+        cells.sort_unstable_by_key(|(&cell, _)| cell);
+        cells.into_iter().map(|(_, &energy)| energy).sum()
+    }
+}
+
+// whole quanta of 1 / PER_UNIT energy each, rounded to the nearest on every
+// write, so totals are exact sums and come out the same in any order; the
+// float kernels still conserve energy only to within half a quantum a write
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuantaBoard<const PER_UNIT: u64> {
+    quanta: Array2<i64>,
+}
+
+impl<const PER_UNIT: u64> QuantaBoard<PER_UNIT> {
+    fn quanta(energy: f64) -> i64 {
+        (energy * PER_UNIT as f64).round() as i64
+    }
+
+    // the quanta a cell holds
+    pub fn count(&self, cell: (usize, usize)) -> i64 {
+        self.quanta[cell]
+    }
+}
+
+impl<const PER_UNIT: u64> Board for QuantaBoard<PER_UNIT> {
+    fn zeros(dims: (usize, usize)) -> Self {
+        Self {
+            quanta: Array2::zeros(dims),
+        }
+    }
+
+    fn dims(&self) -> (usize, usize) {
+        self.quanta.dim()
+    }
+
+    #[inline(always)]
+    fn get(&self, cell: (usize, usize)) -> f64 {
+        self.quanta[cell] as f64 / PER_UNIT as f64
+    }
+
+    #[inline(always)]
+    fn set(&mut self, cell: (usize, usize), energy: f64) {
+        self.quanta[cell] = Self::quanta(energy);
+    }
+
+    #[inline(always)]
+    fn add(&mut self, cell: (usize, usize), energy: f64) {
+        self.quanta[cell] += Self::quanta(energy);
+    }
+
+    fn clear(&mut self) {
+        self.quanta.fill(0);
+    }
+
+    fn total(&self) -> f64 {
+        self.quanta.sum() as f64 / PER_UNIT as f64
+    }
+}
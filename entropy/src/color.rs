@@ -0,0 +1,34 @@
+use entropy::palette::{self, ColorScale, Palette};
+use pixel_canvas::Color;
+
+// blue for no energy, red for max_energy and above
+#[inline(always)]
+pub fn energy_to_rgb(energy: f64, max_energy: f64) -> Color {
+    let [r, g, b] = palette::hue_ramp(energy / max_energy);
+    Color { r, g, b }
+}
+
+// blue for negative, red for positive, symmetric around zero
+#[inline(always)]
+pub fn diverging_rgb(value: f64, max_abs: f64) -> Color {
+    let t = if max_abs > 0.0 { value / max_abs } else { 0.0 };
+    energy_to_rgb((t + 1.0) / 2.0, 1.0)
+}
+
+// value / max on a saved palette, or on the hue ramp without one
+#[inline(always)]
+pub fn ramp_rgb(palette: Option<&Palette>, value: f64, max: f64) -> Color {
+    match palette {
+        Some(palette) => {
+            let [r, g, b] = palette.sample(value / max);
+            Color { r, g, b }
+        }
+        None => energy_to_rgb(value, max),
+    }
+}
+
+// an energy on the ramp up to `max`, placed by `scale`
+#[inline(always)]
+pub fn scaled_rgb(palette: Option<&Palette>, scale: ColorScale, energy: f64, max: f64) -> Color {
+    ramp_rgb(palette, scale.position(energy, max), 1.0)
+}
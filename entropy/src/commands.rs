@@ -0,0 +1,137 @@
+use entropy::presets;
+use entropy::session::Key;
+
+// what a command palette entry does
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    // anything with a hotkey, or that a session records
+    Key(Key),
+    // writes the board as snapshot-<step>.npy
+    Snapshot,
+    // starts or stops writing drawn frames as PNGs
+    ToggleFrames,
+    // starts or stops encoding them to a GIF or video
+    ToggleVideo,
+    ToggleOverlay,
+    // None goes back to the built-in ramp
+    Palette(Option<String>),
+    Preset(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub action: Action,
+}
+
+// every runtime action, with one entry for each saved color palette
+pub fn all(palettes: &[String]) -> Vec<Entry> {
+    let keys = [
+        ("pause or resume", Key::TogglePause),
+        ("step back", Key::Back),
+        ("step forward", Key::Forward),
+        ("next display", Key::NextDisplay),
+        ("next field", Key::NextField),
+        ("next dye view", Key::NextTracerView),
+        ("rotate view", Key::RotateView),
+        ("randomize parameters", Key::Randomize),
+        ("unpin focus", Key::Unpin),
+        ("reset to step 0", Key::Reset),
+    ];
+    let mut commands: Vec<_> = keys
+        .into_iter()
+        .map(|(name, key)| Entry {
+            name: name.to_string(),
+            action: Action::Key(key),
+        })
+        .collect();
+    commands.push(Entry {
+        name: "snapshot board".to_string(),
+        action: Action::Snapshot,
+    });
+    commands.push(Entry {
+        name: "toggle frame capture".to_string(),
+        action: Action::ToggleFrames,
+    });
+    commands.push(Entry {
+        name: "toggle video recording".to_string(),
+        action: Action::ToggleVideo,
+    });
+    commands.push(Entry {
+        name: "toggle kernel overlay".to_string(),
+        action: Action::ToggleOverlay,
+    });
+    commands.push(Entry {
+        name: "palette default".to_string(),
+        action: Action::Palette(None),
+    });
+    commands.extend(palettes.iter().map(|name| Entry {
+        name: format!("palette {}", name),
+        action: Action::Palette(Some(name.clone())),
+    }));
+    commands.extend(presets::NAMES.iter().map(|&name| Entry {
+        name: format!("preset {}", name),
+        action: Action::Preset(name),
+    }));
+    commands
+}
+
+// how well `query` matches `name` as a subsequence, ignoring case; lower is
+// better, counting the characters skipped between matches. None if it doesn't
+pub fn fuzzy_score(query: &str, name: &str) -> Option<usize> {
+    let mut name = name.chars().map(|c| c.to_ascii_lowercase());
+    let mut skipped = 0;
+    let mut started = false;
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        let q = q.to_ascii_lowercase();
+        loop {
+            let c = name.next()?;
+            if c == q {
+                break;
+            }
+            // what comes before the first match doesn't count
+            if started {
+                skipped += 1;
+            }
+        }
+        started = true;
+    }
+    Some(skipped)
+}
+
+// the Ctrl+P overlay: what's been typed and which match is highlighted
+#[derive(Debug, Default)]
+pub struct CommandPalette {
+    pub query: String,
+    pub selected: usize,
+}
+
+impl CommandPalette {
+    // the commands matching the query, best first
+    pub fn matches<'a>(&self, commands: &'a [Entry]) -> Vec<&'a Entry> {
+        let mut matches: Vec<_> = commands
+            .iter()
+            .filter_map(|c| Some((fuzzy_score(&self.query, &c.name)?, c)))
+            .collect();
+        // stable, so ties keep the list's order
+        matches.sort_by_key(|&(score, _)| score);
+        matches.into_iter().map(|(_, c)| c).collect()
+    }
+
+    pub fn type_char(&mut self, c: char) {
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    pub fn backspace(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    pub fn move_selection(&mut self, down: bool, matches: usize) {
+        self.selected = match down {
+            true => (self.selected + 1).min(matches.saturating_sub(1)),
+            false => self.selected.saturating_sub(1),
+        };
+    }
+}
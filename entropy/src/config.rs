@@ -0,0 +1,1087 @@
+use crate::estimator::Estimator;
+use crate::field::Spectrum;
+use crate::model::{
+    Backend, Bath, Boundary, Cells, ClampPolicy, Drift, FixedSource, Focus, HeatCapacity,
+    InitialCondition, Kernel, Levy, Mode, PhaseChange, ResetScope, Scheme, Source, SourcePath,
+    Tracer, Traps, Waiting, Walls, MAX_KERNEL_RADIUS,
+};
+use crate::palette::{self, ColorScale, Colormap, Normalization, Scale};
+use crate::randomize::Randomizer;
+use crate::script::ScriptedEvent;
+use crate::speed::{AutoSpeed, PowerSave};
+use crate::transform::Transform;
+use directories::ProjectDirs;
+use itertools::iproduct;
+use ndarray::Array2;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::{
+    fs::{self, File},
+    io::BufReader,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub dims: (usize, usize),
+    pub hotspots: usize,
+    pub sleep_interval_ms: usize,
+    // how many steps the window runs between frames
+    #[serde(default = "default_steps_per_frame")]
+    pub steps_per_frame: usize,
+    // sets steps_per_frame from the entropy change, never going below the above
+    #[serde(default)]
+    pub auto_speed: Option<AutoSpeed>,
+    // drops the window to idle_fps once the entropy has settled
+    #[serde(default)]
+    pub power_save: Option<PowerSave>,
+    // the fraction of its energy a cell spreads each step, from 0 to 1; the rest
+    // stays put, so lower values slow diffusion down
+    pub heat: f64,
+    pub size_factor: usize,
+    // draw row/column energy sums along the left/top edges of the window
+    #[serde(default)]
+    pub marginals: bool,
+    #[serde(default = "default_marginal_size")]
+    pub marginal_size: usize,
+    // on NaN or negative energy, dump the offending step to debug_dump_path and exit
+    #[serde(default)]
+    pub debug: bool,
+    // debug_dump.json in data_dir() if absent
+    #[serde(default)]
+    pub debug_dump_path: Option<String>,
+    // the window writes every frame_every-th frame it draws here as a numbered
+    // PNG; F turns this on and off, into frames/ if it isn't set
+    #[serde(default)]
+    pub frame_output_dir: Option<String>,
+    #[serde(default = "default_frame_every")]
+    pub frame_every: usize,
+    // encoding the window to a GIF or video, with V or from the start
+    #[serde(default)]
+    pub video: VideoSettings,
+    // the whole run, rng included, written to checkpoint_path every this many
+    // steps, for --resume to carry on from after a crash
+    #[serde(default)]
+    pub checkpoint_every: Option<usize>,
+    #[serde(default = "default_checkpoint_path")]
+    pub checkpoint_path: String,
+    // what to do when a cell's weights don't sum to 1 within assertion_tolerance
+    #[serde(default)]
+    pub assertions: Assertions,
+    #[serde(default = "default_assertion_tolerance")]
+    pub assertion_tolerance: f64,
+    // shade the cells the kernel flagged on the last step
+    #[serde(default)]
+    pub debug_overlay: bool,
+    // what to do with tiny negative or subnormal energies left by rounding:
+    // keep, zero, redistribute or error
+    #[serde(default)]
+    pub clamp_policy: ClampPolicy,
+    // memory budget for the boards kept for stepping back while paused
+    #[serde(default = "default_history_memory_mb")]
+    pub history_memory_mb: usize,
+    #[serde(default)]
+    pub backend: Backend,
+    // how many threads the parallel backend may use; every core if absent
+    #[serde(default)]
+    pub threads: Option<usize>,
+    #[serde(default)]
+    pub boundary: Boundary,
+    #[serde(default)]
+    pub scheme: Scheme,
+    #[serde(default)]
+    pub mode: Mode,
+    // which neighbors a cell spreads to and how much each gets; the 3x3 square,
+    // evenly, if absent
+    #[serde(default)]
+    pub kernel: Kernel,
+    // cells with less energy than this sit out the step, keeping what they have
+    // (and what flows in) until they pass it; 0 steps every cell
+    #[serde(default)]
+    pub active_threshold: f64,
+    // only cells in it step stochastically; the window pins it with a click
+    #[serde(default)]
+    pub focus: Option<Focus>,
+    // what a shift-click in the window adds to the cell under the cursor; a
+    // shift-drag adds it to the cell under the cursor every frame
+    #[serde(default = "default_click_energy")]
+    pub click_energy: f64,
+    // random if absent
+    #[serde(default)]
+    pub seed: Option<u64>,
+    // which field the window shows; cycled with D
+    #[serde(default)]
+    pub display: Display,
+    // step without a window, as with --headless
+    #[serde(default)]
+    pub headless: bool,
+    // a palette saved with `entropy palette save`, by name; the colormap if absent
+    #[serde(default)]
+    pub palette: Option<String>,
+    // "hue", "viridis", "inferno", "grayscale" or {"custom": [stops]}
+    #[serde(default)]
+    pub colormap: Colormap,
+    // energy at the top of the ramp; worked out from the initial board if absent
+    #[serde(default)]
+    pub max_energy: Option<f64>,
+    // "fixed" at the above, the "running_max" or {"percentile": 99} of each frame
+    #[serde(default)]
+    pub normalization: Normalization,
+    // "linear", or "log" to color by log(1 + energy)
+    #[serde(default)]
+    pub scale: ColorScale,
+    // blend colors in linear light and convert to sRGB only for the output, so
+    // gradients don't band or go muddy
+    #[serde(default)]
+    pub linear_light: bool,
+    #[serde(default = "default_local_entropy_window")]
+    pub local_entropy_window: usize,
+    // the run summary reports when the KL divergence from uniform first drops below this
+    #[serde(default = "default_kl_threshold")]
+    pub kl_threshold: f64,
+    // how the board is split in two for the mutual information metric
+    #[serde(default)]
+    pub mi_partition: Partition,
+    #[serde(default = "default_mi_bins")]
+    pub mi_bins: usize,
+    // how the run's entropy is measured: "shannon" over cells, {"binned": {"bins":
+    // 64}} over energy levels, {"nearest_neighbor": {"k": 1}} or {"renyi": {"alpha": 2}}
+    #[serde(default)]
+    pub entropy_estimator: Estimator,
+    // couples the board to a heat bath; without one the system is isolated
+    #[serde(default)]
+    pub bath: Option<Bath>,
+    // steps to wait before averaging the heat current of a two-temperature bath
+    #[serde(default = "default_current_warmup")]
+    pub current_warmup: usize,
+    // per-step probability of putting cells back to their initial energy
+    #[serde(default)]
+    pub reset_rate: f64,
+    #[serde(default)]
+    pub reset_scope: ResetScope,
+    // standard deviation of the gaussian noise added to every cell each step
+    #[serde(default)]
+    pub thermal_noise: f64,
+    // makes that noise spatially correlated; white if absent
+    #[serde(default)]
+    pub noise_spectrum: Option<Spectrum>,
+    // lets part of each cell's energy jump far away instead of to its neighbors
+    #[serde(default)]
+    pub levy: Option<Levy>,
+    // makes cells wait a random number of steps between releases
+    #[serde(default)]
+    pub waiting: Option<Waiting>,
+    // cells that hold back part of their energy for a while, as in disordered media
+    #[serde(default)]
+    pub traps: Option<Traps>,
+    // heaters, possibly moving, that add energy every step
+    #[serde(default)]
+    pub sources: Vec<Source>,
+    // cells that gain a fixed amount every step, and cells emptied every step;
+    // both after the diffusion, so the board can settle into a steady state
+    // with a current through it
+    #[serde(default)]
+    pub fixed_sources: Vec<FixedSource>,
+    #[serde(default)]
+    pub sinks: Vec<Cells>,
+    // cells nothing diffuses into, drawn in their own color
+    #[serde(default)]
+    pub walls: Option<Walls>,
+    // a dye carried along with the energy and drawn over the board; I cycles how
+    #[serde(default)]
+    pub tracer: Option<Tracer>,
+    #[serde(default)]
+    pub initial: InitialCondition,
+    // rotates or mirrors the initial field, trap mask and wall image read from files
+    #[serde(default)]
+    pub transform: Transform,
+    // advection on top of the diffusion; a plain [dx, dy] is a uniform wind, with
+    // x to the right and y up the window
+    #[serde(default, deserialize_with = "drift_or_wind")]
+    pub drift: Option<Drift>,
+    // the window shows energy divided by capacity
+    #[serde(default)]
+    pub capacity: HeatCapacity,
+    #[serde(default)]
+    pub phase_change: Option<PhaseChange>,
+    // also advance the expected, noise-free version of the board (S to view it);
+    // the shadow steps with the scatter kernel's mean weights, so not under lbm
+    // or with sources, noise and the like that it has no expectation of
+    #[serde(default)]
+    pub shadow: bool,
+    // done to the run at given steps
+    #[serde(default)]
+    pub events: Vec<ScriptedEvent>,
+    // what the R key may change while running
+    #[serde(default)]
+    pub randomizer: Randomizer,
+    // save each windowed run under runs/ in data_dir() when it ends
+    #[serde(default = "default_archive")]
+    pub archive: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Display {
+    #[default]
+    Energy,
+    // per-cell change in the -p ln p entropy density since the previous step
+    EntropyProduction,
+    // entropy of the local_entropy_window x local_entropy_window window around each cell
+    LocalEntropy,
+}
+
+impl Display {
+    pub fn next(self) -> Display {
+        match self {
+            Display::Energy => Display::EntropyProduction,
+            Display::EntropyProduction => Display::LocalEntropy,
+            Display::LocalEntropy => Display::Energy,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Assertions {
+    #[default]
+    Off,
+    // print the failing cells and carry on
+    Warn,
+    Panic,
+}
+
+impl Config {
+    // whether the kernel has to record per-cell flags at all
+    pub fn checks_kernel(&self) -> bool {
+        self.assertions != Assertions::Off || self.debug_overlay
+    }
+
+    // FNV-1a over the config as JSON, to tell which config an artifact came from
+    pub fn hash(&self) -> u64 {
+        let json = serde_json::to_vec(self).expect("a config always serializes");
+        json.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            dims: (100, 100),
+            hotspots: 1,
+            sleep_interval_ms: 0,
+            steps_per_frame: default_steps_per_frame(),
+            auto_speed: None,
+            power_save: None,
+            heat: 1.0,
+            size_factor: 5,
+            marginals: false,
+            marginal_size: default_marginal_size(),
+            debug: false,
+            debug_dump_path: None,
+            frame_output_dir: None,
+            frame_every: default_frame_every(),
+            video: VideoSettings::default(),
+            checkpoint_every: None,
+            checkpoint_path: default_checkpoint_path(),
+            assertions: Assertions::default(),
+            assertion_tolerance: default_assertion_tolerance(),
+            debug_overlay: false,
+            clamp_policy: ClampPolicy::default(),
+            history_memory_mb: default_history_memory_mb(),
+            backend: Backend::default(),
+            threads: None,
+            boundary: Boundary::default(),
+            scheme: Scheme::default(),
+            mode: Mode::default(),
+            kernel: Kernel::default(),
+            active_threshold: 0.0,
+            focus: None,
+            click_energy: default_click_energy(),
+            seed: None,
+            display: Display::default(),
+            headless: false,
+            palette: None,
+            colormap: Colormap::default(),
+            max_energy: None,
+            normalization: Normalization::default(),
+            scale: ColorScale::default(),
+            linear_light: false,
+            local_entropy_window: default_local_entropy_window(),
+            kl_threshold: default_kl_threshold(),
+            mi_partition: Partition::default(),
+            mi_bins: default_mi_bins(),
+            entropy_estimator: Estimator::default(),
+            bath: None,
+            current_warmup: default_current_warmup(),
+            reset_rate: 0.0,
+            thermal_noise: 0.0,
+            noise_spectrum: None,
+            reset_scope: ResetScope::default(),
+            levy: None,
+            waiting: None,
+            traps: None,
+            sources: Vec::new(),
+            fixed_sources: Vec::new(),
+            sinks: Vec::new(),
+            walls: None,
+            tracer: None,
+            initial: InitialCondition::default(),
+            transform: Transform::default(),
+            drift: None,
+            capacity: HeatCapacity::default(),
+            phase_change: None,
+            shadow: false,
+            events: Vec::new(),
+            randomizer: Randomizer::default(),
+            archive: default_archive(),
+        }
+    }
+}
+
+// vertical splits into left/right halves, horizontal into top/bottom
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Partition {
+    #[default]
+    Vertical,
+    Horizontal,
+}
+
+impl Config {
+    // true at the walls, if there are any
+    pub fn wall_mask(&self) -> Option<Arc<Array2<bool>>> {
+        self.walls.as_ref().map(|walls| {
+            walls
+                .mask(self.dims, &self.transform)
+                .expect("walls were validated")
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let (h, w) = self.dims;
+
+        if h < 2 || w < 2 {
+            return Err(ConfigError::DimsTooSmall(self.dims));
+        }
+        if !self.kernel.is_valid() {
+            return Err(ConfigError::InvalidKernel);
+        }
+        if self.palette.is_some() && self.colormap != Colormap::default() {
+            return Err(ConfigError::PaletteAndColormap);
+        }
+        // any narrower and a cell's neighbors on either side would be the same cell
+        let side = self.kernel.side();
+        if self.boundary == Boundary::Periodic && (h < side || w < side) {
+            return Err(ConfigError::PeriodicTooSmall(self.dims));
+        }
+        if self.hotspots > h * w {
+            return Err(ConfigError::TooManyHotspots {
+                hotspots: self.hotspots,
+                cells: h * w,
+            });
+        }
+        if !(0.0..=1.0).contains(&self.heat) {
+            return Err(ConfigError::InvalidHeat(self.heat));
+        }
+        if self.size_factor == 0 {
+            return Err(ConfigError::ZeroSizeFactor);
+        }
+        if self.steps_per_frame == 0 {
+            return Err(ConfigError::ZeroStepsPerFrame);
+        }
+        if self.frame_every == 0 {
+            return Err(ConfigError::ZeroFrameEvery);
+        }
+        if self.checkpoint_every == Some(0) {
+            return Err(ConfigError::ZeroCheckpointEvery);
+        }
+        if !self.video.is_valid() {
+            return Err(ConfigError::InvalidVideo);
+        }
+        if !self.entropy_estimator.is_valid() {
+            return Err(ConfigError::InvalidEstimator);
+        }
+        if self.threads == Some(0) {
+            return Err(ConfigError::ZeroThreads);
+        }
+        if let Some(speed) = &self.auto_speed {
+            if !speed.is_valid(self.steps_per_frame) {
+                return Err(ConfigError::InvalidAutoSpeed);
+            }
+        }
+        if self.power_save.is_some_and(|save| !save.is_valid()) {
+            return Err(ConfigError::InvalidPowerSave);
+        }
+        // a single cell has no entropy to show
+        if self.local_entropy_window < 2 {
+            return Err(ConfigError::ZeroWindow);
+        }
+        if let Some(bath) = &self.bath {
+            if !(0.0..=1.0).contains(&bath.coupling) || bath.temperature < 0.0 {
+                return Err(ConfigError::InvalidBath);
+            }
+        }
+        if !(self.active_threshold >= 0.0 && self.active_threshold.is_finite()) {
+            return Err(ConfigError::InvalidActiveThreshold(self.active_threshold));
+        }
+        if let Some(max) = self.max_energy {
+            if !(max > 0.0 && max.is_finite()) {
+                return Err(ConfigError::InvalidMaxEnergy(max));
+            }
+        }
+        if !self.normalization.is_valid() {
+            return Err(ConfigError::InvalidNormalization);
+        }
+        if let Some(focus) = &self.focus {
+            if focus.center.0 >= h || focus.center.1 >= w {
+                return Err(ConfigError::FocusOffBoard(focus.center));
+            }
+        }
+        if !(self.click_energy >= 0.0 && self.click_energy.is_finite()) {
+            return Err(ConfigError::InvalidClickEnergy(self.click_energy));
+        }
+        if !(0.0..=1.0).contains(&self.reset_rate) {
+            return Err(ConfigError::InvalidResetRate(self.reset_rate));
+        }
+        if !(self.thermal_noise >= 0.0 && self.thermal_noise.is_finite()) {
+            return Err(ConfigError::InvalidThermalNoise(self.thermal_noise));
+        }
+        if let InitialCondition::Placed(hotspots) = &self.initial {
+            for spot in hotspots {
+                if spot.y >= h || spot.x >= w {
+                    return Err(ConfigError::HotspotOffBoard((spot.x, spot.y)));
+                }
+                if !(spot.energy >= 0.0 && spot.energy.is_finite()) {
+                    return Err(ConfigError::InvalidHotspotEnergy(spot.energy));
+                }
+            }
+        }
+        if let InitialCondition::Image {
+            energy: Some(energy),
+            ..
+        } = self.initial
+        {
+            if !(energy > 0.0 && energy.is_finite()) {
+                return Err(ConfigError::InvalidImageEnergy(energy));
+            }
+        }
+        let initial_spectrum = match &self.initial {
+            InitialCondition::Field { spectrum, contrast } if *contrast >= 0.0 => Some(spectrum),
+            InitialCondition::Field { .. } => return Err(ConfigError::InvalidSpectrum),
+            _ => None,
+        };
+        if initial_spectrum
+            .into_iter()
+            .chain(&self.noise_spectrum)
+            .any(|spectrum| !spectrum.is_valid())
+        {
+            return Err(ConfigError::InvalidSpectrum);
+        }
+        if let Some(levy) = &self.levy {
+            if !(0.0..=1.0).contains(&levy.fraction) || levy.exponent <= 0.0 {
+                return Err(ConfigError::InvalidLevy);
+            }
+        }
+        if let Some(waiting) = &self.waiting {
+            if waiting.exponent <= 0.0 {
+                return Err(ConfigError::InvalidWaiting);
+            }
+        }
+        if let Some(traps) = &self.traps {
+            if !(0.0..=1.0).contains(&traps.fraction) || !(0.0..=1.0).contains(&traps.density) {
+                return Err(ConfigError::InvalidTraps);
+            }
+        }
+        for source in &self.sources {
+            let period = match source.path {
+                SourcePath::Circle { period, .. } | SourcePath::Line { period, .. } => period,
+                _ => 1.0,
+            };
+            if period <= 0.0 {
+                return Err(ConfigError::InvalidSource);
+            }
+        }
+        let cell_sets = self.fixed_sources.iter().map(|s| &s.cells);
+        let wall_cells = self.walls.iter().flat_map(|walls| &walls.cells);
+        if !cell_sets
+            .chain(&self.sinks)
+            .chain(wall_cells)
+            .all(|cells| cells.fits(self.dims))
+        {
+            return Err(ConfigError::CellsOffBoard);
+        }
+        if self
+            .fixed_sources
+            .iter()
+            .any(|s| !(s.power >= 0.0 && s.power.is_finite()))
+        {
+            return Err(ConfigError::InvalidFixedSource);
+        }
+        if let Some(walls) = &self.walls {
+            walls
+                .mask(self.dims, &self.transform)
+                .map_err(ConfigError::BadWalls)?;
+            // a jump has no path to block
+            if self.levy.is_some() {
+                return Err(ConfigError::WallsUnsupported("levy"));
+            }
+        }
+        if let Some(Tracer::Square { center, .. }) = self.tracer {
+            if center.0 >= h || center.1 >= w {
+                return Err(ConfigError::TracerOffBoard(center));
+            }
+        }
+        if let Some(event) = self.events.iter().find(|e| !e.action.is_valid()) {
+            return Err(ConfigError::InvalidEvent(event.step));
+        }
+        let unsupported = match self.scheme {
+            Scheme::Scatter => vec![],
+            Scheme::Gather => vec![
+                ("levy", self.levy.is_some()),
+                ("drift", self.drift.is_some()),
+                ("capacity", !matches!(self.capacity, HeatCapacity::Uniform)),
+                ("kernel", self.kernel != Kernel::default()),
+                ("tracer", self.tracer.is_some()),
+            ],
+            // drift is the advection it solves for; the rest need drawn weights
+            Scheme::Lbm => vec![
+                ("levy", self.levy.is_some()),
+                ("capacity", !matches!(self.capacity, HeatCapacity::Uniform)),
+                ("kernel", self.kernel != Kernel::default()),
+                ("tracer", self.tracer.is_some()),
+                ("active_threshold", self.active_threshold > 0.0),
+                ("walls", self.walls.is_some()),
+                ("shadow", self.shadow),
+            ],
+        };
+        if let Some((name, _)) = unsupported.iter().find(|(_, used)| *used) {
+            return Err(ConfigError::SchemeUnsupported(self.scheme, name));
+        }
+        // the shadow is the kernel's expectation, with the bath and clamp; what
+        // else changes the board would show up in it as a difference
+        let unshadowed = [
+            ("levy", self.levy.is_some()),
+            ("active_threshold", self.active_threshold > 0.0),
+            ("sources", !self.sources.is_empty()),
+            ("fixed_sources", !self.fixed_sources.is_empty()),
+            ("sinks", !self.sinks.is_empty()),
+            ("traps", self.traps.is_some()),
+            ("waiting", self.waiting.is_some()),
+            ("phase_change", self.phase_change.is_some()),
+            ("reset_rate", self.reset_rate > 0.0),
+            ("thermal_noise", self.thermal_noise > 0.0),
+            ("events", !self.events.is_empty()),
+        ];
+        if let Some((name, _)) = unshadowed.iter().find(|(_, used)| self.shadow && *used) {
+            return Err(ConfigError::ShadowUnsupported(name));
+        }
+        if !self.transform.is_valid() {
+            return Err(ConfigError::InvalidRotation(self.transform.rotate));
+        }
+        if let Some(drift) = &self.drift {
+            let fast = iproduct!(0..h, 0..w).any(|cell| {
+                let (vi, vj) = drift.velocity(cell, self.dims, 0.0);
+                vi.abs() > 1.0 || vj.abs() > 1.0
+            });
+            let unstable = matches!(drift, Drift::Buoyancy { max_speed, .. } if !(0.0..=1.0).contains(max_speed));
+            if fast || unstable {
+                return Err(ConfigError::InvalidDrift);
+            }
+        }
+        let capacity_ok = match &self.capacity {
+            HeatCapacity::Uniform => true,
+            HeatCapacity::Halves { left, right } => *left > 0.0 && *right > 0.0,
+            HeatCapacity::Field(c) => c.dim() == self.dims && c.iter().all(|&c| c > 0.0),
+        };
+        if !capacity_ok {
+            return Err(ConfigError::InvalidCapacity);
+        }
+        if let Some(phase) = &self.phase_change {
+            if phase.latent_heat < 0.0 {
+                return Err(ConfigError::InvalidPhaseChange);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    DimsTooSmall((usize, usize)),
+    TooManyHotspots {
+        hotspots: usize,
+        cells: usize,
+    },
+    ZeroSizeFactor,
+    ZeroWindow,
+    InvalidBath,
+    InvalidHeat(f64),
+    InvalidResetRate(f64),
+    InvalidThermalNoise(f64),
+    InvalidSpectrum,
+    InvalidKernel,
+    PaletteAndColormap,
+    InvalidLevy,
+    InvalidWaiting,
+    InvalidTraps,
+    BadTrapMask(String),
+    InvalidSource,
+    InvalidFixedSource,
+    CellsOffBoard,
+    BadWalls(String),
+    WallsUnsupported(&'static str),
+    InvalidDrift,
+    InvalidCapacity,
+    InvalidPhaseChange,
+    InvalidRotation(u32),
+    InvalidEvent(usize),
+    BadInitialField(String),
+    DimsChanged {
+        from: (usize, usize),
+        to: (usize, usize),
+    },
+    SchemeUnsupported(Scheme, &'static str),
+    ShadowUnsupported(&'static str),
+    InvalidActiveThreshold(f64),
+    InvalidMaxEnergy(f64),
+    InvalidNormalization,
+    FocusOffBoard((usize, usize)),
+    InvalidClickEnergy(f64),
+    HotspotOffBoard((usize, usize)),
+    InvalidHotspotEnergy(f64),
+    InvalidImageEnergy(f64),
+    TracerOffBoard((usize, usize)),
+    PeriodicTooSmall((usize, usize)),
+    ZeroStepsPerFrame,
+    ZeroThreads,
+    ZeroFrameEvery,
+    ZeroCheckpointEvery,
+    InvalidVideo,
+    InvalidEstimator,
+    InvalidAutoSpeed,
+    InvalidPowerSave,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::DimsTooSmall((h, w)) => {
+                write!(f, "dims must be at least 2x2, got {}x{}", h, w)
+            }
+            ConfigError::TooManyHotspots { hotspots, cells } => {
+                write!(f, "{} hotspots don't fit on {} cells", hotspots, cells)
+            }
+            ConfigError::ZeroSizeFactor => write!(f, "size_factor must be at least 1"),
+            ConfigError::ZeroWindow => write!(f, "local_entropy_window must be at least 2"),
+            ConfigError::InvalidBath => write!(
+                f,
+                "bath coupling must be within [0, 1] and its temperature non-negative"
+            ),
+            ConfigError::InvalidHeat(heat) => {
+                write!(f, "heat must be within [0, 1], got {}", heat)
+            }
+            ConfigError::InvalidResetRate(rate) => {
+                write!(f, "reset_rate must be within [0, 1], got {}", rate)
+            }
+            ConfigError::InvalidSpectrum => write!(
+                f,
+                "power_law exponents and field contrasts must be non-negative, gaussian lengths positive"
+            ),
+            ConfigError::InvalidThermalNoise(sigma) => write!(
+                f,
+                "thermal_noise must be a non-negative number, got {}",
+                sigma
+            ),
+            ConfigError::InvalidKernel => write!(
+                f,
+                "kernel radius must be from 1 to {}, and a matrix square with an odd side, non-negative weights and a positive center",
+                MAX_KERNEL_RADIUS
+            ),
+            ConfigError::PaletteAndColormap => {
+                write!(f, "palette and colormap can't both be set")
+            }
+            ConfigError::InvalidLevy => write!(
+                f,
+                "levy fraction must be within [0, 1] and its exponent positive"
+            ),
+            ConfigError::InvalidWaiting => write!(f, "waiting exponent must be positive"),
+            ConfigError::InvalidTraps => {
+                write!(f, "trap fraction and density must be within [0, 1]")
+            }
+            ConfigError::BadTrapMask(e) => write!(f, "couldn't use trap mask {}", e),
+            ConfigError::InvalidSource => write!(f, "source path periods must be positive"),
+            ConfigError::InvalidFixedSource => {
+                write!(f, "fixed source power must be non-negative")
+            }
+            ConfigError::CellsOffBoard => {
+                write!(f, "a fixed source, sink or wall has cells off the board")
+            }
+            ConfigError::BadWalls(e) => write!(f, "couldn't use wall image {}", e),
+            ConfigError::WallsUnsupported(name) => write!(f, "walls don't work with {}", name),
+            ConfigError::InvalidDrift => {
+                write!(f, "drift velocity components must be within [-1, 1]")
+            }
+            ConfigError::InvalidCapacity => write!(
+                f,
+                "heat capacities must be positive, with one per cell for a field"
+            ),
+            ConfigError::InvalidPhaseChange => write!(f, "latent_heat can't be negative"),
+            ConfigError::InvalidRotation(r) => {
+                write!(f, "rotate must be 0, 90, 180 or 270, got {}", r)
+            }
+            ConfigError::InvalidEvent(step) => write!(
+                f,
+                "the event at step {} needs a positive scale and finite shift",
+                step
+            ),
+            ConfigError::BadInitialField(e) => write!(f, "couldn't load initial field {}", e),
+            ConfigError::DimsChanged { from, to } => write!(
+                f,
+                "a running simulation can't change dims from {:?} to {:?}",
+                from, to
+            ),
+            ConfigError::SchemeUnsupported(scheme, name) => write!(
+                f,
+                "the {} scheme doesn't support {}",
+                format!("{:?}", scheme).to_lowercase(),
+                name
+            ),
+            ConfigError::ShadowUnsupported(name) => {
+                write!(f, "the shadow field doesn't model {}", name)
+            }
+            ConfigError::ZeroStepsPerFrame => write!(f, "steps_per_frame must be at least 1"),
+            ConfigError::ZeroThreads => write!(f, "threads must be at least 1"),
+            ConfigError::ZeroFrameEvery => write!(f, "frame_every must be at least 1"),
+            ConfigError::ZeroCheckpointEvery => write!(f, "checkpoint_every must be at least 1"),
+            ConfigError::InvalidEstimator => write!(
+                f,
+                "entropy_estimator needs at least 1 bin, k of at least 1, or a positive alpha"
+            ),
+            ConfigError::InvalidVideo => write!(
+                f,
+                "video every and fps must be at least 1, and seconds positive"
+            ),
+            ConfigError::InvalidPowerSave => write!(
+                f,
+                "power_save needs a positive settled_change and a positive, finite idle_fps"
+            ),
+            ConfigError::InvalidAutoSpeed => write!(
+                f,
+                "auto_speed needs a positive target_change and max_steps_per_frame of at least steps_per_frame"
+            ),
+            ConfigError::InvalidActiveThreshold(t) => write!(
+                f,
+                "active_threshold must be finite and non-negative, got {}",
+                t
+            ),
+            ConfigError::InvalidMaxEnergy(max) => {
+                write!(f, "max_energy must be finite and positive, got {}", max)
+            }
+            ConfigError::InvalidNormalization => {
+                write!(f, "normalization percentile must be within [0, 100]")
+            }
+            ConfigError::PeriodicTooSmall((h, w)) => write!(
+                f,
+                "a periodic board must be at least as big as its kernel, 3x3 by default, got {}x{}",
+                h, w
+            ),
+            ConfigError::FocusOffBoard((i, j)) => {
+                write!(f, "the focus center ({}, {}) is off the board", i, j)
+            }
+            ConfigError::InvalidClickEnergy(e) => {
+                write!(f, "click_energy must be finite and non-negative, got {}", e)
+            }
+            ConfigError::HotspotOffBoard((x, y)) => {
+                write!(f, "the hotspot at x {}, y {} is off the board", x, y)
+            }
+            ConfigError::InvalidHotspotEnergy(energy) => {
+                write!(f, "hotspot energy must be non-negative, got {}", energy)
+            }
+            ConfigError::InvalidImageEnergy(energy) => {
+                write!(f, "the initial image's energy must be positive, got {}", energy)
+            }
+            ConfigError::TracerOffBoard((i, j)) => {
+                write!(f, "the tracer center ({}, {}) is off the board", i, j)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// the largest window side most GL drivers can texture
+pub const MAX_WINDOW_SIDE: usize = 16384;
+
+// settings that still run, but not as written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigWarning {
+    // the window would be too big, so size_factor is lowered to `fitted`
+    SizeFactorReduced { size_factor: usize, fitted: usize },
+    // even one pixel per cell is too big, so the board is cropped to the window
+    BoardCropped { width: usize, height: usize },
+}
+
+impl fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigWarning::SizeFactorReduced {
+                size_factor,
+                fitted,
+            } => write!(
+                f,
+                "size_factor {} makes the window wider than {} pixels, using {}",
+                size_factor, MAX_WINDOW_SIDE, fitted
+            ),
+            ConfigWarning::BoardCropped { width, height } => write!(
+                f,
+                "a {}x{} pixel window is too big to show, the board is cropped to {}x{}",
+                width, height, MAX_WINDOW_SIDE, MAX_WINDOW_SIDE
+            ),
+        }
+    }
+}
+
+// how the window is laid out: `size_factor` pixels per cell, with the marginal
+// strips `margin` pixels wide to the left of and above the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowLayout {
+    pub size_factor: usize,
+    pub margin: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Config {
+    // the energy the board is drawn up to, for a run that started from `initial`
+    pub fn max_energy_for(&self, initial: &Array2<f64>) -> f64 {
+        self.max_energy
+            .unwrap_or_else(|| palette::auto_max_energy(initial))
+    }
+
+    // the top of the ramp as it moves under the normalization
+    pub fn scale_for(&self, initial: &Array2<f64>) -> Scale {
+        Scale::new(self.normalization, self.max_energy_for(initial))
+    }
+
+    pub fn window_layout(&self) -> (WindowLayout, Vec<ConfigWarning>) {
+        let (h, w) = self.dims;
+        let margin = if self.marginals {
+            self.marginal_size.min(MAX_WINDOW_SIDE / 2)
+        } else {
+            0
+        };
+        let room = MAX_WINDOW_SIDE - margin;
+
+        let mut warnings = Vec::new();
+        let fitted = self.size_factor.min(room / h.max(w)).max(1);
+        if fitted < self.size_factor {
+            warnings.push(ConfigWarning::SizeFactorReduced {
+                size_factor: self.size_factor,
+                fitted,
+            });
+        }
+        let (width, height) = (w * fitted + margin, h * fitted + margin);
+        if width > MAX_WINDOW_SIDE || height > MAX_WINDOW_SIDE {
+            warnings.push(ConfigWarning::BoardCropped { width, height });
+        }
+
+        let layout = WindowLayout {
+            size_factor: fitted,
+            margin,
+            width: width.min(MAX_WINDOW_SIDE),
+            height: height.min(MAX_WINDOW_SIDE),
+        };
+        (layout, warnings)
+    }
+}
+
+// what an image pixel shows; offsets count away from the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Board { row: usize, col: usize },
+    ColumnMarginal { col: usize, offset: usize },
+    RowMarginal { row: usize, offset: usize },
+    Blank,
+}
+
+impl WindowLayout {
+    // image rows start at the bottom, so the column strip is above the board;
+    // anything outside the board and strips is padding, and cells past the edge
+    // of the image are cropped
+    #[inline(always)]
+    pub fn region(&self, x: usize, y: usize, (h, w): (usize, usize)) -> Region {
+        let (board_w, board_h) = (w * self.size_factor, h * self.size_factor);
+        let on_rows = y < board_h;
+        let on_cols = x >= self.margin && x - self.margin < board_w;
+
+        match (on_cols, on_rows) {
+            (true, true) => Region::Board {
+                row: y / self.size_factor,
+                col: (x - self.margin) / self.size_factor,
+            },
+            (true, false) if y - board_h < self.margin => Region::ColumnMarginal {
+                col: (x - self.margin) / self.size_factor,
+                offset: y - board_h,
+            },
+            (false, true) if x < self.margin => Region::RowMarginal {
+                row: y / self.size_factor,
+                offset: self.margin - 1 - x,
+            },
+            _ => Region::Blank,
+        }
+    }
+}
+
+fn default_assertion_tolerance() -> f64 {
+    1e-9
+}
+
+fn default_click_energy() -> f64 {
+    10.0
+}
+
+fn default_steps_per_frame() -> usize {
+    1
+}
+
+fn default_marginal_size() -> usize {
+    40
+}
+
+fn default_frame_every() -> usize {
+    1
+}
+
+fn default_checkpoint_path() -> String {
+    "checkpoint.bin".to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoSettings {
+    // a .gif is encoded directly, anything else, e.g. .mp4, by piping to ffmpeg
+    #[serde(default = "default_video_path")]
+    pub path: String,
+    // keep every this many drawn frames
+    #[serde(default = "default_frame_every")]
+    pub every: usize,
+    #[serde(default = "default_video_fps")]
+    pub fps: u32,
+    // stop by itself once the video is this long
+    #[serde(default)]
+    pub seconds: Option<f64>,
+    // record from the first frame instead of waiting for V
+    #[serde(default)]
+    pub start: bool,
+}
+
+impl Default for VideoSettings {
+    fn default() -> Self {
+        Self {
+            path: default_video_path(),
+            every: default_frame_every(),
+            fps: default_video_fps(),
+            seconds: None,
+            start: false,
+        }
+    }
+}
+
+impl VideoSettings {
+    pub fn is_valid(&self) -> bool {
+        self.every > 0 && self.fps > 0 && self.seconds.is_none_or(|s| s > 0.0 && s.is_finite())
+    }
+}
+
+fn default_video_path() -> String {
+    "entropy.gif".to_string()
+}
+
+fn default_video_fps() -> u32 {
+    30
+}
+
+fn default_history_memory_mb() -> usize {
+    64
+}
+
+fn default_local_entropy_window() -> usize {
+    5
+}
+
+fn default_kl_threshold() -> f64 {
+    0.01
+}
+
+fn default_mi_bins() -> usize {
+    16
+}
+
+fn default_current_warmup() -> usize {
+    500
+}
+
+fn default_archive() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DriftOrWind {
+    Wind((f64, f64)),
+    Drift(Drift),
+}
+
+fn drift_or_wind<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Drift>, D::Error> {
+    Ok(match Option::<DriftOrWind>::deserialize(deserializer)? {
+        // velocities are (row, col), and rows count up the window
+        Some(DriftOrWind::Wind((dx, dy))) => Some(Drift::Uniform { velocity: (dy, dx) }),
+        Some(DriftOrWind::Drift(drift)) => Some(drift),
+        None => None,
+    })
+}
+
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "entropy")
+}
+
+// where files the program writes on its own go: the platform data directory
+// (e.g. ~/.local/share/entropy), or the current directory if there is none
+pub fn data_dir() -> PathBuf {
+    project_dirs()
+        .map(|dirs| dirs.data_dir().to_path_buf())
+        .filter(|dir| fs::create_dir_all(dir).is_ok())
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+// config.json in the current directory, else in the platform config directory
+// (e.g. ~/.config/entropy, %APPDATA%\entropy\config, ~/Library/Application Support/entropy)
+pub fn config_path() -> Option<PathBuf> {
+    let local = PathBuf::from("config.json");
+    if local.is_file() {
+        return Some(local);
+    }
+
+    project_dirs()
+        .map(|dirs| dirs.config_dir().join("config.json"))
+        .filter(|path| path.is_file())
+}
+
+pub fn get_config() -> Config {
+    let path = config_path()
+        .expect("Couldn't find config.json in the current directory or the config directory");
+    read_config(&path)
+}
+
+pub fn read_config(path: &Path) -> Config {
+    let file = File::open(path).expect("Couldn't open the config file");
+    let reader = BufReader::new(file);
+
+    let config: Config = serde_json::from_reader(reader).expect("Couldn't parse json");
+
+    config
+}
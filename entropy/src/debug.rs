@@ -0,0 +1,119 @@
+use crate::config::data_dir;
+use crate::model::{board_time_step, traced_time_step, SimRng, StepReport};
+use crate::Config;
+use ndarray::Array2;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+// everything needed to replay a single bad step: seed the rng with `seed`,
+// move it to `word_pos`, then step `lagged_board` once with `config`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StepDump {
+    pub step: usize,
+    pub seed: u64,
+    pub word_pos: u128,
+    pub cell: (usize, usize),
+    pub value: f64,
+    pub weights: Option<Array2<f64>>,
+    pub lagged_board: Array2<f64>,
+    pub config: Config,
+}
+
+impl StepDump {
+    // the board the dumped step came out with, bit for bit
+    pub fn replay(&self) -> Array2<f64> {
+        let mut board = self.lagged_board.clone();
+        board_time_step(
+            &mut Array2::zeros(board.dim()),
+            &mut board,
+            &self.config,
+            &mut rng_at(self.seed, self.word_pos),
+            None,
+        );
+        board
+    }
+}
+
+pub fn rng_at(seed: u64, word_pos: u128) -> SimRng {
+    let mut rng = SimRng::seed_from_u64(seed);
+    rng.set_word_pos(word_pos);
+    rng
+}
+
+// first cell that is NaN or negative, in row-major order
+pub fn find_anomaly(board: &Array2<f64>) -> Option<((usize, usize), f64)> {
+    board
+        .indexed_iter()
+        .find(|(_, &e)| e.is_nan() || e < 0.0)
+        .map(|(cell, &e)| (cell, e))
+}
+
+pub fn write_dump(path: &Path, dump: &StepDump) {
+    let file = File::create(path).expect("Couldn't create debug dump file");
+    serde_json::to_writer(BufWriter::new(file), dump).expect("Couldn't write debug dump");
+}
+
+pub fn read_dump(path: &Path) -> StepDump {
+    let file = File::open(path).expect("Couldn't open debug dump file");
+    serde_json::from_reader(BufReader::new(file)).expect("Couldn't read debug dump")
+}
+
+// a time step that dumps and exits as soon as the board goes bad
+pub fn checked_time_step(
+    board: &mut Array2<f64>,
+    lagged_board: &mut Array2<f64>,
+    tracer: Option<&mut Array2<f64>>,
+    config: &Config,
+    rng: &mut SimRng,
+    seed: u64,
+    step: usize,
+) -> StepReport {
+    let word_pos = rng.get_word_pos();
+    let before = lagged_board.clone();
+
+    let report = traced_time_step(board, lagged_board, tracer, config, rng, None);
+
+    if let Some((cell, value)) = find_anomaly(lagged_board) {
+        // replay the step to capture the weights the offending cell drew
+        let mut replay = before.clone();
+        let weights = board_time_step(
+            &mut Array2::zeros(before.dim()),
+            &mut replay,
+            config,
+            &mut rng_at(seed, word_pos),
+            Some(cell),
+        )
+        .watched;
+
+        let dump = StepDump {
+            step,
+            seed,
+            word_pos,
+            cell,
+            value,
+            weights,
+            lagged_board: before,
+            config: config.clone(),
+        };
+        let path = config
+            .debug_dump_path
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| data_dir().join("debug_dump.json"));
+        write_dump(&path, &dump);
+
+        eprintln!(
+            "step {}: cell {:?} has energy {}, dumped to {}",
+            step,
+            cell,
+            value,
+            path.display()
+        );
+        std::process::exit(1);
+    }
+
+    report
+}
@@ -0,0 +1,51 @@
+use crate::color::energy_to_rgb;
+use ndarray::Array2;
+use ndarray_npy::read_npy;
+use std::path::Path;
+
+pub fn run(a: &Path, b: &Path, top: usize, heatmap: Option<&Path>) {
+    let a: Array2<f64> = read_npy(a).expect("Couldn't read first snapshot");
+    let b: Array2<f64> = read_npy(b).expect("Couldn't read second snapshot");
+
+    if a.dim() != b.dim() {
+        eprintln!("shape mismatch: {:?} vs {:?}", a.dim(), b.dim());
+        std::process::exit(1);
+    }
+
+    let diff = (&a - &b).mapv(f64::abs);
+    let max = diff.fold(0.0_f64, |m, &d| m.max(d));
+    let mean = diff.mean().unwrap_or(0.0);
+
+    println!("max abs diff:  {}", max);
+    println!("mean abs diff: {}", mean);
+
+    let mut cells: Vec<_> = diff.indexed_iter().collect();
+    cells.sort_by(|x, y| y.1.total_cmp(x.1));
+
+    println!("largest discrepancies:");
+    for ((i, j), d) in cells.into_iter().take(top) {
+        println!(
+            "  ({}, {})  a = {}  b = {}  |a - b| = {}",
+            i,
+            j,
+            a[[i, j]],
+            b[[i, j]],
+            d
+        );
+    }
+
+    if let Some(path) = heatmap {
+        write_heatmap(&diff, max, path);
+    }
+}
+
+fn write_heatmap(diff: &Array2<f64>, max: f64, path: &Path) {
+    let (h, w) = diff.dim();
+    let max = if max > 0.0 { max } else { 1.0 };
+
+    let img = image::RgbImage::from_fn(w as u32, h as u32, |x, y| {
+        let c = energy_to_rgb(diff[[y as usize, x as usize]], max);
+        image::Rgb([c.r, c.g, c.b])
+    });
+    img.save(path).expect("Couldn't write heatmap image");
+}
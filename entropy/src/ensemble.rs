@@ -0,0 +1,140 @@
+use crate::estimator::EntropyEstimator;
+use crate::series::SeriesFormat;
+use crate::{par_runs, Config, ConfigError, SimRng};
+use ndarray::Array2;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// independent runs of one config, sampled every `every` steps, with the mean
+// entropy and variance of each sample given a bootstrap confidence band
+pub struct EnsembleExperiment {
+    pub runs: usize,
+    pub steps: usize,
+    pub every: usize,
+    // resamples of the runs drawn per band
+    pub resamples: usize,
+    // the fraction of the resampled means a band covers, e.g. 0.95
+    pub confidence: f64,
+}
+
+// a mean over the runs, and the band the bootstrap puts around it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Band {
+    pub mean: f64,
+    pub lo: f64,
+    pub hi: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EnsembleRow {
+    pub step: usize,
+    pub entropy: Band,
+    pub variance: Band,
+}
+
+pub struct Ensemble {
+    pub runs: usize,
+    pub rows: Vec<EnsembleRow>,
+}
+
+impl EnsembleExperiment {
+    pub fn run(&self, config: &Config) -> Result<Ensemble, ConfigError> {
+        let every = self.every.max(1);
+        let estimator = config.entropy_estimator;
+        let sample = |board: &Array2<f64>| (estimator.estimate(board), board.var(0.0));
+
+        // each run's (entropy, variance) at steps 0, every, 2 * every, ...
+        let samples: Vec<Vec<(f64, f64)>> = par_runs(config, self.runs.max(1))?
+            .map(|mut sim| {
+                let mut samples = vec![sample(sim.board())];
+                for step in 1..=self.steps {
+                    sim.step();
+                    if step % every == 0 {
+                        samples.push(sample(sim.board()));
+                    }
+                }
+                samples
+            })
+            .collect();
+
+        // seeded runs get the same bands every time
+        let mut rng = SimRng::seed_from_u64(config.seed.unwrap_or_else(rand::random));
+        let rows = (0..samples[0].len())
+            .map(|i| {
+                let entropies: Vec<f64> = samples.iter().map(|s| s[i].0).collect();
+                let variances: Vec<f64> = samples.iter().map(|s| s[i].1).collect();
+                EnsembleRow {
+                    step: i * every,
+                    entropy: bootstrap(&entropies, self.resamples, self.confidence, &mut rng),
+                    variance: bootstrap(&variances, self.resamples, self.confidence, &mut rng),
+                }
+            })
+            .collect();
+
+        Ok(Ensemble {
+            runs: samples.len(),
+            rows,
+        })
+    }
+}
+
+// the mean of `values` and the percentile interval of the means of `resamples`
+// resamples of them, drawn with replacement
+pub fn bootstrap(values: &[f64], resamples: usize, confidence: f64, rng: &mut SimRng) -> Band {
+    let n = values.len();
+    let mean = values.iter().sum::<f64>() / n as f64;
+    if n < 2 || resamples == 0 {
+        return Band {
+            mean,
+            lo: mean,
+            hi: mean,
+        };
+    }
+
+    let mut means: Vec<f64> = (0..resamples)
+        .map(|_| (0..n).map(|_| values[rng.gen_range(0..n)]).sum::<f64>() / n as f64)
+        .collect();
+    means.sort_by(f64::total_cmp);
+    let tail = (1.0 - confidence) / 2.0;
+    let at = |q: f64| means[((q * resamples as f64) as usize).min(resamples - 1)];
+    Band {
+        mean,
+        lo: at(tail),
+        hi: at(1.0 - tail),
+    }
+}
+
+impl Ensemble {
+    pub const HEADER: &'static str =
+        "step,entropy_mean,entropy_lo,entropy_hi,variance_mean,variance_lo,variance_hi";
+
+    // as CSV or, for .json and .jsonl, JSON lines, as the per-step stats are
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let format = SeriesFormat::from_path(path);
+        let mut out = BufWriter::new(File::create(path)?);
+        if format == SeriesFormat::Csv {
+            writeln!(out, "{}", Self::HEADER)?;
+        }
+        for row in &self.rows {
+            match format {
+                SeriesFormat::Csv => {
+                    let (s, v) = (row.entropy, row.variance);
+                    writeln!(
+                        out,
+                        "{},{},{},{},{},{},{}",
+                        row.step, s.mean, s.lo, s.hi, v.mean, v.lo, v.hi
+                    )?
+                }
+                SeriesFormat::Json => {
+                    serde_json::to_writer(&mut out, row)?;
+                    writeln!(out)?;
+                }
+            }
+        }
+        out.flush()
+    }
+}
@@ -0,0 +1,196 @@
+use crate::stats::shannon_entropy;
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+
+// a way of putting a number on the board's entropy, in nats; they disagree by
+// design, so the run's curves depend on which one the config picks
+pub trait EntropyEstimator {
+    fn estimate(&self, board: &Array2<f64>) -> f64;
+
+    // the most it can come to on a board of `cells` cells, if it's bounded
+    fn max_entropy(&self, cells: usize) -> Option<f64>;
+}
+
+// the board normalized into a distribution over its cells, as everywhere else
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shannon;
+
+// the plug-in estimate over a histogram of the cell energies, from zero to the
+// highest, so it says how evenly energy levels are used rather than cells
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Binned {
+    pub bins: usize,
+}
+
+// Kozachenko-Leonenko: the differential entropy of the cell energies as a
+// sample, from each one's distance to its k-th nearest neighbor, with no bins
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NearestNeighbor {
+    pub k: usize,
+}
+
+// Renyi entropy of order alpha of the normalized board; alpha below 1 weighs
+// the quiet cells up and above 1 the hot ones, and 1 is Shannon
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Renyi {
+    pub alpha: f64,
+}
+
+impl EntropyEstimator for Shannon {
+    fn estimate(&self, board: &Array2<f64>) -> f64 {
+        shannon_entropy(board)
+    }
+
+    fn max_entropy(&self, cells: usize) -> Option<f64> {
+        Some((cells as f64).ln())
+    }
+}
+
+impl EntropyEstimator for Binned {
+    fn estimate(&self, board: &Array2<f64>) -> f64 {
+        let max = board.iter().copied().fold(0.0, f64::max);
+        let n = board.len() as f64;
+        if max <= 0.0 || n == 0.0 {
+            return 0.0;
+        }
+        let mut counts = vec![0usize; self.bins];
+        for &e in board {
+            let k = ((e.max(0.0) / max) * self.bins as f64) as usize;
+            counts[k.min(self.bins - 1)] += 1;
+        }
+        counts
+            .iter()
+            .filter(|&&c| c > 0)
+            .map(|&c| {
+                let p = c as f64 / n;
+                -p * p.ln()
+            })
+            .sum()
+    }
+
+    fn max_entropy(&self, _: usize) -> Option<f64> {
+        Some((self.bins as f64).ln())
+    }
+}
+
+impl EntropyEstimator for NearestNeighbor {
+    fn estimate(&self, board: &Array2<f64>) -> f64 {
+        let mut sample: Vec<f64> = board.iter().copied().collect();
+        let n = sample.len();
+        if n <= self.k {
+            return 0.0;
+        }
+        sample.sort_by(f64::total_cmp);
+
+        // in one dimension the k nearest neighbors of a sorted value lie within
+        // the k on either side of it
+        let mut log_distances = 0.0;
+        for i in 0..n {
+            let (lo, hi) = (i.saturating_sub(self.k), (i + self.k).min(n - 1));
+            let mut distances: Vec<f64> = (lo..=hi)
+                .filter(|&j| j != i)
+                .map(|j| (sample[j] - sample[i]).abs())
+                .collect();
+            distances.sort_by(f64::total_cmp);
+            // tied values would put a log of zero in the sum
+            log_distances += distances[self.k - 1].max(f64::MIN_POSITIVE).ln();
+        }
+        let n = n as f64;
+        digamma(n) - digamma(self.k as f64) + 2f64.ln() + log_distances / n
+    }
+
+    fn max_entropy(&self, _: usize) -> Option<f64> {
+        None
+    }
+}
+
+impl EntropyEstimator for Renyi {
+    fn estimate(&self, board: &Array2<f64>) -> f64 {
+        if (self.alpha - 1.0).abs() < 1e-12 {
+            return shannon_entropy(board);
+        }
+        let total = board.sum();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        let sum: f64 = board
+            .iter()
+            .filter(|&&e| e > 0.0)
+            .map(|&e| (e / total).powf(self.alpha))
+            .sum();
+        sum.ln() / (1.0 - self.alpha)
+    }
+
+    fn max_entropy(&self, cells: usize) -> Option<f64> {
+        Some((cells as f64).ln())
+    }
+}
+
+// the estimator a config picks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Estimator {
+    #[default]
+    Shannon,
+    Binned {
+        #[serde(default = "default_bins")]
+        bins: usize,
+    },
+    NearestNeighbor {
+        #[serde(default = "default_k")]
+        k: usize,
+    },
+    Renyi {
+        alpha: f64,
+    },
+}
+
+fn default_bins() -> usize {
+    64
+}
+
+fn default_k() -> usize {
+    1
+}
+
+impl Estimator {
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            Estimator::Shannon => true,
+            Estimator::Binned { bins } => bins > 0,
+            Estimator::NearestNeighbor { k } => k > 0,
+            Estimator::Renyi { alpha } => alpha > 0.0 && alpha.is_finite(),
+        }
+    }
+}
+
+impl EntropyEstimator for Estimator {
+    fn estimate(&self, board: &Array2<f64>) -> f64 {
+        match *self {
+            Estimator::Shannon => Shannon.estimate(board),
+            Estimator::Binned { bins } => Binned { bins }.estimate(board),
+            Estimator::NearestNeighbor { k } => NearestNeighbor { k }.estimate(board),
+            Estimator::Renyi { alpha } => Renyi { alpha }.estimate(board),
+        }
+    }
+
+    fn max_entropy(&self, cells: usize) -> Option<f64> {
+        match *self {
+            Estimator::Shannon => Shannon.max_entropy(cells),
+            Estimator::Binned { bins } => Binned { bins }.max_entropy(cells),
+            Estimator::NearestNeighbor { k } => NearestNeighbor { k }.max_entropy(cells),
+            Estimator::Renyi { alpha } => Renyi { alpha }.max_entropy(cells),
+        }
+    }
+}
+
+// the digamma function, by its recurrence up past 6 and then its asymptotic series
+fn digamma(mut x: f64) -> f64 {
+    let mut result = 0.0;
+    while x < 6.0 {
+        result -= 1.0 / x;
+        x += 1.0;
+    }
+    let f = 1.0 / (x * x);
+    result + x.ln() - 0.5 / x - f * (1.0 / 12.0 - f * (1.0 / 120.0 - f * (1.0 / 252.0)))
+}
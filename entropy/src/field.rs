@@ -0,0 +1,139 @@
+use crate::SimRng;
+use ndarray::Array2;
+use num_complex::Complex64;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// the power spectrum of a gaussian random field, as a function of the
+// wavenumber k in cycles per cell
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Spectrum {
+    // P(k) ~ k^-exponent; 0 is white noise, 2 to 4 look like clouds
+    PowerLaw { exponent: f64 },
+    // P(k) ~ exp(-(2 pi k length)^2 / 2), smooth blobs about `length` cells across
+    Gaussian { length: f64 },
+}
+
+impl Spectrum {
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            Spectrum::PowerLaw { exponent } => exponent.is_finite() && exponent >= 0.0,
+            Spectrum::Gaussian { length } => length.is_finite() && length > 0.0,
+        }
+    }
+
+    fn power(&self, k: f64) -> f64 {
+        match *self {
+            Spectrum::PowerLaw { exponent } => k.powf(-exponent),
+            Spectrum::Gaussian { length } => {
+                (-(std::f64::consts::TAU * k * length).powi(2) / 2.0).exp()
+            }
+        }
+    }
+}
+
+// a zero-mean, unit-variance field with the given spectrum: white noise
+// filtered in Fourier space. it's made on a power-of-two grid at least twice the
+// board each way and cropped, so correlations don't wrap around the edges
+pub fn gaussian_field(
+    (h, w): (usize, usize),
+    spectrum: &Spectrum,
+    rng: &mut SimRng,
+) -> Array2<f64> {
+    let (ph, pw) = ((2 * h).next_power_of_two(), (2 * w).next_power_of_two());
+    let mut grid: Vec<Complex64> = (0..ph * pw)
+        .map(|_| Complex64::new(standard_normal(rng), 0.0))
+        .collect();
+
+    fft2(&mut grid, (ph, pw), false);
+    // the frequency of index k on an n-point axis, in cycles per cell
+    let frequency = |k: usize, n: usize| {
+        let k = if k <= n / 2 {
+            k as f64
+        } else {
+            k as f64 - n as f64
+        };
+        k / n as f64
+    };
+    for (index, z) in grid.iter_mut().enumerate() {
+        let (ky, kx) = (frequency(index / pw, ph), frequency(index % pw, pw));
+        let k = ky.hypot(kx);
+        // no constant term, so the field averages to zero
+        *z *= if k == 0.0 {
+            0.0
+        } else {
+            spectrum.power(k).sqrt()
+        };
+    }
+    fft2(&mut grid, (ph, pw), true);
+
+    let mut field = Array2::from_shape_fn((h, w), |(i, j)| grid[i * pw + j].re);
+    let mean = field.mean().unwrap_or(0.0);
+    let std = field.std(0.0);
+    field.mapv_inplace(|x| if std > 0.0 { (x - mean) / std } else { 0.0 });
+    field
+}
+
+// Box-Muller
+pub fn standard_normal(rng: &mut SimRng) -> f64 {
+    let (u, v): (f64, f64) = (rng.gen(), rng.gen());
+    (-2.0 * (1.0 - u).ln()).sqrt() * (std::f64::consts::TAU * v).cos()
+}
+
+// the 2d transform of a row-major grid, as transforms of every row then every
+// column; both sides must be powers of two
+fn fft2(grid: &mut [Complex64], (h, w): (usize, usize), inverse: bool) {
+    for row in grid.chunks_mut(w) {
+        fft(row, inverse);
+    }
+    let mut column = vec![Complex64::default(); h];
+    for j in 0..w {
+        for i in 0..h {
+            column[i] = grid[i * w + j];
+        }
+        fft(&mut column, inverse);
+        for i in 0..h {
+            grid[i * w + j] = column[i];
+        }
+    }
+}
+
+// iterative radix-2 Cooley-Tukey; the inverse is scaled by 1/n
+fn fft(x: &mut [Complex64], inverse: bool) {
+    let n = x.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            x.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let step = Complex64::from_polar(1.0, sign * std::f64::consts::TAU / len as f64);
+        for start in (0..n).step_by(len) {
+            let mut twiddle = Complex64::new(1.0, 0.0);
+            for k in 0..len / 2 {
+                let (a, b) = (x[start + k], x[start + k + len / 2] * twiddle);
+                x[start + k] = a + b;
+                x[start + k + len / 2] = a - b;
+                twiddle *= step;
+            }
+        }
+        len <<= 1;
+    }
+
+    if inverse {
+        for z in x.iter_mut() {
+            *z /= n as f64;
+        }
+    }
+}
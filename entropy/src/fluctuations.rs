@@ -0,0 +1,95 @@
+use crate::{stats, Config, ConfigError, Simulation};
+use ndarray::Array2;
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+pub struct FluctuationExperiment {
+    pub trajectories: usize,
+    pub steps: usize,
+    // steps run from the uniform field before trajectories start, to reach the
+    // stochastic steady state
+    pub burn_in: usize,
+    pub bins: usize,
+}
+
+// histogram of the entropy change over short trajectories, alongside the counts
+// of the opposite change, which is what the Crooks relation
+// P(dS) / P(-dS) = exp(dS) is about
+pub struct FluctuationHistogram {
+    pub centers: Vec<f64>,
+    pub forward: Vec<usize>,
+    pub reverse: Vec<usize>,
+}
+
+impl FluctuationExperiment {
+    pub fn run(&self, config: &Config) -> Result<FluctuationHistogram, ConfigError> {
+        let uniform = Array2::from_elem(config.dims, 1.0);
+
+        let mut equilibrium = Simulation::from_board(config.clone(), uniform)?;
+        for _ in 0..self.burn_in {
+            equilibrium.step();
+        }
+        let start = equilibrium.board().clone();
+        let base_seed = equilibrium.seed();
+
+        let changes: Vec<f64> = (0..self.trajectories)
+            .into_par_iter()
+            .map(|k| {
+                let mut config = config.clone();
+                config.seed = Some(base_seed.wrapping_add(k as u64 + 1));
+                let mut sim = Simulation::from_board(config, start.clone())
+                    .expect("config was already validated");
+
+                let before = stats::shannon_entropy(sim.board());
+                for _ in 0..self.steps {
+                    sim.step();
+                }
+                stats::shannon_entropy(sim.board()) - before
+            })
+            .collect();
+
+        Ok(histogram(&changes, self.bins.max(1)))
+    }
+}
+
+// symmetric around zero so that bins i and bins - 1 - i hold opposite changes
+fn histogram(changes: &[f64], bins: usize) -> FluctuationHistogram {
+    let max = changes.iter().fold(0.0_f64, |m, &c| m.max(c.abs()));
+    let max = if max > 0.0 { max } else { 1.0 };
+    let width = 2.0 * max / bins as f64;
+
+    let mut forward = vec![0; bins];
+    for &c in changes {
+        let bin = ((c + max) / width) as usize;
+        forward[bin.min(bins - 1)] += 1;
+    }
+
+    let reverse = forward.iter().rev().copied().collect();
+    let centers = (0..bins).map(|i| -max + (i as f64 + 0.5) * width).collect();
+
+    FluctuationHistogram {
+        centers,
+        forward,
+        reverse,
+    }
+}
+
+impl FluctuationHistogram {
+    pub fn write_csv(&self, path: &Path) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "delta_s,forward,reverse,log_ratio")?;
+
+        for ((c, f), r) in self.centers.iter().zip(&self.forward).zip(&self.reverse) {
+            let log_ratio = if *f > 0 && *r > 0 {
+                (*f as f64 / *r as f64).ln().to_string()
+            } else {
+                String::new()
+            };
+            writeln!(out, "{},{},{},{}", c, f, r, log_ratio)?;
+        }
+
+        out.flush()
+    }
+}
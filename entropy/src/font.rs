@@ -0,0 +1,174 @@
+use pixel_canvas::{image::XY, Color, Image};
+
+pub const GLYPH_W: usize = 3;
+pub const GLYPH_H: usize = 5;
+
+// each glyph is 5 rows of 3 bits, top row first, most significant bit on the left
+fn glyph(c: char) -> [u8; GLYPH_H] {
+    match c.to_ascii_uppercase() {
+        '0' => [7, 5, 5, 5, 7],
+        '1' => [2, 6, 2, 2, 7],
+        '2' => [7, 1, 7, 4, 7],
+        '3' => [7, 1, 7, 1, 7],
+        '4' => [5, 5, 7, 1, 1],
+        '5' => [7, 4, 7, 1, 7],
+        '6' => [7, 4, 7, 5, 7],
+        '7' => [7, 1, 1, 1, 1],
+        '8' => [7, 5, 7, 5, 7],
+        '9' => [7, 5, 7, 1, 7],
+        'A' => [2, 5, 7, 5, 5],
+        'B' => [6, 5, 6, 5, 6],
+        'C' => [3, 4, 4, 4, 3],
+        'D' => [6, 5, 5, 5, 6],
+        'E' => [7, 4, 6, 4, 7],
+        'F' => [7, 4, 6, 4, 4],
+        'G' => [3, 4, 5, 5, 3],
+        'H' => [5, 5, 7, 5, 5],
+        'I' => [7, 2, 2, 2, 7],
+        'J' => [1, 1, 1, 5, 2],
+        'K' => [5, 5, 6, 5, 5],
+        'L' => [4, 4, 4, 4, 7],
+        'M' => [5, 7, 7, 5, 5],
+        'N' => [6, 5, 5, 5, 5],
+        'O' => [2, 5, 5, 5, 2],
+        'P' => [6, 5, 6, 4, 4],
+        'Q' => [2, 5, 5, 6, 3],
+        'R' => [6, 5, 6, 5, 5],
+        'S' => [3, 4, 2, 1, 6],
+        'T' => [7, 2, 2, 2, 2],
+        'U' => [5, 5, 5, 5, 7],
+        'V' => [5, 5, 5, 5, 2],
+        'W' => [5, 5, 7, 7, 5],
+        'X' => [5, 5, 2, 5, 5],
+        'Y' => [5, 5, 2, 2, 2],
+        'Z' => [7, 1, 2, 4, 7],
+        ' ' => [0, 0, 0, 0, 0],
+        '.' => [0, 0, 0, 0, 2],
+        ',' => [0, 0, 0, 2, 4],
+        '-' => [0, 0, 7, 0, 0],
+        '+' => [0, 2, 7, 2, 0],
+        ':' => [0, 2, 0, 2, 0],
+        '(' => [1, 2, 2, 2, 1],
+        ')' => [4, 2, 2, 2, 4],
+        '[' => [3, 2, 2, 2, 3],
+        ']' => [6, 2, 2, 2, 6],
+        '/' => [1, 1, 2, 4, 4],
+        '%' => [5, 1, 2, 4, 5],
+        '=' => [0, 7, 0, 7, 0],
+        '_' => [0, 0, 0, 0, 7],
+        '<' => [1, 2, 4, 2, 1],
+        '>' => [4, 2, 1, 2, 4],
+        '!' => [2, 2, 2, 0, 2],
+        '#' => [5, 7, 5, 7, 5],
+        '*' => [0, 5, 2, 5, 0],
+        '|' => [2, 2, 2, 2, 2],
+        '\'' => [2, 2, 0, 0, 0],
+        _ => [7, 1, 2, 0, 2], // '?'
+    }
+}
+
+pub fn text_width(text: &str, scale: usize) -> usize {
+    let n = text.chars().count();
+    if n == 0 {
+        0
+    } else {
+        (n * (GLYPH_W + 1) - 1) * scale
+    }
+}
+
+// anything text can be drawn on, with rows counting up from the bottom
+pub trait Surface {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    fn set(&mut self, x: usize, y: usize, color: Color);
+}
+
+impl Surface for Image {
+    fn width(&self) -> usize {
+        Image::width(self)
+    }
+
+    fn height(&self) -> usize {
+        Image::height(self)
+    }
+
+    fn set(&mut self, x: usize, y: usize, color: Color) {
+        self[XY(x, y)] = color;
+    }
+}
+
+// image rows count down from the top, so they are flipped
+impl Surface for image::RgbImage {
+    fn width(&self) -> usize {
+        self.dimensions().0 as usize
+    }
+
+    fn height(&self) -> usize {
+        self.dimensions().1 as usize
+    }
+
+    fn set(&mut self, x: usize, y: usize, color: Color) {
+        let flipped = (self.dimensions().1 as usize - 1 - y) as u32;
+        self.put_pixel(x as u32, flipped, image::Rgb([color.r, color.g, color.b]));
+    }
+}
+
+#[inline(always)]
+fn put(image: &mut impl Surface, x: usize, y: usize, color: Color) {
+    if x < image.width() && y < image.height() {
+        image.set(x, y, color);
+    }
+}
+
+// (x, y) is the lower left corner of the text
+pub fn draw_text(
+    image: &mut impl Surface,
+    x: usize,
+    y: usize,
+    text: &str,
+    scale: usize,
+    color: Color,
+) {
+    for (n, c) in text.chars().enumerate() {
+        let gx = x + n * (GLYPH_W + 1) * scale;
+        for (r, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_W {
+                if bits & (1 << (GLYPH_W - 1 - col)) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        put(
+                            image,
+                            gx + col * scale + dx,
+                            y + (GLYPH_H - 1 - r) * scale + dy,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+// text on a dark box, shifted so the whole box stays inside the image
+pub fn draw_label(image: &mut impl Surface, x: usize, y: usize, text: &str, scale: usize) {
+    let pad = scale;
+    let box_w = text_width(text, scale) + 2 * pad;
+    let box_h = GLYPH_H * scale + 2 * pad;
+    let x = x.min(image.width().saturating_sub(box_w));
+    let y = y.min(image.height().saturating_sub(box_h));
+
+    for by in y..y + box_h {
+        for bx in x..x + box_w {
+            put(image, bx, by, Color { r: 0, g: 0, b: 0 });
+        }
+    }
+
+    let white = Color {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+    draw_text(image, x + pad, y + pad, text, scale, white);
+}
@@ -0,0 +1,166 @@
+use crate::Simulation;
+use serde_json::Value;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+// every file we write starts with a header line "<kind> <major>.<minor>.<patch>";
+// files of an older major version have to go through `migrate` first
+pub const STATE_KIND: &str = "entropy-state";
+pub const STATE_VERSION: Version = Version(1, 0, 0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version(pub u32, pub u32, pub u32);
+
+impl Version {
+    fn parse(s: &str) -> Option<Version> {
+        let mut parts = s.trim().split('.').map(|p| p.parse().ok());
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(Some(a)), Some(Some(b)), Some(Some(c)), None) => Some(Version(a, b, c)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+#[derive(Debug)]
+pub enum FormatError {
+    Io(io::Error),
+    BadHeader(String),
+    WrongKind {
+        expected: &'static str,
+        found: String,
+    },
+    NeedsMigration(Version),
+    TooNew(Version),
+    Parse(serde_json::Error),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Io(e) => write!(f, "{}", e),
+            FormatError::BadHeader(h) => write!(f, "unrecognized header {:?}", h),
+            FormatError::WrongKind { expected, found } => {
+                write!(f, "expected a {} file, found {}", expected, found)
+            }
+            FormatError::NeedsMigration(v) => write!(
+                f,
+                "file is version {}, run `entropy migrate` to upgrade it to {}",
+                v, STATE_VERSION
+            ),
+            FormatError::TooNew(v) => write!(
+                f,
+                "file is version {}, this build only reads up to {}",
+                v, STATE_VERSION
+            ),
+            FormatError::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+impl From<io::Error> for FormatError {
+    fn from(e: io::Error) -> Self {
+        FormatError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for FormatError {
+    fn from(e: serde_json::Error) -> Self {
+        FormatError::Parse(e)
+    }
+}
+
+pub fn write_state(path: &Path, sim: &Simulation) -> Result<(), FormatError> {
+    let mut file = io::BufWriter::new(File::create(path)?);
+    writeln!(file, "{} {}", STATE_KIND, STATE_VERSION)?;
+    serde_json::to_writer(&mut file, sim)?;
+    file.flush()?;
+    Ok(())
+}
+
+// written beside `path` and renamed over it, so a crash partway leaves the last
+// checkpoint whole
+pub fn write_checkpoint(path: &Path, sim: &Simulation) -> Result<(), FormatError> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = Path::new(&partial);
+    write_state(partial, sim)?;
+    File::open(partial)?.sync_all()?;
+    fs::rename(partial, path)?;
+    Ok(())
+}
+
+pub fn read_state(path: &Path) -> Result<Simulation, FormatError> {
+    let (version, payload) = read_versioned(path)?;
+
+    if version.0 < STATE_VERSION.0 {
+        return Err(FormatError::NeedsMigration(version));
+    }
+
+    Ok(serde_json::from_value(payload)?)
+}
+
+// upgrades a state file to the current version, returning the version it had
+pub fn migrate(input: &Path, output: &Path) -> Result<Version, FormatError> {
+    let (version, mut payload) = read_versioned(input)?;
+
+    for (major, step) in MIGRATIONS {
+        if version.0 <= *major {
+            payload = step(payload);
+        }
+    }
+
+    let mut out = Vec::new();
+    writeln!(out, "{} {}", STATE_KIND, STATE_VERSION)?;
+    serde_json::to_writer(&mut out, &payload)?;
+    fs::write(output, out)?;
+
+    Ok(version)
+}
+
+// each (n, step) turns a payload of major version n into one of version n + 1
+type Migration = fn(Value) -> Value;
+const MIGRATIONS: &[(u32, Migration)] = &[
+    // 0.x files are a bare serialized Simulation without a header
+    (0, |payload| payload),
+];
+
+fn read_versioned(path: &Path) -> Result<(Version, Value), FormatError> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut header = String::new();
+    reader.read_line(&mut header)?;
+
+    if header.trim_start().starts_with('{') {
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest)?;
+        return Ok((Version(0, 0, 0), serde_json::from_str(&(header + &rest))?));
+    }
+
+    let (kind, version) = header
+        .trim_end()
+        .split_once(' ')
+        .ok_or_else(|| FormatError::BadHeader(header.clone()))?;
+    let version = Version::parse(version).ok_or_else(|| FormatError::BadHeader(header.clone()))?;
+
+    if kind != STATE_KIND {
+        return Err(FormatError::WrongKind {
+            expected: STATE_KIND,
+            found: kind.to_string(),
+        });
+    }
+    if version.0 > STATE_VERSION.0 {
+        return Err(FormatError::TooNew(version));
+    }
+
+    Ok((version, serde_json::from_reader(reader)?))
+}
@@ -0,0 +1,63 @@
+use ndarray::Array2;
+use std::collections::VecDeque;
+
+// the most recent boards, oldest first, bounded by a frame count
+pub struct History {
+    frames: VecDeque<Array2<f64>>,
+    capacity: usize,
+}
+
+impl History {
+    // frames are counted with the array header each one carries, and the deque
+    // only grows as they come, so small boards don't reserve the whole cap
+    pub fn with_memory_cap(dims: (usize, usize), cap_mb: usize) -> Self {
+        let frame_bytes =
+            dims.0 * dims.1 * std::mem::size_of::<f64>() + std::mem::size_of::<Array2<f64>>();
+        let capacity = cap_mb * 1024 * 1024 / frame_bytes;
+
+        Self {
+            frames: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    // the most frames kept at once
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    pub fn push(&mut self, board: &Array2<f64>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.frames.len() == self.capacity {
+            // recycle the oldest frame's allocation
+            let mut oldest = self.frames.pop_front().unwrap();
+            oldest.clone_from(board);
+            self.frames.push_back(oldest);
+        } else {
+            self.frames.push_back(board.clone());
+        }
+    }
+
+    // `back` frames before the most recent one
+    pub fn get(&self, back: usize) -> Option<&Array2<f64>> {
+        self.frames
+            .len()
+            .checked_sub(back + 1)
+            .map(|i| &self.frames[i])
+    }
+}
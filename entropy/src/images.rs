@@ -0,0 +1,276 @@
+use crate::color::scaled_rgb;
+use crate::font;
+use clap::ValueEnum;
+use entropy::palette::{ColorScale, Palette};
+use image::{Rgb, RgbImage};
+use ndarray::Array2;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+// how colors are brought down to `levels` shades per channel after colormapping
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Dither {
+    // a 4x4 Bayer threshold; stable from frame to frame, so it suits animations
+    Ordered,
+    // error diffusion; smoother, but the pattern crawls between frames
+    FloydSteinberg,
+}
+
+// how video frames between two keyframes are made
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Interpolation {
+    // repeat the earlier keyframe
+    Hold,
+    // blend the two boards, weighted by how far between them the frame is
+    #[default]
+    Linear,
+}
+
+// the board a fraction t of the way from `a` to `b`
+pub fn interpolate(a: &Array2<f64>, b: &Array2<f64>, t: f64, mode: Interpolation) -> Array2<f64> {
+    match mode {
+        Interpolation::Hold => a.clone(),
+        Interpolation::Linear => a * (1.0 - t) + b * t,
+    }
+}
+
+// a board as an image, one pixel a cell, with row 0 at the bottom as in the window
+pub fn render(
+    board: &Array2<f64>,
+    palette: Option<&Palette>,
+    max_energy: f64,
+    scale: ColorScale,
+) -> RgbImage {
+    let (h, w) = board.dim();
+    RgbImage::from_fn(w as u32, h as u32, |x, y| {
+        let energy = board[[h - 1 - y as usize, x as usize]];
+        let c = scaled_rgb(palette, scale, energy, max_energy);
+        Rgb([c.r, c.g, c.b])
+    })
+}
+
+// what the window drew; the canvas counts rows up from the bottom, images down
+// from the top
+pub fn from_canvas(image: &pixel_canvas::Image) -> RgbImage {
+    let (w, h) = (image.width(), image.height());
+    RgbImage::from_fn(w as u32, h as u32, |x, y| {
+        let c = image[pixel_canvas::XY(x as usize, h - 1 - y as usize)];
+        Rgb([c.r, c.g, c.b])
+    })
+}
+
+// where an image came from, written into its tEXt chunks so it can be traced
+// back to a run once it's been shared
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Provenance {
+    pub config_hash: u64,
+    pub seed: u64,
+    pub step: usize,
+}
+
+impl Provenance {
+    pub fn at(self, step: usize) -> Self {
+        Self { step, ..self }
+    }
+
+    fn chunks(&self) -> [(&'static str, String); 4] {
+        [
+            ("Software", format!("entropy {}", env!("CARGO_PKG_VERSION"))),
+            ("Config hash", format!("{:016x}", self.config_hash)),
+            ("Seed", self.seed.to_string()),
+            ("Step", self.step.to_string()),
+        ]
+    }
+}
+
+// writes `image` as a PNG carrying `provenance`
+pub fn save(
+    image: &RgbImage,
+    path: &Path,
+    provenance: Provenance,
+) -> Result<(), png::EncodingError> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    for (keyword, text) in provenance.chunks() {
+        encoder.add_text_chunk(keyword.to_string(), text)?;
+    }
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(image.as_raw())?;
+    writer.finish()
+}
+
+// the longer side of a thumbnail
+const THUMBNAIL: u32 = 128;
+
+// a copy of `image` scaled to THUMBNAIL pixels on its longer side, keeping
+// cells square
+pub fn thumbnail(image: &RgbImage) -> RgbImage {
+    let (w, h) = image.dimensions();
+    let scale = THUMBNAIL as f64 / w.max(h).max(1) as f64;
+    let size = |side: u32| ((side as f64 * scale).round() as u32).max(1);
+    image::imageops::resize(
+        image,
+        size(w),
+        size(h),
+        image::imageops::FilterType::Nearest,
+    )
+}
+
+const BAYER: [[f32; 4]; 4] = [
+    [0.0, 8.0, 2.0, 10.0],
+    [12.0, 4.0, 14.0, 6.0],
+    [3.0, 11.0, 1.0, 9.0],
+    [15.0, 7.0, 13.0, 5.0],
+];
+
+// quantizes every channel to `levels` evenly spaced shades, at least 2
+pub fn dither(image: &mut RgbImage, method: Dither, levels: u8) {
+    let step = 255.0 / (levels.max(2) - 1) as f32;
+    let quantize = |v: f32| ((v / step).round().clamp(0.0, 255.0 / step) * step).round();
+    let (w, h) = image.dimensions();
+
+    match method {
+        Dither::Ordered => {
+            for (x, y, pixel) in image.enumerate_pixels_mut() {
+                // offsets within half a shade either way
+                let offset = (BAYER[y as usize % 4][x as usize % 4] + 0.5) / 16.0 - 0.5;
+                for c in pixel.0.iter_mut() {
+                    *c = quantize(*c as f32 + offset * step) as u8;
+                }
+            }
+        }
+        Dither::FloydSteinberg => {
+            let mut values: Vec<[f32; 3]> = image.pixels().map(|p| p.0.map(|c| c as f32)).collect();
+            let (w, h) = (w as usize, h as usize);
+            for y in 0..h {
+                for x in 0..w {
+                    let old = values[y * w + x];
+                    let new = old.map(quantize);
+                    values[y * w + x] = new;
+                    let spread = [(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)];
+                    for (dx, dy, weight) in spread {
+                        let (nx, ny) = (x as isize + dx, y + dy);
+                        if nx < 0 || nx as usize >= w || ny >= h {
+                            continue;
+                        }
+                        let neighbor = &mut values[ny * w + nx as usize];
+                        for k in 0..3 {
+                            neighbor[k] += (old[k] - new[k]) * weight / 16.0;
+                        }
+                    }
+                }
+            }
+            for (pixel, value) in image.pixels_mut().zip(values) {
+                pixel.0 = value.map(|c| c.clamp(0.0, 255.0) as u8);
+            }
+        }
+    }
+}
+
+// which frames go on a contact sheet, parsed from e.g. "cols=5 every=200"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MontageSpec {
+    pub cols: usize,
+    pub every: usize,
+}
+
+impl std::str::FromStr for MontageSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut spec = MontageSpec {
+            cols: 5,
+            every: 100,
+        };
+        for part in s.split([' ', ',']).filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, got {:?}", part))?;
+            let value: usize = value.parse().map_err(|e| format!("{:?}: {}", part, e))?;
+            match key {
+                "cols" => spec.cols = value,
+                "every" => spec.every = value,
+                _ => return Err(format!("unknown montage setting {:?}", key)),
+            }
+        }
+        if spec.cols == 0 || spec.every == 0 {
+            return Err("cols and every must be at least 1".to_string());
+        }
+        Ok(spec)
+    }
+}
+
+// the boards of a run every `spec.every` steps, laid out on a grid when it ends
+pub struct Montage {
+    spec: MontageSpec,
+    palette: Option<Palette>,
+    max_energy: f64,
+    scale: ColorScale,
+    tiles: Vec<(usize, Array2<f64>)>,
+}
+
+impl Montage {
+    // boards are drawn at least this many pixels on their longer side
+    const TILE: usize = 160;
+    const GAP: usize = 4;
+    // room above each tile for its label
+    const LABEL: usize = 16;
+
+    pub fn new(
+        spec: MontageSpec,
+        palette: Option<Palette>,
+        max_energy: f64,
+        scale: ColorScale,
+    ) -> Self {
+        Self {
+            spec,
+            palette,
+            max_energy,
+            scale,
+            tiles: Vec::new(),
+        }
+    }
+
+    pub fn observe(&mut self, step: usize, board: &Array2<f64>) {
+        if step.is_multiple_of(self.spec.every) {
+            self.tiles.push((step, board.clone()));
+        }
+    }
+
+    pub fn render(&self) -> RgbImage {
+        let (h, w) = self.tiles.first().map_or((1, 1), |(_, board)| board.dim());
+        let scale = (Self::TILE / h.max(w)).max(1);
+        let (tile_w, tile_h) = (w * scale, h * scale);
+        let cols = self.spec.cols.min(self.tiles.len()).max(1);
+        let rows = self.tiles.len().div_ceil(cols).max(1);
+        let cell_w = tile_w + Self::GAP;
+        let cell_h = tile_h + Self::LABEL + Self::GAP;
+
+        let mut sheet = RgbImage::from_pixel(
+            (cols * cell_w + Self::GAP) as u32,
+            (rows * cell_h + Self::GAP) as u32,
+            Rgb([32, 32, 32]),
+        );
+        for (k, (step, board)) in self.tiles.iter().enumerate() {
+            let (left, top) = (
+                Self::GAP + (k % cols) * cell_w,
+                Self::GAP + (k / cols) * cell_h,
+            );
+            let tile = image::imageops::resize(
+                &render(board, self.palette.as_ref(), self.max_energy, self.scale),
+                tile_w as u32,
+                tile_h as u32,
+                image::imageops::FilterType::Nearest,
+            );
+            image::imageops::replace(&mut sheet, &tile, left as i64, (top + Self::LABEL) as i64);
+
+            // the font counts rows up from the bottom
+            let label_y = sheet.height() as usize - top - Self::LABEL + 1;
+            font::draw_label(&mut sheet, left, label_y, &format!("STEP {}", step), 2);
+        }
+        sheet
+    }
+}
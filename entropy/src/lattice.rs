@@ -0,0 +1,140 @@
+use crate::model::Boundary;
+use crate::Config;
+use itertools::iproduct;
+use ndarray::Array2;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// the D2Q9 velocities as (row, col): at rest, the four axes, then the diagonals
+const VELOCITIES: [(isize, isize); 9] = [
+    (0, 0),
+    (0, 1),
+    (1, 0),
+    (0, -1),
+    (-1, 0),
+    (1, 1),
+    (1, -1),
+    (-1, -1),
+    (-1, 1),
+];
+const WEIGHTS: [f64; 9] = [
+    4.0 / 9.0,
+    1.0 / 9.0,
+    1.0 / 9.0,
+    1.0 / 9.0,
+    1.0 / 9.0,
+    1.0 / 36.0,
+    1.0 / 36.0,
+    1.0 / 36.0,
+    1.0 / 36.0,
+];
+// the velocity pointing the other way
+const OPPOSITE: [usize; 9] = [0, 3, 4, 1, 2, 7, 8, 5, 6];
+
+// the state of the lattice Boltzmann scheme: nine populations per cell, each
+// moving one cell a step along its velocity, whose sum is the board. collisions
+// relax them toward an equilibrium drifting with the local drift velocity, which
+// solves the advection-diffusion equation with no randomness at all
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lattice {
+    dims: (usize, usize),
+    populations: Vec<[f64; 9]>,
+}
+
+impl Lattice {
+    // populations at equilibrium with `board`
+    pub fn at_equilibrium(board: &Array2<f64>, config: &Config) -> Self {
+        let dims = board.dim();
+        let mean = board.mean().unwrap_or(0.0);
+        let populations = iproduct!(0..dims.0, 0..dims.1)
+            .map(|cell| equilibrium(board[cell], velocity(cell, board[cell] - mean, config)))
+            .collect();
+        Self { dims, populations }
+    }
+
+    // collides and streams once, leaving the new densities on `board`; returns
+    // the energy that streamed past an absorbing edge
+    pub fn step(&mut self, board: &mut Array2<f64>, config: &Config) -> f64 {
+        let (h, w) = self.dims;
+        let mean = board.mean().unwrap_or(0.0);
+        let min_rows = config.threads.map_or(1, |n| h.div_ceil(n));
+        // the diffusivity is (tau - 1/2) / 3, and this makes it heat / 3 as
+        // under the scatter scheme
+        let omega = 1.0 / (0.5 + config.heat);
+
+        self.populations
+            .par_chunks_mut(w)
+            .with_min_len(min_rows)
+            .enumerate()
+            .for_each(|(i, row)| {
+                for (j, f) in row.iter_mut().enumerate() {
+                    let rho = board[(i, j)];
+                    // energy added to or taken from the board since the last step,
+                    // by a bath, sources or noise, comes in at rest
+                    let missing = rho - f.iter().sum::<f64>();
+                    let feq = equilibrium(rho, velocity((i, j), rho - mean, config));
+                    for k in 0..9 {
+                        f[k] += WEIGHTS[k] * missing;
+                        f[k] += omega * (feq[k] - f[k]);
+                    }
+                }
+            });
+
+        let populations = &self.populations;
+        let mut next = vec![[0.0; 9]; h * w];
+        let escaped: f64 = next
+            .par_chunks_mut(w)
+            .with_min_len(min_rows)
+            .enumerate()
+            .map(|(i, row)| {
+                let mut escaped = 0.0;
+                for (j, f) in row.iter_mut().enumerate() {
+                    for (k, &(di, dj)) in VELOCITIES.iter().enumerate() {
+                        // the cell this population streams in from
+                        let source = config.boundary.neighbor((i, j), (-di, -dj), self.dims);
+                        f[k] = match source {
+                            Some((si, sj)) => populations[si * w + sj][k],
+                            // what ran into a wall comes back the way it went
+                            None if config.boundary == Boundary::Closed => {
+                                populations[i * w + j][OPPOSITE[k]]
+                            }
+                            None => {
+                                escaped += populations[i * w + j][OPPOSITE[k]];
+                                0.0
+                            }
+                        };
+                    }
+                }
+                escaped
+            })
+            .sum();
+
+        self.populations = next;
+        for (e, f) in board.iter_mut().zip(&self.populations) {
+            *e = f.iter().sum();
+        }
+        escaped
+    }
+}
+
+// the drift velocity at `cell`, scaled so energy moves as fast on average as
+// it does under the scatter scheme
+fn velocity(cell: (usize, usize), excess: f64, config: &Config) -> (f64, f64) {
+    match &config.drift {
+        Some(drift) => {
+            let (vi, vj) = drift.velocity(cell, config.dims, excess);
+            (2.0 * vi / 3.0, 2.0 * vj / 3.0)
+        }
+        None => (0.0, 0.0),
+    }
+}
+
+// populations with density `rho` moving at `u`, to first order in u; fast
+// drifts can make some of them negative
+fn equilibrium(rho: f64, (ui, uj): (f64, f64)) -> [f64; 9] {
+    let mut f = [0.0; 9];
+    for (k, &(di, dj)) in VELOCITIES.iter().enumerate() {
+        f[k] = WEIGHTS[k] * rho * (1.0 + 3.0 * (di as f64 * ui + dj as f64 * uj));
+    }
+    f
+}
@@ -0,0 +1,47 @@
+pub mod bench;
+pub mod board;
+pub mod config;
+pub mod debug;
+pub mod ensemble;
+pub mod estimator;
+pub mod field;
+pub mod fluctuations;
+pub mod format;
+pub mod history;
+pub mod lattice;
+pub mod model;
+pub mod palette;
+pub mod presets;
+pub mod randomize;
+pub mod recording;
+pub mod report;
+pub mod runs;
+pub mod script;
+pub mod series;
+pub mod session;
+pub mod simulation;
+pub mod soak;
+pub mod speed;
+pub mod stats;
+pub mod sweep;
+pub mod sync;
+pub mod timing;
+pub mod transform;
+pub mod upload;
+pub mod verify;
+pub mod vtk;
+pub mod zarr;
+
+pub use board::{Board, QuantaBoard, SparseBoard};
+pub use config::{
+    get_config, read_config, Assertions, Config, ConfigError, ConfigWarning, Display, Region,
+    WindowLayout,
+};
+pub use model::{
+    board_time_step, init_board, lattice_time_step, scratch_time_step, traced_time_step, Backend,
+    Bath, BathRegion, Boundary, Cells, ClampPolicy, Drift, FixedSource, Focus, Front, HeatCapacity,
+    Hotspot, InitialCondition, Kernel, KernelFlags, Levy, Mode, ParallelScratch, PhaseChange,
+    ResetScope, Scheme, SimRng, Source, SourcePath, StepReport, Tracer, TrapSites, Traps, Waiting,
+    Walls,
+};
+pub use simulation::{par_runs, Frame, Simulation, SimulationBuilder};
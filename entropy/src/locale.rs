@@ -0,0 +1,75 @@
+// number formatting for text drawn in the window. anything written to files or
+// stdout keeps Rust's own formatting so it parses the same everywhere
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    group: char,
+    decimal: char,
+}
+
+impl NumberFormat {
+    // from LC_ALL, LC_NUMERIC or LANG, in that order, e.g. "de_DE.UTF-8"
+    pub fn from_env() -> Self {
+        let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        let language = locale.split(['_', '.', '-']).next().unwrap_or("");
+
+        match language {
+            "de" | "es" | "it" | "nl" | "pt" | "da" | "id" | "tr" | "el" => Self {
+                group: '.',
+                decimal: ',',
+            },
+            "fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "nb" | "fi" | "uk" | "hu" => Self {
+                group: ' ',
+                decimal: ',',
+            },
+            _ => Self {
+                group: ',',
+                decimal: '.',
+            },
+        }
+    }
+
+    pub fn int(&self, n: usize) -> String {
+        self.grouped(&n.to_string())
+    }
+
+    pub fn float(&self, x: f64) -> String {
+        let text = x.to_string();
+        let (sign, text) = match text.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", text.as_str()),
+        };
+
+        match text.split_once('.') {
+            Some((whole, fraction)) => {
+                format!(
+                    "{}{}{}{}",
+                    sign,
+                    self.grouped(whole),
+                    self.decimal,
+                    fraction
+                )
+            }
+            // inf and NaN go through unchanged
+            None => format!("{}{}", sign, self.grouped(text)),
+        }
+    }
+
+    fn grouped(&self, digits: &str) -> String {
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return digits.to_string();
+        }
+
+        let mut out = String::new();
+        for (k, c) in digits.chars().enumerate() {
+            if k > 0 && (digits.len() - k).is_multiple_of(3) {
+                out.push(self.group);
+            }
+            out.push(c);
+        }
+        out
+    }
+}
@@ -1,215 +1,2625 @@
-use ndarray::{s, Array2};
+mod color;
+mod commands;
+mod diff;
+mod font;
+mod images;
+mod locale;
+#[cfg(unix)]
+mod profile;
+mod video;
+mod viewer;
+
+use clap::{Args, Parser, Subcommand};
+use color::{diverging_rgb, ramp_rgb, scaled_rgb};
+use commands::{Action, CommandPalette, Entry};
+use entropy::bench::{self, BenchReport};
+use entropy::ensemble::EnsembleExperiment;
+use entropy::estimator::EntropyEstimator;
+use entropy::fluctuations::FluctuationExperiment;
+use entropy::model::DEFAULT_FOCUS_RADIUS;
+use entropy::palette::{self, Blend, ColorScale, Palette, Stop};
+use entropy::recording::{self, RecordingWriter};
+use entropy::report::Report;
+use entropy::runs::{self, Manifest};
+use entropy::series::{SeriesRow, SeriesWriter};
+use entropy::session::{Key, Replay, Session};
+use entropy::soak::{self, SoakSpec};
+use entropy::speed::{Governor, Idler};
+use entropy::stats::{self, RunMetrics, StepMetrics};
+use entropy::sweep::{self, Sweep, SweepError};
+use entropy::sync::{Follower, Leader, Message};
+use entropy::timing::Histogram;
+use entropy::transform::Transform;
+use entropy::upload::{Target, Upload};
+use entropy::vtk::VtkSeries;
+use entropy::zarr::{ZarrSpec, ZarrWriter};
+use entropy::{
+    format, get_config, history, presets, read_config, verify, Boundary, Config, Display, Focus,
+    Frame, HeatCapacity, KernelFlags, Region, SimRng, Simulation,
+};
+use image::RgbImage;
+use images::{Dither, Interpolation, Montage, MontageSpec, Provenance};
+use ndarray::{Array1, Array2, Axis};
+use pixel_canvas::canvas::CanvasInfo;
+use pixel_canvas::input::glutin::event::{
+    ElementState, KeyboardInput, MouseButton, VirtualKeyCode,
+};
+use pixel_canvas::input::{Event, MouseState, WindowEvent};
 use pixel_canvas::{Canvas, Color};
-use rand::{rngs::ThreadRng, Rng};
-use serde::{Deserialize, Serialize};
-use std::iter::zip;
-use std::str;
-use std::{fs::File, io::BufReader};
+use rand::SeedableRng;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(Parser)]
+#[command(about = "A stochastic heat diffusion toy")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    overrides: Overrides,
+    /// Run a built-in scenario instead of config.json
+    #[arg(long)]
+    preset: Option<String>,
+    /// Save the keys pressed during the run, with the step of each, to this file
+    #[arg(long)]
+    record: Option<PathBuf>,
+    /// Replay a recorded session, using its seed
+    #[arg(long)]
+    replay: Option<PathBuf>,
+    /// Carry on from a checkpoint written by an earlier run, with its config
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["preset", "config", "replay", "lead", "follow"]
+    )]
+    resume: Option<PathBuf>,
+    /// Let other windows follow this one, listening on this address (e.g. 0.0.0.0:7878)
+    #[arg(long, value_name = "ADDR", conflicts_with = "follow")]
+    lead: Option<String>,
+    /// Mirror the window leading at this address, with its config and seed
+    #[arg(long, value_name = "ADDR")]
+    follow: Option<String>,
+    /// Write a grid of the run's boards when it ends, e.g. "cols=5 every=200"
+    #[arg(long, value_name = "SPEC")]
+    montage: Option<MontageSpec>,
+    #[arg(long, default_value = "montage.png", value_name = "PATH")]
+    montage_output: PathBuf,
+    /// Write each step's total energy, max cell energy, variance and entropy to
+    /// this file, as JSON lines if it ends in .json or .jsonl and CSV otherwise
+    #[arg(long, value_name = "PATH")]
+    stats_out: Option<PathBuf>,
+    /// Write an HTML page with the run's entropy curve, final frame, energy
+    /// budget and config to this file when it ends
+    #[arg(long, value_name = "PATH")]
+    report: Option<PathBuf>,
+    /// Upload the run's files when it ends, to s3://bucket/prefix, gs://... or
+    /// file:///dir, with a SHA256SUMS manifest: its archive and whatever --record,
+    /// --montage, --stats-out and --report wrote
+    #[arg(long, value_name = "URI")]
+    upload: Option<Target>,
+    /// Upload only the files with these names, e.g. manifest.json,report.html
+    #[arg(long, value_name = "NAMES", value_delimiter = ',', requires = "upload")]
+    upload_only: Vec<String>,
+    /// Times to retry a file that fails to upload
+    #[arg(long, default_value_t = 3, value_name = "N", requires = "upload")]
+    upload_retries: u32,
+    /// Step in a plain loop without opening a window, e.g. over ssh
+    #[arg(long)]
+    headless: bool,
+    /// Stop a headless run after this many steps instead of running until killed
+    #[arg(long, value_name = "N")]
+    steps: Option<usize>,
+    /// Don't print a row of stats per headless step
+    #[arg(long)]
+    quiet: bool,
+    /// Run headless, writing each step's board to stdout as raw RGB24 frames,
+    /// e.g. for `ffmpeg -f rawvideo -pix_fmt rgb24 -s WxH -i -`
+    #[arg(long, conflicts_with = "quiet")]
+    pipe_frames: bool,
+    /// Draw each cell of a piped frame as a square this many pixels a side
+    #[arg(long, default_value_t = 1, value_name = "N", requires = "pipe_frames")]
+    pipe_scale: u32,
+    /// Run at low priority on at most a couple of threads, and step slowly while
+    /// the window is in the background
+    #[arg(long)]
+    background: bool,
+    /// Run this many steps without a window under a sampling profiler
+    #[arg(long, value_name = "N")]
+    profile_run: Option<usize>,
+    /// Where --profile-run writes its collapsed stacks
+    #[arg(long, default_value = "profile.folded")]
+    profile_output: PathBuf,
+    /// Run without a window for a long time, checking the run's integrity as it
+    /// goes, e.g. "hours=24 check=1000 checkpoint=10 keep=3"
+    #[arg(long, value_name = "SPEC")]
+    soak: Option<SoakSpec>,
+    /// Where --soak writes its log and checkpoints
+    #[arg(long, default_value = "soak", value_name = "PATH")]
+    soak_dir: PathBuf,
+}
+
+// config fields that can be set from the command line, over whatever the
+// config file or preset says
+#[derive(Args)]
+struct Overrides {
+    /// Read the config from this file instead of config.json
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Board size as HEIGHTxWIDTH, e.g. 200x200
+    #[arg(long, global = true, value_name = "HxW", value_parser = parse_dims)]
+    dims: Option<(usize, usize)>,
+    #[arg(long, global = true)]
+    hotspots: Option<usize>,
+    #[arg(long, global = true)]
+    seed: Option<u64>,
+}
+
+impl Overrides {
+    fn config(&self) -> Config {
+        let mut config = match &self.config {
+            Some(path) => read_config(path),
+            None => get_config(),
+        };
+        self.apply(&mut config);
+        config
+    }
+
+    fn apply(&self, config: &mut Config) {
+        if let Some(dims) = self.dims {
+            config.dims = dims;
+        }
+        if let Some(hotspots) = self.hotspots {
+            config.hotspots = hotspots;
+        }
+        if let Some(seed) = self.seed {
+            config.seed = Some(seed);
+        }
+    }
+}
+
+fn parse_dims(s: &str) -> Result<(usize, usize), String> {
+    let (h, w) = s
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected HEIGHTxWIDTH, got {:?}", s))?;
+    let side = |n: &str| {
+        n.trim()
+            .parse::<usize>()
+            .map_err(|e| format!("{:?}: {}", n, e))
+    };
+    Ok((side(h)?, side(w)?))
+}
+
+fn parse_confidence(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(c) if c > 0.0 && c < 1.0 => Ok(c),
+        _ => Err(format!(
+            "expected a fraction between 0 and 1, e.g. 0.95, got {:?}",
+            s
+        )),
+    }
+}
+
+#[derive(Args)]
+struct ImageArgs {
+    /// Also write each keyframe as a PNG under png/, colored as in the window
+    #[arg(long)]
+    png: bool,
+    /// Reduce the PNGs to --dither-levels shades per channel, for e-ink or small palettes
+    #[arg(long, value_enum)]
+    dither: Option<Dither>,
+    #[arg(long, default_value_t = 4)]
+    dither_levels: u8,
+    /// Also write a numbered PNG sequence under video/ with this many frames per
+    /// keyframe, for encoding at a higher framerate than the keyframes
+    #[arg(long, value_name = "N")]
+    video_frames: Option<usize>,
+    /// How the video frames between keyframes are made
+    #[arg(long, value_enum, default_value_t)]
+    interpolate: Interpolation,
+}
+
+#[derive(Args)]
+struct DataArgs {
+    /// Also write each keyframe as VTK ImageData under vtk/, with a series.pvd
+    /// that opens them in ParaView as one time series
+    #[arg(long)]
+    vtk: bool,
+    /// Also write the keyframes to a Zarr store, frames.zarr, that xarray opens;
+    /// chunking and compression as in "chunks=16x128x128 level=5"
+    #[arg(long, value_name = "SPEC", num_args = 0..=1, default_missing_value = "")]
+    zarr: Option<ZarrSpec>,
+}
+
+impl ImageArgs {
+    fn render(
+        &self,
+        board: &Array2<f64>,
+        palette: Option<&Palette>,
+        max_energy: f64,
+        scale: ColorScale,
+    ) -> RgbImage {
+        let mut image = images::render(board, palette, max_energy, scale);
+        if let Some(method) = self.dither {
+            images::dither(&mut image, method, self.dither_levels);
+        }
+        image
+    }
+
+    fn write(
+        &self,
+        board: &Array2<f64>,
+        palette: Option<&Palette>,
+        max_energy: f64,
+        scale: ColorScale,
+        path: &Path,
+        provenance: Provenance,
+    ) {
+        images::save(
+            &self.render(board, palette, max_energy, scale),
+            path,
+            provenance,
+        )
+        .expect("Couldn't write keyframe image");
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compare two .npy board snapshots
+    Diff {
+        a: PathBuf,
+        b: PathBuf,
+        /// How many of the largest discrepancies to list
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        /// Write an image of |a - b| to this path
+        #[arg(long)]
+        heatmap: Option<PathBuf>,
+    },
+    /// Histogram entropy changes of short trajectories started from equilibrium
+    Fluctuations {
+        #[arg(long, default_value_t = 1000)]
+        trajectories: usize,
+        /// Length of each trajectory
+        #[arg(long, default_value_t = 10)]
+        steps: usize,
+        /// Steps run from the uniform field before sampling trajectories
+        #[arg(long, default_value_t = 200)]
+        burn_in: usize,
+        #[arg(long, default_value_t = 41)]
+        bins: usize,
+        #[arg(long, default_value = "fluctuations.csv")]
+        output: PathBuf,
+    },
+    /// Step independent runs of the config and write the mean entropy and
+    /// variance at each sampled step, with bootstrap confidence bands
+    Ensemble {
+        #[arg(long, default_value_t = 32)]
+        runs: usize,
+        #[arg(long, default_value_t = 1000)]
+        steps: usize,
+        /// Sample the runs every this many steps
+        #[arg(long, default_value_t = 10)]
+        every: usize,
+        /// Bootstrap resamples of the runs per band
+        #[arg(long, default_value_t = 1000)]
+        resamples: usize,
+        /// How much of the resampled means a band covers
+        #[arg(long, default_value_t = 0.95, value_parser = parse_confidence)]
+        confidence: f64,
+        /// Written as JSON lines if it ends in .json or .jsonl and CSV otherwise
+        #[arg(long, default_value = "ensemble.csv", value_name = "PATH")]
+        output: PathBuf,
+    },
+    /// Run one task's share of a sweep file, e.g. from a SLURM or Kubernetes job
+    /// array, writing a JSON line per point to OUTPUT/task-N.jsonl
+    Sweep {
+        sweep: PathBuf,
+        /// This task's number from 0, e.g. $SLURM_ARRAY_TASK_ID or $JOB_COMPLETION_INDEX
+        #[arg(long, default_value_t = 0, value_name = "N")]
+        task_index: usize,
+        /// How many tasks share the sweep
+        #[arg(long, default_value_t = 1, value_name = "M")]
+        num_tasks: usize,
+        #[arg(long, default_value = "sweep", value_name = "DIR")]
+        output: PathBuf,
+        /// Print the points this task would run, with their seeds, and stop
+        #[arg(long)]
+        list: bool,
+    },
+    /// Run a small seeded simulation headlessly and check it behaves
+    Verify,
+    /// Time each backend and scheme, write the results, and compare them with a
+    /// baseline written by an earlier run
+    Bench {
+        /// A report from an earlier run, e.g. old.json, to check for regressions against
+        #[arg(long, value_name = "PATH")]
+        baseline: Option<PathBuf>,
+        #[arg(long, default_value = "bench.json", value_name = "PATH")]
+        output: PathBuf,
+        /// Timed steps per case
+        #[arg(long, default_value_t = 200)]
+        steps: usize,
+        /// The slowdown, as a fraction, that counts as a regression
+        #[arg(long, default_value_t = 0.1)]
+        threshold: f64,
+        /// Only run cases whose name contains this
+        #[arg(long)]
+        filter: Option<String>,
+    },
+    /// Upgrade a saved state file to the current format version
+    Migrate {
+        input: PathBuf,
+        /// Where to write the upgraded file; defaults to overwriting the input
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Run headlessly and write keyframes, with a downsampled pyramid of each for
+    /// streaming viewers
+    Export {
+        #[arg(long, default_value_t = 1000)]
+        steps: usize,
+        /// Steps between keyframes
+        #[arg(long, default_value_t = 10)]
+        every: usize,
+        /// Pyramid levels including full resolution; by default, halvings down to 16 cells a side
+        #[arg(long)]
+        levels: Option<usize>,
+        #[arg(long, default_value = "trajectory.entropy")]
+        output: PathBuf,
+        #[command(flatten)]
+        images: ImageArgs,
+        #[command(flatten)]
+        data: DataArgs,
+    },
+    /// Serve a browser viewer for a recording made by `entropy export`
+    Viewer {
+        #[arg(default_value = "trajectory.entropy")]
+        recording: PathBuf,
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+    },
+    /// List archived runs
+    Runs {
+        #[command(subcommand)]
+        action: Option<RunsAction>,
+    },
+    /// List, show or save the color palettes a config can name
+    ///
+    /// Palettes are edited from the command line, not interactively: `save`
+    /// takes every color stop as an argument, and `show --preview` writes the
+    /// ramp to an image to check it. There's no live preview in the window;
+    /// run with the palette named in the config to see it on the board.
+    Palette {
+        #[command(subcommand)]
+        action: Option<PaletteAction>,
+    },
+}
+
+#[derive(Subcommand)]
+enum PaletteAction {
+    /// Print a palette's stops
+    Show {
+        name: String,
+        /// Also write the ramp as an image to this path
+        #[arg(long)]
+        preview: Option<PathBuf>,
+    },
+    /// Save a palette from its stops, replacing any of the same name
+    Save {
+        name: String,
+        /// Blend between stops in OkLab rather than sRGB, for smoother gradients
+        #[arg(long)]
+        oklab: bool,
+        /// Each stop as <at>=<color>, e.g. 0=#000000 0.6=#ff4400 1=#ffffcc
+        #[arg(required = true, num_args = 2..)]
+        stops: Vec<String>,
+    },
+}
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Config {
-    dims: (usize, usize),
-    hotspots: usize,
-    sleep_interval_ms: usize,
-    heat: f64,
-    size_factor: usize,
+#[derive(Subcommand)]
+enum RunsAction {
+    /// Print an archived run's manifest, and replay it or copy out its files
+    Show {
+        name: String,
+        /// Replay the run in a window with its config, seed and keys
+        #[arg(long)]
+        replay: bool,
+        /// Copy the manifest, final state and session into this directory
+        #[arg(long)]
+        export: Option<PathBuf>,
+    },
+}
+
+// what S cycles through when the shadow field is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldView {
+    Board,
+    Shadow,
+    Difference,
+}
+
+impl FieldView {
+    fn next(self) -> FieldView {
+        match self {
+            FieldView::Board => FieldView::Shadow,
+            FieldView::Shadow => FieldView::Difference,
+            FieldView::Difference => FieldView::Board,
+        }
+    }
+}
+
+// what I cycles through when there is a tracer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TracerView {
+    // tinting the board
+    Blend,
+    // the dye alone, in place of the board
+    Alone,
+    Hidden,
+}
+
+impl TracerView {
+    fn next(self) -> TracerView {
+        match self {
+            TracerView::Blend => TracerView::Alone,
+            TracerView::Alone => TracerView::Hidden,
+            TracerView::Hidden => TracerView::Blend,
+        }
+    }
+}
+
+struct InputState {
+    square: bool,
+    mouse: MouseState,
+    hovering: bool,
+    paused: bool,
+    // how many frames back from the latest one is being shown
+    history_offset: usize,
+    display: Display,
+    field_view: FieldView,
+    tracer_view: TracerView,
+    // quarter turns counterclockwise the board is drawn with
+    view_turns: u32,
+    // keys pressed since the last frame, applied by the render loop
+    keys: Vec<Key>,
+    // set by R, handled on the next frame
+    randomize: bool,
+    // a button pressed over the window, turned into a key on the next frame
+    click: Option<MouseButton>,
+    // the left button is held down with shift, putting down energy
+    depositing: bool,
+    // text shown over the board and how many more frames to show it for
+    notice: Option<(String, usize)>,
+    ctrl: bool,
+    shift: bool,
+    // what Ctrl+P offers, and the palette while it's open
+    commands: Vec<Entry>,
+    command_palette: Option<CommandPalette>,
+    // chosen from the palette, handled on the next frame
+    actions: Vec<Action>,
+    // whether the window has focus and is shown, which a background run steps
+    // slowly without
+    focused: bool,
+    minimized: bool,
+    // any key, click or mouse movement since the last frame, which wakes an idle
+    // window
+    touched: bool,
+}
+
+impl InputState {
+    fn new(display: Display, square: bool, commands: Vec<Entry>) -> Self {
+        Self {
+            square,
+            mouse: MouseState::new(),
+            hovering: false,
+            paused: false,
+            history_offset: 0,
+            display,
+            field_view: FieldView::Board,
+            tracer_view: TracerView::Blend,
+            view_turns: 0,
+            keys: Vec::new(),
+            randomize: false,
+            click: None,
+            depositing: false,
+            notice: None,
+            ctrl: false,
+            shift: false,
+            commands,
+            command_palette: None,
+            actions: Vec::new(),
+            focused: true,
+            minimized: false,
+            touched: false,
+        }
+    }
+
+    fn key(key: VirtualKeyCode) -> Option<Key> {
+        match key {
+            VirtualKeyCode::Space => Some(Key::TogglePause),
+            VirtualKeyCode::Left => Some(Key::Back),
+            VirtualKeyCode::Right => Some(Key::Forward),
+            VirtualKeyCode::D => Some(Key::NextDisplay),
+            VirtualKeyCode::R => Some(Key::Randomize),
+            VirtualKeyCode::T => Some(Key::RotateView),
+            VirtualKeyCode::S => Some(Key::NextField),
+            VirtualKeyCode::I => Some(Key::NextTracerView),
+            _ => None,
+        }
+    }
+
+    fn apply_key(&mut self, key: Key) {
+        match key {
+            Key::TogglePause => {
+                self.paused = !self.paused;
+                self.history_offset = 0;
+            }
+            Key::Back if self.paused => self.history_offset += 1,
+            Key::Forward if self.paused => {
+                self.history_offset = self.history_offset.saturating_sub(1)
+            }
+            Key::Back | Key::Forward => {}
+            Key::NextDisplay => self.display = self.display.next(),
+            Key::Randomize => self.randomize = true,
+            // a quarter turn only fits the window when the board is square
+            Key::RotateView if self.square => self.view_turns = (self.view_turns + 1) % 4,
+            Key::RotateView => self.view_turns = (self.view_turns + 2) % 4,
+            Key::NextField => self.field_view = self.field_view.next(),
+            Key::NextTracerView => self.tracer_view = self.tracer_view.next(),
+            // a new run has no history to look back on
+            Key::Reset | Key::Preset { .. } => self.history_offset = 0,
+            // the simulation's to handle
+            Key::Pin { .. } | Key::Unpin | Key::Deposit { .. } => {}
+        }
+    }
+
+    fn handle_input(info: &CanvasInfo, state: &mut InputState, event: &Event<()>) -> bool {
+        if let Event::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput { .. }
+                | WindowEvent::MouseInput { .. }
+                | WindowEvent::CursorMoved { .. }
+                | WindowEvent::Focused(true),
+            ..
+        } = event
+        {
+            state.touched = true;
+        }
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CursorLeft { .. },
+                ..
+            } => {
+                state.hovering = false;
+                state.depositing = false;
+                true
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CursorMoved { .. },
+                ..
+            } => {
+                state.hovering = true;
+                MouseState::handle_input(info, &mut state.mouse, event)
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button,
+                        ..
+                    },
+                ..
+            } => {
+                if *button == MouseButton::Left && state.shift {
+                    state.depositing = true;
+                } else {
+                    state.click = Some(*button);
+                }
+                true
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        state: ElementState::Released,
+                        button: MouseButton::Left,
+                        ..
+                    },
+                ..
+            } => {
+                state.depositing = false;
+                false
+            }
+            Event::WindowEvent {
+                event: WindowEvent::Focused(focused),
+                ..
+            } => {
+                state.focused = *focused;
+                false
+            }
+            // some platforms minimize a window by shrinking it to nothing
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                state.minimized = size.width == 0 || size.height == 0;
+                false
+            }
+            Event::WindowEvent {
+                event: WindowEvent::ModifiersChanged(modifiers),
+                ..
+            } => {
+                state.ctrl = modifiers.ctrl();
+                state.shift = modifiers.shift();
+                false
+            }
+            Event::WindowEvent {
+                event: WindowEvent::ReceivedCharacter(c),
+                ..
+            } => match &mut state.command_palette {
+                Some(palette) if !c.is_control() => {
+                    palette.type_char(*c);
+                    true
+                }
+                _ => false,
+            },
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } if state.command_palette.is_some() => {
+                state.palette_key(*key);
+                true
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::F),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                state.actions.push(Action::ToggleFrames);
+                true
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::V),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                state.actions.push(Action::ToggleVideo);
+                true
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::P),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } if state.ctrl => {
+                state.command_palette = Some(CommandPalette::default());
+                true
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => match InputState::key(*key) {
+                Some(key) => {
+                    state.keys.push(key);
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    // while the palette is open keys edit the query instead of acting
+    fn palette_key(&mut self, key: VirtualKeyCode) {
+        let Some(palette) = &mut self.command_palette else {
+            return;
+        };
+        let matches = palette.matches(&self.commands);
+        match key {
+            VirtualKeyCode::Escape => self.command_palette = None,
+            VirtualKeyCode::Back => palette.backspace(),
+            VirtualKeyCode::Up => palette.move_selection(false, matches.len()),
+            VirtualKeyCode::Down => palette.move_selection(true, matches.len()),
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => {
+                match matches.get(palette.selected).map(|c| c.action.clone()) {
+                    Some(Action::Key(key)) => self.keys.push(key),
+                    Some(action) => self.actions.push(action),
+                    None => {}
+                }
+                self.command_palette = None;
+            }
+            _ => {}
+        }
+    }
 }
 
 fn main() {
-    let config = get_config();
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Command::Diff { a, b, top, heatmap }) => diff::run(&a, &b, top, heatmap.as_deref()),
+        Some(Command::Fluctuations {
+            trajectories,
+            steps,
+            burn_in,
+            bins,
+            output,
+        }) => {
+            let experiment = FluctuationExperiment {
+                trajectories,
+                steps,
+                burn_in,
+                bins,
+            };
+            let histogram = experiment.run(&cli.overrides.config()).unwrap_or_else(|e| {
+                eprintln!("Invalid config: {}", e);
+                std::process::exit(1);
+            });
+            histogram
+                .write_csv(&output)
+                .expect("Couldn't write fluctuation histogram");
+        }
+        Some(Command::Ensemble {
+            runs,
+            steps,
+            every,
+            resamples,
+            confidence,
+            output,
+        }) => {
+            let experiment = EnsembleExperiment {
+                runs,
+                steps,
+                every,
+                resamples,
+                confidence,
+            };
+            let ensemble = experiment.run(&cli.overrides.config()).unwrap_or_else(|e| {
+                eprintln!("Invalid config: {}", e);
+                std::process::exit(1);
+            });
+            if let Err(e) = ensemble.write(&output) {
+                eprintln!("Couldn't write {}: {}", output.display(), e);
+                std::process::exit(1);
+            }
+            println!(
+                "wrote {} samples of {} runs to {}",
+                ensemble.rows.len(),
+                ensemble.runs,
+                output.display()
+            );
+        }
+        Some(Command::Sweep {
+            sweep,
+            task_index,
+            num_tasks,
+            output,
+            list,
+        }) => run_sweep(
+            cli.overrides.config(),
+            &sweep,
+            task_index,
+            num_tasks,
+            &output,
+            list,
+        ),
+        Some(Command::Bench {
+            baseline,
+            output,
+            steps,
+            threshold,
+            filter,
+        }) => bench(
+            baseline.as_deref(),
+            &output,
+            steps,
+            threshold,
+            filter.as_deref(),
+        ),
+        Some(Command::Verify) => {
+            let checks = verify::run();
+            for check in &checks {
+                let status = if check.passed { "ok" } else { "FAILED" };
+                println!("{:<26}{:<8}{}", check.name, status, check.detail);
+            }
+            if checks.iter().all(|check| check.passed) {
+                println!("PASS");
+            } else {
+                println!("FAIL");
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Migrate { input, output }) => {
+            let output = output.unwrap_or_else(|| input.clone());
+            match format::migrate(&input, &output) {
+                Ok(from) => println!(
+                    "migrated {} from {} to {}",
+                    output.display(),
+                    from,
+                    format::STATE_VERSION
+                ),
+                Err(e) => {
+                    eprintln!("Couldn't migrate {}: {}", input.display(), e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Command::Export {
+            steps,
+            every,
+            levels,
+            output,
+            images,
+            data,
+        }) => export(
+            cli.overrides.config(),
+            steps,
+            every,
+            levels,
+            &output,
+            &images,
+            &data,
+        ),
+        Some(Command::Viewer { recording, port }) => viewer::run(&recording, port),
+        Some(Command::Runs { action: None }) => list_runs(),
+        Some(Command::Runs {
+            action:
+                Some(RunsAction::Show {
+                    name,
+                    replay,
+                    export,
+                }),
+        }) => show_run(&name, replay, export.as_deref()),
+        Some(Command::Palette { action: None }) => list_palettes(),
+        Some(Command::Palette {
+            action: Some(PaletteAction::Show { name, preview }),
+        }) => show_palette(&name, preview.as_deref()),
+        Some(Command::Palette {
+            action: Some(PaletteAction::Save { name, stops, oklab }),
+        }) => save_palette(&name, &stops, oklab),
+        None => {
+            let resumed = cli.resume.map(|path| {
+                format::read_state(&path).unwrap_or_else(|e| {
+                    eprintln!("Couldn't resume from {}: {}", path.display(), e);
+                    std::process::exit(1);
+                })
+            });
+            // the checkpoint's config comes with it
+            let mut config = match (&resumed, cli.preset) {
+                (Some(sim), _) => {
+                    let mut config = sim.config().clone();
+                    cli.overrides.apply(&mut config);
+                    config
+                }
+                (None, Some(_)) if cli.overrides.config.is_some() => {
+                    eprintln!("--preset and --config can't be used together");
+                    std::process::exit(1);
+                }
+                (None, Some(name)) => {
+                    let mut config = presets::by_name(&name).unwrap_or_else(|| {
+                        eprintln!(
+                            "Unknown preset {}, expected one of: {}",
+                            name,
+                            presets::NAMES.join(", ")
+                        );
+                        std::process::exit(1);
+                    });
+                    cli.overrides.apply(&mut config);
+                    config
+                }
+                (None, None) => cli.overrides.config(),
+            };
 
-    start_loop(config);
+            if let Some(steps) = cli.profile_run {
+                #[cfg(unix)]
+                profile::run(config, steps, &cli.profile_output);
+                #[cfg(not(unix))]
+                {
+                    let _ = (config, steps);
+                    eprintln!("--profile-run is only supported on unix");
+                    std::process::exit(1);
+                }
+                return;
+            }
+
+            if cli.background {
+                enter_background(&mut config);
+            }
+
+            if let Some(spec) = cli.soak {
+                soak(config, &spec, &cli.soak_dir);
+                return;
+            }
+
+            let replay = cli.replay.map(|path| {
+                let session = Session::read(&path).unwrap_or_else(|e| {
+                    eprintln!("Couldn't read session {}: {}", path.display(), e);
+                    std::process::exit(1);
+                });
+                config.seed = Some(session.seed);
+                check_session(&session, &config, &path);
+                session.replay()
+            });
+
+            let link = if let Some(addr) = cli.follow {
+                let (seed, leader_config, follower) =
+                    Follower::connect(&addr).unwrap_or_else(|e| {
+                        eprintln!("Couldn't follow {}: {}", addr, e);
+                        std::process::exit(1);
+                    });
+                config = leader_config;
+                config.seed = Some(seed);
+                // the leader's run is the one worth keeping
+                config.archive = false;
+                Some(Link::Follow {
+                    follower,
+                    pending: VecDeque::new(),
+                    target: 0,
+                })
+            } else if let Some(addr) = cli.lead {
+                let seed = *config.seed.get_or_insert_with(rand::random);
+                let leader = Leader::bind(&addr, seed, &config).unwrap_or_else(|e| {
+                    eprintln!("Couldn't listen on {}: {}", addr, e);
+                    std::process::exit(1);
+                });
+                if let Ok(addr) = leader.local_addr() {
+                    println!("leading on {}", addr);
+                }
+                Some(Link::Lead(leader))
+            } else {
+                None
+            };
+
+            let sim = match resumed {
+                Some(mut sim) => {
+                    if let Err(e) = sim.set_config(config) {
+                        eprintln!("Couldn't resume: {}", e);
+                        std::process::exit(1);
+                    }
+                    eprintln!("resuming at step {}", sim.steps());
+                    sim
+                }
+                None => new_simulation(config),
+            };
+            let outputs = RunOutputs {
+                record: cli.record,
+                montage: cli.montage.map(|spec| (spec, cli.montage_output)),
+                stats_out: cli.stats_out,
+                report: cli.report,
+                upload: cli.upload.map(|target| Upload {
+                    target,
+                    retries: cli.upload_retries,
+                    backoff: UPLOAD_BACKOFF,
+                    only: cli.upload_only,
+                }),
+            };
+            if cli.headless || cli.pipe_frames || sim.config().headless {
+                if link.is_some() {
+                    eprintln!("A headless run can't lead or follow");
+                    std::process::exit(1);
+                }
+                let output = if cli.pipe_frames {
+                    HeadlessOutput::Frames(cli.pipe_scale)
+                } else if cli.quiet {
+                    HeadlessOutput::Quiet
+                } else {
+                    HeadlessOutput::Rows
+                };
+                run_headless(sim, outputs, replay, cli.steps, output);
+                return;
+            }
+
+            start_loop(sim, outputs, replay, link, cli.background);
+        }
+    }
 }
 
-#[inline(always)]
-fn start_loop(config: Config) {
-    let (h, w) = config.dims;
+fn new_simulation(config: Config) -> Simulation {
+    Simulation::new(config).unwrap_or_else(|e| {
+        eprintln!("Invalid config: {}", e);
+        std::process::exit(1);
+    })
+}
 
-    let mut lagged_board = init_board(&config);
-    let mut board = Array2::zeros((h, w));
+// threads a background run may use, fewer if the process has fewer cores, as
+// its cgroup's CPU quota may say
+const BACKGROUND_THREADS: usize = 2;
+// the least time between frames while a background window is out of focus
+const BACKGROUND_FRAME_MS: u64 = 250;
 
-    let mut rng = rand::thread_rng();
+// lowers the priority of this process and the threads it starts from here on,
+// and holds the step to a couple of threads
+fn enter_background(config: &mut Config) {
+    // setpriority only reads its arguments
+    #[cfg(unix)]
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, 10) } != 0 {
+        eprintln!(
+            "Couldn't lower the priority: {}",
+            std::io::Error::last_os_error()
+        );
+    }
 
-    let canvas = Canvas::new(w * config.size_factor, h * config.size_factor);
-    let mut i = 0_usize;
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let threads = config
+        .threads
+        .unwrap_or(cores)
+        .min(cores)
+        .min(BACKGROUND_THREADS);
+    config.threads = Some(threads);
+    if let Err(e) = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build_global()
+    {
+        eprintln!("Couldn't cap the threads: {}", e);
+    }
+}
 
-    canvas.render(move |_, image| {
-        i += 1;
-        println!("{}", i);
-        board_time_step(&mut board, &mut lagged_board, &config, &mut rng);
+fn bench(
+    baseline: Option<&Path>,
+    output: &Path,
+    steps: usize,
+    threshold: f64,
+    filter: Option<&str>,
+) {
+    // read first, so a bad baseline doesn't cost a whole run
+    let baseline = baseline.map(|path| {
+        BenchReport::read(path).unwrap_or_else(|e| {
+            eprintln!("Couldn't read baseline {}: {}", path.display(), e);
+            std::process::exit(1);
+        })
+    });
 
-        for (y, row) in image.chunks_mut(w * config.size_factor).enumerate() {
-            for (x, pixel) in row.iter_mut().enumerate() {
-                let energy = lagged_board[[y / config.size_factor, x / config.size_factor]];
-                let rgb = energy_to_rgb(energy, 2.0);
-                *pixel = rgb;
+    let report = bench::run(steps, filter);
+    for result in &report.results {
+        println!(
+            "{:<22}median {:>10.1?}  p95 {:>10.1?}",
+            result.name,
+            Duration::from_nanos(result.median_ns as u64),
+            Duration::from_nanos(result.p95_ns as u64)
+        );
+    }
+    report.write(output).unwrap_or_else(|e| {
+        eprintln!("Couldn't write {}: {}", output.display(), e);
+        std::process::exit(1);
+    });
+    println!("wrote {}", output.display());
+
+    let Some(baseline) = baseline else {
+        return;
+    };
+    let comparisons = bench::compare(&baseline, &report, threshold);
+    for c in &comparisons {
+        let mark = if c.regressed { "REGRESSED" } else { "ok" };
+        println!("{:<22}{:>+8.1}%  {}", c.name, 100.0 * c.change, mark);
+    }
+    if comparisons.iter().any(|c| c.regressed) {
+        println!("FAIL");
+        std::process::exit(1);
+    }
+    println!("PASS");
+}
+
+fn soak(config: Config, spec: &SoakSpec, dir: &Path) {
+    let mut sim = Simulation::new(config).unwrap_or_else(|e| {
+        eprintln!("Invalid config: {}", e);
+        std::process::exit(1);
+    });
+    println!("soaking seed {} in {}", sim.seed(), dir.display());
+    let report = soak::run(&mut sim, spec, dir).unwrap_or_else(|e| {
+        eprintln!("Soak stopped at step {}: {}", sim.steps(), e);
+        std::process::exit(1);
+    });
+
+    println!("{} steps, {} checks", report.steps, report.checks);
+    if let (Some(first), Some(last)) = (report.first_resident, report.last_resident) {
+        println!(
+            "resident memory: {:.1} MiB -> {:.1} MiB",
+            first as f64 / 1048576.0,
+            last as f64 / 1048576.0
+        );
+    }
+    for (step, problem) in &report.failures {
+        println!("step {}: {}", step, problem);
+    }
+    if report.passed() {
+        println!("PASS");
+    } else {
+        println!("FAIL");
+        std::process::exit(1);
+    }
+}
+
+fn export(
+    config: Config,
+    steps: usize,
+    every: usize,
+    levels: Option<usize>,
+    output: &Path,
+    images: &ImageArgs,
+    data: &DataArgs,
+) {
+    let every = every.max(1);
+    let palette = load_palette(&config);
+    let sim = Simulation::new(config).unwrap_or_else(|e| {
+        eprintln!("Invalid config: {}", e);
+        std::process::exit(1);
+    });
+    let mut scale = sim.config().scale_for(sim.board());
+    let color_scale = sim.config().scale;
+    let dims = sim.config().dims;
+    let levels = levels.unwrap_or_else(|| recording::default_levels(dims, 16));
+    let provenance = Provenance {
+        config_hash: sim.config().hash(),
+        seed: sim.seed(),
+        step: 0,
+    };
+
+    let mut writer = RecordingWriter::create(output, dims, every, levels)
+        .expect("Couldn't create recording directory");
+    let initial = Frame {
+        step: 0,
+        board: sim.board().clone(),
+    };
+    let (png_dir, video_dir) = (output.join("png"), output.join("video"));
+    if images.png {
+        std::fs::create_dir_all(&png_dir).expect("Couldn't create png directory");
+    }
+    let video_frames = images.video_frames.map(|n| n.max(1));
+    if video_frames.is_some() {
+        std::fs::create_dir_all(&video_dir).expect("Couldn't create video directory");
+    }
+    let mut vtk = data
+        .vtk
+        .then(|| VtkSeries::create(&output.join("vtk")).expect("Couldn't create vtk directory"));
+    let zarr_path = output.join("frames.zarr");
+    let mut zarr = data.zarr.map(|spec| {
+        let attrs = serde_json::json!({
+            "seed": sim.seed(),
+            "config": sim.config(),
+        });
+        ZarrWriter::create(&zarr_path, dims, spec, attrs).expect("Couldn't create the zarr store")
+    });
+
+    let mut previous: Option<Frame> = None;
+    let mut written = 0;
+    let frames = std::iter::once(initial).chain(sim.take(steps).filter(|f| f.step % every == 0));
+    for frame in frames {
+        writer.push(&frame).expect("Couldn't write keyframe");
+        if let Some(vtk) = &mut vtk {
+            vtk.push(frame.step, &frame.board)
+                .expect("Couldn't write VTK keyframe");
+        }
+        if let Some(zarr) = &mut zarr {
+            zarr.push(frame.step, &frame.board)
+                .expect("Couldn't write zarr keyframe");
+        }
+        // frames between keyframes are drawn to the scale of the later one
+        let max_energy = scale.update(&frame.board);
+        if images.png {
+            let path = png_dir.join(format!("{:08}.png", frame.step));
+            images.write(
+                &frame.board,
+                palette.as_ref(),
+                max_energy,
+                color_scale,
+                &path,
+                provenance.at(frame.step),
+            );
+        }
+
+        // the frames from the last keyframe up to, but not including, this one,
+        // each stamped with the step nearest to it
+        if let (Some(n), Some(previous)) = (video_frames, &previous) {
+            for k in 0..n {
+                let t = k as f64 / n as f64;
+                let board =
+                    images::interpolate(&previous.board, &frame.board, t, images.interpolate);
+                let step =
+                    previous.step + (t * (frame.step - previous.step) as f64).round() as usize;
+                let path = video_dir.join(format!("{:08}.png", written));
+                images.write(
+                    &board,
+                    palette.as_ref(),
+                    max_energy,
+                    color_scale,
+                    &path,
+                    provenance.at(step),
+                );
+                written += 1;
             }
         }
-        std::thread::sleep(std::time::Duration::from_millis(
-            config.sleep_interval_ms as u64,
+        previous = Some(frame);
+    }
+    if let Some(last) = &previous {
+        let max_energy = scale.update(&last.board);
+        if video_frames.is_some() {
+            let path = video_dir.join(format!("{:08}.png", written));
+            images.write(
+                &last.board,
+                palette.as_ref(),
+                max_energy,
+                color_scale,
+                &path,
+                provenance.at(last.step),
+            );
+        }
+
+        // a small picture of where the run ended, for browsing experiment folders
+        let thumbnail = images::thumbnail(&images.render(
+            &last.board,
+            palette.as_ref(),
+            max_energy,
+            color_scale,
         ));
-    });
+        images::save(
+            &thumbnail,
+            &output.join("thumbnail.png"),
+            provenance.at(last.step),
+        )
+        .expect("Couldn't write thumbnail");
+    }
+
+    if let Some(zarr) = zarr {
+        let frames = zarr.finish().expect("Couldn't write the zarr store");
+        println!("wrote {} frames to {}", frames, zarr_path.display());
+    }
+    if let Some(vtk) = vtk {
+        let series = vtk.finish().expect("Couldn't write the VTK series");
+        println!("wrote {}", series.display());
+    }
+    let index = writer.finish().expect("Couldn't write recording index");
+    println!(
+        "wrote {} keyframes at {} levels to {}",
+        index.frames.len(),
+        index.levels.len(),
+        output.display()
+    );
 }
 
-#[inline(always)]
-fn board_time_step(
-    board: &mut Array2<f64>,
-    lagged_board: &mut Array2<f64>,
-    config: &Config,
-    rng: &mut ThreadRng,
+fn run_sweep(
+    config: Config,
+    path: &Path,
+    task_index: usize,
+    num_tasks: usize,
+    output: &Path,
+    list: bool,
 ) {
-    let (h, w) = config.dims;
+    let fail = |e: SweepError| -> ! {
+        eprintln!("Couldn't run sweep {}: {}", path.display(), e);
+        std::process::exit(1);
+    };
+    let sweep = Sweep::read(path).unwrap_or_else(|e| fail(e));
+    let points = sweep
+        .task(task_index, num_tasks)
+        .unwrap_or_else(|e| fail(e));
+    if list {
+        for point in &points {
+            println!(
+                "{}\tseed {}\t{}",
+                point.index,
+                point.seed,
+                serde_json::Value::Object(point.parameters.clone())
+            );
+        }
+        return;
+    }
 
-    let corner_slices = [
-        s![0..2_usize, 0..2_usize], // top left
-        s![0..2_usize, w - 2..w],   // top right
-        s![h - 2..h, 0..2_usize],   // bottom left
-        s![h - 2..h, w - 2..w],     // bottom right
-    ];
-    let corner_energies: [f64; 4] = [
-        lagged_board[[0, 0]],
-        lagged_board[[0, w - 1]],
-        lagged_board[[h - 1, 0]],
-        lagged_board[[h - 1, w - 1]],
-    ];
+    let rows = sweep.run(&config, &points).unwrap_or_else(|e| fail(e));
+    let path = sweep::task_path(output, task_index);
+    if let Err(e) = sweep::write_rows(&path, &rows) {
+        eprintln!("Couldn't write {}: {}", path.display(), e);
+        std::process::exit(1);
+    }
+    println!(
+        "wrote {} of {} points to {}",
+        rows.len(),
+        sweep.len(),
+        path.display()
+    );
+}
 
-    for (slice, energy) in zip(corner_slices, corner_energies) {
-        let mut slice = board.slice_mut(slice);
-        slice += &(energy * &probability_mat((2, 2), rng));
+fn list_runs() {
+    let manifests = runs::list(&runs::runs_dir()).unwrap_or_else(|e| {
+        eprintln!("Couldn't read the run archive: {}", e);
+        std::process::exit(1);
+    });
+    if manifests.is_empty() {
+        println!("no archived runs in {}", runs::runs_dir().display());
+        return;
+    }
+
+    println!(
+        "{:<24}{:<22}{:>10}{:>12}{:>12}  config",
+        "name", "started (utc)", "steps", "duration", "entropy"
+    );
+    for manifest in &manifests {
+        println!(
+            "{:<24}{:<22}{:>10}{:>11.1}s{:>12.4}  {}",
+            manifest.name,
+            runs::utc_time(manifest.started),
+            manifest.steps,
+            manifest.duration_secs,
+            manifest.final_entropy,
+            manifest.config_summary()
+        );
     }
+}
 
-    // top and bottom borders
-    for j in 1..w - 1 {
-        let mut slice = board.slice_mut(s![0..2_usize, j - 1..=j + 1]);
-        let energy = lagged_board[[0, j]];
-        slice += &(energy * &probability_mat((2, 3), rng));
+fn load_palette(config: &Config) -> Option<Palette> {
+    let palette = match &config.palette {
+        Some(name) => Some(
+            Palette::load(&palette::palettes_dir(), name).unwrap_or_else(|e| {
+                eprintln!("Couldn't load palette {}: {}", name, e);
+                std::process::exit(1);
+            }),
+        ),
+        None => config.colormap.palette(),
+    };
+    in_light(palette, config)
+}
+
+// under linear_light, sRGB blends and the built-in ramp go through linear light
+fn in_light(palette: Option<Palette>, config: &Config) -> Option<Palette> {
+    match palette {
+        _ if !config.linear_light => palette,
+        Some(palette) => Some(palette.in_linear_light()),
+        None => Some(Palette::hue().in_linear_light()),
+    }
+}
+
+fn list_palettes() {
+    let dir = palette::palettes_dir();
+    let names = palette::list(&dir).unwrap_or_else(|e| {
+        eprintln!("Couldn't read the palettes: {}", e);
+        std::process::exit(1);
+    });
+    if names.is_empty() {
+        println!("no palettes in {}", dir.display());
     }
-    for j in 1..w - 1 {
-        let mut slice = board.slice_mut(s![h - 2..h, j - 1..=j + 1]);
-        let energy = lagged_board[[h - 1, j]];
-        slice += &(energy * &probability_mat((2, 3), rng));
+    for name in names {
+        println!("{}", name);
+    }
+}
+
+fn show_palette(name: &str, preview: Option<&Path>) {
+    let palette = Palette::load(&palette::palettes_dir(), name).unwrap_or_else(|e| {
+        eprintln!("Couldn't load palette {}: {}", name, e);
+        std::process::exit(1);
+    });
+    for stop in palette.stops() {
+        println!("{:<8}{}", stop.at, String::from(stop.color));
+    }
+    match palette.blend() {
+        Blend::Rgb => {}
+        Blend::Linear => println!("blended in linear light"),
+        Blend::Oklab => println!("blended in oklab"),
+    }
+
+    if let Some(path) = preview {
+        let (w, h) = (512, 48);
+        let img = image::RgbImage::from_fn(w, h, |x, _| {
+            image::Rgb(palette.sample(x as f64 / (w - 1) as f64))
+        });
+        img.save(path).expect("Couldn't write the palette preview");
+    }
+}
+
+fn save_palette(name: &str, stops: &[String], oklab: bool) {
+    let blend = if oklab { Blend::Oklab } else { Blend::Rgb };
+    let palette = stops
+        .iter()
+        .map(|s| Stop::parse(s))
+        .collect::<Result<Vec<_>, _>>()
+        .and_then(Palette::new)
+        .and_then(|palette| {
+            palette
+                .with_blend(blend)
+                .save(&palette::palettes_dir(), name)
+        });
+    match palette {
+        Ok(path) => println!("saved {}", path.display()),
+        Err(e) => {
+            eprintln!("Couldn't save palette {}: {}", name, e);
+            std::process::exit(1);
+        }
     }
+}
+
+fn show_run(name: &str, replay: bool, export: Option<&Path>) {
+    let root = runs::runs_dir();
+    let manifest = runs::load(&root, name).unwrap_or_else(|e| {
+        eprintln!("Couldn't read run {}: {}", name, e);
+        std::process::exit(1);
+    });
 
-    // left to right
-    for i in 1..h - 1 {
-        // leftmost
-        let mut slice = board.slice_mut(s![i - 1..=i + 1, 0..2_usize]);
-        let energy = lagged_board[[i, 0]];
-        slice += &(energy * &probability_mat((3, 2), rng));
+    println!("name:      {}", manifest.name);
+    println!("started:   {} utc", runs::utc_time(manifest.started));
+    println!("duration:  {:.1}s", manifest.duration_secs);
+    println!("steps:     {}", manifest.steps);
+    println!("seed:      {}", manifest.seed);
+    println!(
+        "entropy:   {} -> {}",
+        manifest.initial_entropy, manifest.final_entropy
+    );
+    println!("config:    {}", manifest.config_summary());
+    println!("files:     {}", root.join(name).display());
 
-        // in between
-        for j in 1..w - 1 {
-            let mut slice = board.slice_mut(s![i - 1..=i + 1, j - 1..=j + 1]);
-            let energy = lagged_board[[i, j]];
-            slice += &(energy * &probability_mat((3, 3), rng));
+    if let Some(dest) = export {
+        let copied = runs::export(&root, name, dest).unwrap_or_else(|e| {
+            eprintln!("Couldn't export run {}: {}", name, e);
+            std::process::exit(1);
+        });
+        for path in copied {
+            println!("exported {}", path.display());
         }
+    }
 
-        // rightmost
-        let mut slice = board.slice_mut(s![i - 1..=i + 1, h - 2..h]);
-        let energy = lagged_board[[i, w - 1]];
-        slice += &(energy * &probability_mat((3, 2), rng));
+    if replay {
+        let path = root.join(name).join(runs::SESSION);
+        let session = if path.is_file() {
+            Session::read(&path).unwrap_or_else(|e| {
+                eprintln!("Couldn't read session {}: {}", path.display(), e);
+                std::process::exit(1);
+            })
+        } else {
+            Session::new(manifest.seed, manifest.config.clone())
+        };
+        // the manifest has the config the run ended with, the session the one it
+        // started with
+        let mut config = session.config.clone().unwrap_or(manifest.config);
+        config.seed = Some(manifest.seed);
+        // a replay is not a new experiment
+        config.archive = false;
+        start_loop(
+            new_simulation(config),
+            RunOutputs::default(),
+            Some(session.replay()),
+            None,
+            false,
+        );
     }
+}
 
-    lagged_board.clone_from(board);
+// a replay under another config would quietly go differently, or click off the
+// board, so it isn't started
+fn check_session(session: &Session, config: &Config, path: &Path) {
+    if session.config.is_none() {
+        eprintln!(
+            "warning: session {} has no config to check against",
+            path.display()
+        );
+    }
+    let mismatches = session.mismatches(config);
+    if !mismatches.is_empty() {
+        eprintln!(
+            "Couldn't replay session {}: it was recorded with a different {}",
+            path.display(),
+            mismatches.join(", ")
+        );
+        std::process::exit(1);
+    }
+}
 
-    board.fill(0.0);
+// how long a notice such as the randomizer's changes stays up
+const NOTICE_FRAMES: usize = 120;
+// how many matches the command palette lists
+const PALETTE_LINES: usize = 8;
+
+// everything that outlives the window: when the render loop is torn down, i.e.
+// when the window closes, the summary is printed, the session written and the
+// run archived
+struct Run {
+    sim: Simulation,
+    metrics: RunMetrics,
+    session: Session,
+    record: Option<PathBuf>,
+    montage: Option<(Montage, PathBuf)>,
+    series: Option<SeriesWriter>,
+    // the entropy at every step, for the report
+    report: Option<(PathBuf, Vec<(usize, f64)>)>,
+    // with the files to upload besides the archive
+    upload: Option<(Upload, Vec<PathBuf>)>,
+    started: SystemTime,
+    step_times: Histogram,
+    // between the starts of consecutive frames
+    frame_times: Histogram,
+    last_frame: Option<Instant>,
 }
 
-#[inline(always)]
-fn energy_to_rgb(energy: f64, max_energy: f64) -> Color {
-    let (energy, max_energy) = (energy as f64, max_energy as f64);
-    let min_hue: f64 = 240.0; // Blue
-    let max_hue: f64 = 0.0; // Red
-    let normalized_energy = energy / max_energy;
-    let hue = min_hue - (normalized_energy * (min_hue - max_hue));
-    let (r, g, b) = hsv_to_rgb(hue, 1.0, 1.0);
-    Color {
-        r: (r * 255.0) as u8,
-        g: (g * 255.0) as u8,
-        b: (b * 255.0) as u8,
+// the files a run writes as it goes or once it's over
+#[derive(Default)]
+struct RunOutputs {
+    record: Option<PathBuf>,
+    montage: Option<(MontageSpec, PathBuf)>,
+    stats_out: Option<PathBuf>,
+    report: Option<PathBuf>,
+    upload: Option<Upload>,
+}
+
+// before the first retry of an upload; it doubles with each one after
+const UPLOAD_BACKOFF: Duration = Duration::from_secs(2);
+
+impl Run {
+    fn new(sim: Simulation, outputs: RunOutputs) -> Self {
+        let upload = outputs.upload.map(|upload| {
+            let files = [
+                outputs.record.clone(),
+                outputs.montage.as_ref().map(|(_, path)| path.clone()),
+                outputs.stats_out.clone(),
+                outputs.report.clone(),
+            ];
+            (upload, files.into_iter().flatten().collect())
+        });
+        let montage = outputs.montage.map(|(spec, path)| {
+            let max_energy = sim.config().max_energy_for(sim.board());
+            let mut montage = Montage::new(
+                spec,
+                load_palette(sim.config()),
+                max_energy,
+                sim.config().scale,
+            );
+            montage.observe(sim.steps(), sim.board());
+            (montage, path)
+        });
+        let mut series = outputs.stats_out.map(|path| {
+            SeriesWriter::create(&path).unwrap_or_else(|e| {
+                eprintln!("Couldn't create {}: {}", path.display(), e);
+                std::process::exit(1);
+            })
+        });
+        observe_series(&mut series, &sim);
+        let metrics = RunMetrics::new(sim.board(), sim.config());
+        let report = outputs
+            .report
+            .map(|path| (path, vec![(sim.steps(), metrics.initial().entropy)]));
+        Self {
+            metrics,
+            session: Session::new(sim.seed(), sim.config().clone()),
+            sim,
+            record: outputs.record,
+            montage,
+            series,
+            report,
+            upload,
+            started: SystemTime::now(),
+            step_times: Histogram::new(),
+            frame_times: Histogram::new(),
+            last_frame: None,
+        }
+    }
+
+    fn step(sim: &mut Simulation, step_times: &mut Histogram) {
+        let start = Instant::now();
+        sim.step();
+        step_times.record(start.elapsed());
+    }
+
+    fn frame(&mut self) {
+        let now = Instant::now();
+        if let Some(last) = self.last_frame.replace(now) {
+            self.frame_times.record(now - last);
+        }
     }
 }
 
-#[inline(always)]
-fn hsv_to_rgb(h: f64, s: f64, v: f64) -> (f64, f64, f64) {
-    let c = v * s;
-    let h_prime = h / 60.0;
-    let x = c * (1.0 - (h_prime % 2.0 - 1.0).abs());
-
-    let (r, g, b) = if h_prime < 1.0 {
-        (c, x, 0.0)
-    } else if h_prime < 2.0 {
-        (x, c, 0.0)
-    } else if h_prime < 3.0 {
-        (0.0, c, x)
-    } else if h_prime < 4.0 {
-        (0.0, x, c)
-    } else if h_prime < 5.0 {
-        (x, 0.0, c)
-    } else {
-        (c, 0.0, x)
-    };
+impl Drop for Run {
+    fn drop(&mut self) {
+        // so a run left to pick its own seed can be repeated with --seed
+        eprintln!("{:<18}{}", "seed:", self.sim.seed());
+        eprintln!("{}", self.metrics.summary());
+        for (name, times) in [
+            ("step time:", &self.step_times),
+            ("frame time:", &self.frame_times),
+        ] {
+            if times.count() > 0 {
+                eprintln!("{:<18}{}", name, times.report());
+            }
+        }
+
+        if let Some(series) = &mut self.series {
+            if let Err(e) = series.flush() {
+                eprintln!("Couldn't write stats: {}", e);
+            }
+        }
+
+        if let Some(path) = &self.record {
+            self.session
+                .write(path)
+                .expect("Couldn't write session file");
+        }
+
+        if let Some((montage, path)) = &self.montage {
+            let provenance = Provenance {
+                config_hash: self.sim.config().hash(),
+                seed: self.sim.seed(),
+                step: self.sim.steps(),
+            };
+            images::save(&montage.render(), path, provenance).expect("Couldn't write montage");
+            eprintln!("wrote montage to {}", path.display());
+        }
 
-    let m = v - c;
+        if let Some((path, curve)) = self.report.take() {
+            let config = self.sim.config();
+            let palette = load_palette(config);
+            let max_energy = config.scale_for(self.sim.board()).update(self.sim.board());
+            let started = self
+                .started
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            let budget = self.metrics.budget();
+            let report = Report {
+                title: format!("entropy run, {} utc", runs::utc_time(started)),
+                seed: self.sim.seed(),
+                config: config.clone(),
+                entropy: curve,
+                budget: budget.rows().to_vec(),
+                drift: budget.drift(),
+                final_frame: images::render(
+                    self.sim.board(),
+                    palette.as_ref(),
+                    max_energy,
+                    config.scale,
+                ),
+            };
+            match report.write(&path) {
+                Ok(()) => eprintln!("wrote report to {}", path.display()),
+                Err(e) => eprintln!("Couldn't write report {}: {}", path.display(), e),
+            }
+        }
+
+        let started = self
+            .started
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let mut name = runs::run_name(started);
+        let mut archived = None;
+        if self.sim.config().archive {
+            let mut manifest = Manifest {
+                name: name.clone(),
+                started,
+                duration_secs: self.started.elapsed().map_or(0.0, |d| d.as_secs_f64()),
+                steps: self.sim.steps(),
+                seed: self.sim.seed(),
+                initial_entropy: self.metrics.initial().entropy,
+                final_entropy: self.metrics.last().entropy,
+                config: self.sim.config().clone(),
+            };
+            match runs::archive(
+                &runs::runs_dir(),
+                &mut manifest,
+                &self.sim,
+                Some(&self.session),
+            ) {
+                Ok(dir) => {
+                    eprintln!("archived as {} in {}", manifest.name, dir.display());
+                    name = manifest.name;
+                    archived = Some(dir);
+                }
+                Err(e) => eprintln!("Couldn't archive run: {}", e),
+            }
+        }
 
-    (r + m, g + m, b + m)
+        if let Some((upload, mut files)) = self.upload.take() {
+            if let Some(dir) = archived {
+                files.extend(
+                    [runs::MANIFEST, runs::FINAL_STATE, runs::SESSION].map(|f| dir.join(f)),
+                );
+            }
+            // under the same name as in the archive, so runs don't overwrite each other
+            match upload.run(&name, &files) {
+                Ok(manifest) => eprintln!("uploaded to {}", manifest),
+                Err(e) => eprintln!("Couldn't upload run: {}", e),
+            }
+        }
+    }
 }
 
-#[inline(always)]
-fn probability_mat((a, b): (usize, usize), rng: &mut ThreadRng) -> Array2<f64> {
-    let mut p = Array2::<f64>::zeros((a, b));
-    let mut s = 0.0;
+// what a headless run writes to stdout
+enum HeadlessOutput {
+    // a row of stats per step
+    Rows,
+    Quiet,
+    // the board per step, as raw frames scaled up by this much
+    Frames(u32),
+}
 
-    for i in 0..a {
-        for j in 0..b {
-            p[[i, j]] = rng.gen();
-            s += p[[i, j]];
+// the window's run without the window: recorded randomizations and focus pins
+// still happen at their steps, everything that only changes the view is dropped
+fn run_headless(
+    sim: Simulation,
+    outputs: RunOutputs,
+    mut replay: Option<Replay>,
+    steps: Option<usize>,
+    output: HeadlessOutput,
+) {
+    let config = sim.config().clone();
+    let rows = matches!(output, HeadlessOutput::Rows);
+    if rows {
+        println!("{}", StepMetrics::HEADER);
+    }
+    let mut pipe = match output {
+        HeadlessOutput::Frames(factor) => {
+            let pipe = video::FramePipe::new(
+                load_palette(&config),
+                config.scale_for(sim.board()),
+                config.scale,
+                factor,
+            );
+            let (w, h) = pipe.size(config.dims);
+            eprintln!("piping {}x{} rgb24 frames", w, h);
+            Some(pipe)
+        }
+        _ => None,
+    };
+    if let Some(pipe) = &mut pipe {
+        if pipe.push(sim.board()).is_err() {
+            return;
         }
     }
 
-    p /= s;
+    let mut randomizer_rng = SimRng::seed_from_u64(sim.seed().wrapping_add(1));
+    let mut run = Run::new(sim, outputs);
+
+    while steps.is_none_or(|steps| run.sim.steps() < steps) {
+        let sim = &mut run.sim;
+        let keys = replay
+            .as_mut()
+            .map(|replay| replay.due(sim.steps()))
+            .unwrap_or_default();
+        for key in keys {
+            run.session.record(sim.steps(), key);
+            if key == Key::Randomize {
+                eprintln!("{}", randomize(sim, &config, &mut randomizer_rng));
+            }
+            sim_key(sim, &mut run.metrics, key);
+        }
+
+        Run::step(sim, &mut run.step_times);
+        if let Some((montage, _)) = &mut run.montage {
+            montage.observe(sim.steps(), sim.board());
+        }
+        observe_series(&mut run.series, sim);
+        observe_checkpoint(sim);
+        let metrics = run
+            .metrics
+            .update(sim.steps(), sim.board(), sim.last_report());
+        if let Some((_, curve)) = &mut run.report {
+            curve.push((metrics.step, metrics.entropy));
+        }
+        if rows {
+            println!("{}", metrics.row());
+        }
+        // whatever reads the frames closing the pipe ends the run
+        if let Some(pipe) = &mut pipe {
+            if pipe.push(sim.board()).is_err() {
+                break;
+            }
+        }
+    }
+}
 
-    p
+// how a window is tied to others over the network
+enum Link {
+    Lead(Leader),
+    Follow {
+        follower: Follower,
+        // changes received but not yet reached
+        pending: VecDeque<Message>,
+        // the step the leader has got to
+        target: usize,
+    },
 }
 
-fn init_board(config: &Config) -> Array2<f64> {
+#[inline(always)]
+fn start_loop(
+    sim: Simulation,
+    outputs: RunOutputs,
+    mut replay: Option<Replay>,
+    mut link: Option<Link>,
+    background: bool,
+) {
+    let config = sim.config().clone();
     let (h, w) = config.dims;
-    let hotspots = config.hotspots;
 
-    let mut board = Array2::<f64>::zeros((h, w));
+    let mut history = history::History::with_memory_cap(config.dims, config.history_memory_mb);
+    history.push(sim.board());
+
+    println!("{}", StepMetrics::HEADER);
+
+    let (layout, warnings) = config.window_layout();
+    for warning in warnings {
+        eprintln!("warning: {}", warning);
+    }
+    let margin = layout.margin;
+    let (board_w, board_h) = (w * layout.size_factor, h * layout.size_factor);
+
+    let mut state = InputState::new(
+        config.display,
+        h == w,
+        commands::all(&palette::list(&palette::palettes_dir()).unwrap_or_default()),
+    );
+    // the first frame starts the video, once the window's size is known
+    if config.video.start {
+        state.actions.push(Action::ToggleVideo);
+    }
+    let canvas = Canvas::new(layout.width, layout.height)
+        .state(state)
+        .input(InputState::handle_input);
+
+    let numbers = locale::NumberFormat::from_env();
+    let mut palette = load_palette(&config);
+    let mut scale = config.scale_for(sim.board());
+    let mut capture = FrameCapture::new(&config);
+    let mut recorder = video::Recorder::new(config.video.clone());
+
+    // seeded from the run so replayed randomizations come out the same
+    let mut randomizer_rng = SimRng::seed_from_u64(sim.seed().wrapping_add(1));
+    let mut governor = config
+        .auto_speed
+        .map(|speed| Governor::new(speed, config.steps_per_frame));
+    let mut idler = config.power_save.map(Idler::new);
+    let mut run = Run::new(sim, outputs);
+
+    canvas.render(move |input, image| {
+        run.frame();
+        let frame_start = Instant::now();
+        let sim = &mut run.sim;
+        let mut keys = replay
+            .as_mut()
+            .map(|replay| replay.due(sim.steps()))
+            .unwrap_or_default();
+        // left pins the focus on the cell under the cursor, right lets it go
+        let (mx, my) = (input.mouse.x, input.mouse.y);
+        match input.click.take() {
+            Some(MouseButton::Left) if input.hovering && mx >= 0 && my >= 0 => {
+                if let Region::Board { row, col } = layout.region(mx as usize, my as usize, (h, w))
+                {
+                    let view = Transform::rotation(input.view_turns);
+                    let (row, col) = view.source((row, col), (h, w));
+                    input.keys.push(Key::Pin { row, col });
+                }
+            }
+            Some(MouseButton::Right) => input.keys.push(Key::Unpin),
+            _ => {}
+        }
+        // shift and the left button put energy down under the cursor, every frame
+        // it's held
+        if input.depositing && input.hovering && mx >= 0 && my >= 0 {
+            if let Region::Board { row, col } = layout.region(mx as usize, my as usize, (h, w)) {
+                let view = Transform::rotation(input.view_turns);
+                let (row, col) = view.source((row, col), (h, w));
+                input.keys.push(Key::Deposit { row, col });
+            }
+        }
+        keys.append(&mut input.keys);
+        if let Some(Link::Follow { .. }) = link {
+            // the leader decides everything but how this window draws the board
+            keys.retain(|key| {
+                matches!(
+                    key,
+                    Key::NextDisplay | Key::RotateView | Key::NextField | Key::NextTracerView
+                )
+            });
+        }
+        for key in keys {
+            run.session.record(sim.steps(), key);
+            if let Some(Link::Lead(leader)) = &mut link {
+                let step = sim.steps();
+                leader.broadcast(Message::Key { step, key });
+            }
+            input.apply_key(key);
+            sim_key(sim, &mut run.metrics, key);
+            restarted(sim, key, &mut history, &mut scale);
+        }
+
+        for action in std::mem::take(&mut input.actions) {
+            let text = match action {
+                Action::Key(_) => continue,
+                Action::Snapshot => {
+                    let path = format!("snapshot-{:08}.npy", sim.steps());
+                    match ndarray_npy::write_npy(&path, sim.board()) {
+                        Ok(()) => format!("WROTE {}", path),
+                        Err(e) => format!("COULDN'T WRITE {}: {}", path, e),
+                    }
+                }
+                Action::ToggleFrames => match capture.toggle() {
+                    Ok(Some(dir)) => format!("WRITING FRAMES TO {}", dir.display()),
+                    Ok(None) => "STOPPED WRITING FRAMES".to_string(),
+                    Err(e) => format!("COULDN'T WRITE FRAMES: {}", e),
+                },
+                Action::ToggleVideo => {
+                    let dims = (image.width() as u32, image.height() as u32);
+                    match recorder.toggle(dims) {
+                        Ok(true) => format!("RECORDING TO {}", recorder.path().display()),
+                        Ok(false) => format!("WROTE {}", recorder.path().display()),
+                        Err(e) => format!("COULDN'T RECORD: {}", e),
+                    }
+                }
+                Action::ToggleOverlay => {
+                    let mut retuned = sim.config().clone();
+                    retuned.debug_overlay = !retuned.debug_overlay;
+                    let on = retuned.debug_overlay;
+                    match sim.set_config(retuned) {
+                        Ok(()) => format!("KERNEL OVERLAY {}", if on { "ON" } else { "OFF" }),
+                        Err(e) => e.to_string(),
+                    }
+                }
+                Action::Palette(name) => {
+                    let loaded = name
+                        .as_ref()
+                        .map(|name| Palette::load(&palette::palettes_dir(), name))
+                        .transpose();
+                    match loaded {
+                        Ok(loaded) => {
+                            // the default is the config's colormap
+                            let loaded = loaded.or_else(|| sim.config().colormap.palette());
+                            palette = in_light(loaded, sim.config());
+                            format!("PALETTE {}", name.as_deref().unwrap_or("DEFAULT"))
+                        }
+                        Err(e) => format!("COULDN'T LOAD PALETTE: {}", e),
+                    }
+                }
+                // a new board would leave linked windows behind
+                Action::Preset(_) if link.is_some() => {
+                    "CAN'T LOAD A PRESET WHILE LINKED".to_string()
+                }
+                // loaded as a key on the next frame, so the session replays it
+                Action::Preset(name) => match preset_config(sim, name).validate() {
+                    Ok(()) => {
+                        let index = presets::NAMES.iter().position(|n| *n == name);
+                        input.keys.push(Key::Preset {
+                            index: index.expect("listed presets exist"),
+                        });
+                        format!("PRESET {}", name)
+                    }
+                    Err(e) => e.to_string(),
+                },
+            };
+            input.notice = Some((text, NOTICE_FRAMES));
+        }
+
+        if std::mem::take(&mut input.randomize) {
+            let text = randomize(sim, &config, &mut randomizer_rng);
+            if let Some(Link::Lead(leader)) = &mut link {
+                leader.broadcast(Message::Config {
+                    step: sim.steps(),
+                    config: sim.config().clone(),
+                });
+            }
+            eprintln!("{}", text);
+            input.notice = Some((text, NOTICE_FRAMES));
+        }
+
+        let throttled = background && (!input.focused || input.minimized);
+        let steps = match &governor {
+            _ if throttled => 1,
+            Some(governor) => governor.steps_per_frame(sim.steps(), &sim.config().events),
+            None => sim.config().steps_per_frame,
+        };
+        if let Some(idler) = &mut idler {
+            if std::mem::take(&mut input.touched) {
+                idler.wake();
+            }
+            idler.expect(sim.steps(), steps, &sim.config().events);
+        }
+        // |entropy change| summed over the steps this frame runs
+        let (mut frame_change, mut frame_steps) = (0.0, 0);
+        // the metrics and history are passed in so that keys from a leader can
+        // reset them
+        let mut advance =
+            |sim: &mut Simulation, metrics: &mut RunMetrics, history: &mut history::History| {
+                Run::step(sim, &mut run.step_times);
+                if let Some((montage, _)) = &mut run.montage {
+                    montage.observe(sim.steps(), sim.board());
+                }
+                observe_series(&mut run.series, sim);
+                observe_checkpoint(sim);
+                let metrics = metrics.update(sim.steps(), sim.board(), sim.last_report());
+                if let Some((_, curve)) = &mut run.report {
+                    curve.push((metrics.step, metrics.entropy));
+                }
+                println!("{}", metrics.row());
+                if let Some(governor) = &mut governor {
+                    governor.observe(metrics.production);
+                }
+                frame_change += metrics.production.abs();
+                frame_steps += 1;
+                history.push(sim.board());
+            };
+        match &mut link {
+            Some(Link::Follow {
+                follower,
+                pending,
+                target,
+            }) => {
+                for message in follower.poll() {
+                    match message {
+                        Message::Step { step } => *target = step,
+                        message => pending.push_back(message),
+                    }
+                }
+                // changes are made at the step the leader made them at, then the
+                // run catches up to the leader
+                loop {
+                    while let Some(message) = pending.front().filter(|m| m.step() <= sim.steps()) {
+                        match message.clone() {
+                            // the leader's new config follows it
+                            Message::Key { key, .. } if key != Key::Randomize => {
+                                input.apply_key(key);
+                                sim_key(sim, &mut run.metrics, key);
+                                restarted(sim, key, &mut history, &mut scale);
+                            }
+                            Message::Config { config, .. } => {
+                                if let Err(e) = sim.set_config(config) {
+                                    eprintln!("Couldn't follow config change: {}", e);
+                                }
+                            }
+                            _ => {}
+                        }
+                        pending.pop_front();
+                    }
+                    if sim.steps() >= *target {
+                        break;
+                    }
+                    advance(sim, &mut run.metrics, &mut history);
+                }
+            }
+            _ if input.paused => {}
+            _ => {
+                for _ in 0..steps {
+                    advance(sim, &mut run.metrics, &mut history);
+                }
+            }
+        }
+        if let Some(Link::Lead(leader)) = &mut link {
+            leader.broadcast(Message::Step { step: sim.steps() });
+        }
+        // a paused board is as settled as it gets
+        if let Some(idler) = &mut idler {
+            idler.observe(frame_change / frame_steps.max(1) as f64);
+        }
+
+        input.history_offset = input.history_offset.min(history.len().saturating_sub(1));
+        let shown = if input.paused {
+            history.get(input.history_offset).unwrap_or(sim.board())
+        } else {
+            sim.board()
+        };
+        // there is no history for the shadow, so it is always the latest one
+        let shadow = sim
+            .shadow()
+            .filter(|_| input.field_view != FieldView::Board);
+        let difference = input.field_view == FieldView::Difference && shadow.is_some();
+        let shown = match (input.field_view, shadow) {
+            (FieldView::Shadow, Some(shadow)) => shadow,
+            _ => shown,
+        };
+
+        let field = match input.display {
+            _ if difference => Cow::Owned(shown - shadow.unwrap()),
+            Display::Energy if matches!(sim.config().capacity, HeatCapacity::Uniform) => {
+                Cow::Borrowed(shown)
+            }
+            Display::Energy => Cow::Owned(sim.config().capacity.temperature(shown)),
+            Display::EntropyProduction => Cow::Owned(stats::entropy_production_map(
+                shown,
+                history.get(input.history_offset + 1),
+            )),
+            Display::LocalEntropy => Cow::Owned(stats::local_entropy_map(
+                shown,
+                sim.config().local_entropy_window,
+            )),
+        };
+
+        // the view may be turned; everything below is in view orientation
+        let view = Transform::rotation(input.view_turns);
+        let (field, shown) = if view.is_identity() {
+            (field, Cow::Borrowed(shown))
+        } else {
+            (
+                Cow::Owned(view.apply(field.view())),
+                Cow::Owned(view.apply(shown.view())),
+            )
+        };
+        let shown = shown.as_ref();
+
+        let field_max_abs = field.fold(0.0_f64, |m, &v| m.max(v.abs()));
+        // only the energy view goes up to a normalized max
+        let color_scale = sim.config().scale;
+        let max_energy = if input.display == Display::Energy && !difference {
+            scale.update(&field)
+        } else {
+            1.0
+        };
+        let max_local_entropy = ((sim.config().local_entropy_window.pow(2)) as f64).ln();
+
+        let (row_sums, col_sums) = marginal_sums(shown);
+        let max_row_sum = row_sums.fold(0.0_f64, |a, &b| a.max(b));
+        let max_col_sum = col_sums.fold(0.0_f64, |a, &b| a.max(b));
+
+        // the kernel's flags are from the latest step, whatever is being shown
+        let flags = sim
+            .last_report()
+            .flags
+            .as_ref()
+            .filter(|_| sim.config().debug_overlay);
+
+        let focus = sim.config().focus;
+        let phase_change = &sim.config().phase_change;
+        let walls = sim.config().wall_mask();
+        // like the shadow, the dye has no history and is always the latest one
+        let dye = sim
+            .tracer()
+            .filter(|_| input.tracer_view != TracerView::Hidden);
+
+        // the image needn't be the size the layout asked for, so go by its own width
+        let width = image.width();
+        for (y, row) in image.chunks_mut(width).enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = match layout.region(x, y, (h, w)) {
+                    Region::Board { row, col } => {
+                        let cell = [row, col];
+                        let value = field[cell];
+                        let color = match input.display {
+                            _ if difference => diverging_rgb(value, field_max_abs),
+                            Display::Energy => {
+                                scaled_rgb(palette.as_ref(), color_scale, value, max_energy)
+                            }
+                            Display::EntropyProduction => diverging_rgb(value, field_max_abs),
+                            Display::LocalEntropy => {
+                                ramp_rgb(palette.as_ref(), value, max_local_entropy)
+                            }
+                        };
+                        let color = match phase_change {
+                            // diagonal hatching over frozen cells
+                            Some(phase) if phase.is_solid(shown[cell]) && (x + y) % 6 < 2 => {
+                                darken(color)
+                            }
+                            _ => color,
+                        };
+                        let source = view.source((row, col), (h, w));
+                        let color = match (dye, input.tracer_view) {
+                            (Some(dye), TracerView::Blend) => {
+                                blend(color, DYE, 0.7 * dye[source].clamp(0.0, 1.0))
+                            }
+                            (Some(dye), _) => blend(BLACK, DYE, dye[source].clamp(0.0, 1.0)),
+                            (None, _) => color,
+                        };
+                        let color = match focus {
+                            Some(focus) if focus.on_edge(source) => Color {
+                                r: 255,
+                                g: 255,
+                                b: 255,
+                            },
+                            _ => color,
+                        };
+                        let color = match &walls {
+                            Some(walls) if walls[source] => WALL,
+                            _ => color,
+                        };
+                        match flags.map(|f| f.0[source]) {
+                            Some(f) if f & KernelFlags::UNNORMALIZED != 0 => Color {
+                                r: 255,
+                                g: 0,
+                                b: 255,
+                            },
+                            // a sparse dot pattern, since every wall cell has it
+                            Some(f) if f & KernelFlags::CLAMPED != 0 && (x + y) % 4 == 0 => Color {
+                                r: 255,
+                                g: 255,
+                                b: 0,
+                            },
+                            _ => color,
+                        }
+                    }
+                    Region::ColumnMarginal { col, offset } => {
+                        marginal_pixel(col_sums[col], max_col_sum, offset, margin)
+                    }
+                    Region::RowMarginal { row, offset } => {
+                        marginal_pixel(row_sums[row], max_row_sum, offset, margin)
+                    }
+                    Region::Blank => Color { r: 0, g: 0, b: 0 },
+                };
+            }
+        }
+
+        // pixel inspector
+        let (mx, my) = (input.mouse.x, input.mouse.y);
+        if input.hovering && mx >= 0 && my >= 0 {
+            let (mx, my) = (mx as usize, my as usize);
+            if let Region::Board { row, col } = layout.region(mx, my, (h, w)) {
+                let value = field[[row, col]];
+                let (row, col) = view.source((row, col), (h, w));
+                let text = format!(
+                    "({}, {}) {}",
+                    numbers.int(row),
+                    numbers.int(col),
+                    numbers.float(value)
+                );
+                font::draw_label(image, mx + 12, my + 12, &text, 2);
+            }
+        }
+
+        if input.field_view != FieldView::Board {
+            let text = match shadow {
+                None => "NO SHADOW FIELD, SET shadow IN THE CONFIG".to_string(),
+                Some(_) if difference => {
+                    let rms = (field.mapv(|d| d * d).mean().unwrap_or(0.0)).sqrt();
+                    format!("STOCHASTIC - SHADOW  RMS {}", numbers.float(rms))
+                }
+                Some(_) => "SHADOW".to_string(),
+            };
+            font::draw_label(image, margin, 0, &text, 2);
+        }
+
+        let eta = match run.metrics.last().steps_to_equilibrium {
+            Some(steps) if steps < 0.5 => Some("AT EQUILIBRIUM".to_string()),
+            Some(steps) => Some(format!(
+                "EQUILIBRIUM IN {} STEPS",
+                numbers.int(steps.round() as usize)
+            )),
+            None => None,
+        };
+        if let Some(text) = eta {
+            font::draw_label(image, board_w + margin, 0, &text, 2);
+        }
+        if governor.is_some() {
+            let text = format!("{} STEPS PER FRAME", numbers.int(steps));
+            font::draw_label(image, board_w + margin, 20, &text, 2);
+        }
+        if sim.config().boundary == Boundary::Absorbing {
+            let text = format!("ESCAPED {}", numbers.float(run.metrics.budget().escaped()));
+            font::draw_label(image, board_w + margin, 40, &text, 2);
+        }
+        let last = run.metrics.last();
+        let most = match sim.config().entropy_estimator.max_entropy(h * w) {
+            Some(most) => format!(" OF {}", numbers.float(most)),
+            None => String::new(),
+        };
+        let text = format!(
+            "ENTROPY {}{}  ENERGY {}",
+            numbers.float(last.entropy),
+            most,
+            numbers.float(last.total_energy)
+        );
+        font::draw_label(image, board_w + margin, 60, &text, 2);
+
+        if let Some((text, frames)) = &mut input.notice {
+            font::draw_label(image, margin, board_h.saturating_sub(20), text, 2);
+            *frames -= 1;
+            if *frames == 0 {
+                input.notice = None;
+            }
+        }
 
-    let mut rng = rand::thread_rng();
+        // the query, then the best matches with the highlighted one marked
+        if let Some(command_palette) = &input.command_palette {
+            let matches = command_palette.matches(&input.commands);
+            let lines = std::iter::once(format!("> {}_", command_palette.query)).chain(
+                matches
+                    .iter()
+                    .take(PALETTE_LINES)
+                    .enumerate()
+                    .map(|(k, c)| {
+                        let mark = if k == command_palette.selected {
+                            "*"
+                        } else {
+                            " "
+                        };
+                        format!("{} {}", mark, c.name)
+                    }),
+            );
+            for (k, line) in lines.enumerate() {
+                let y = board_h.saturating_sub(20 * (k + 2));
+                font::draw_label(image, margin + 20, y, &line, 2);
+            }
+        }
 
-    let mut quota = 0;
+        if input.paused {
+            let text = format!(
+                "PAUSED  STEP {}",
+                numbers.int(sim.steps().saturating_sub(input.history_offset))
+            );
+            font::draw_label(image, margin, board_h, &text, 2);
+        }
+        capture.observe(image, sim.config().hash(), sim.seed(), sim.steps());
+        if recorder.recording() {
+            match recorder.observe(&images::from_canvas(image)) {
+                Ok(true) => {}
+                Ok(false) => {
+                    let text = format!("WROTE {}", recorder.path().display());
+                    input.notice = Some((text, NOTICE_FRAMES));
+                }
+                Err(e) => {
+                    let _ = recorder.stop();
+                    let text = format!("COULDN'T RECORD: {}", e);
+                    eprintln!("{}", text);
+                    input.notice = Some((text, NOTICE_FRAMES));
+                }
+            }
+        }
 
-    // pad board with negative infinities in its borders
-    // board.slice_mut(s![0, 0..w + 2]).fill(-f64::INFINITY);
-    // board.slice_mut(s![h + 1, 0..w + 2]).fill(-f64::INFINITY);
-    // board.slice_mut(s![0..h + 2, 0]).fill(-f64::INFINITY);
-    // board.slice_mut(s![0..h + 2, w + 1]).fill(-f64::INFINITY);
+        let sleep = match config.sleep_interval_ms as u64 {
+            ms if throttled => ms.max(BACKGROUND_FRAME_MS),
+            ms => ms,
+        };
+        std::thread::sleep(std::time::Duration::from_millis(sleep));
+        // an idle window makes up the rest of its slower frame
+        if let Some(idler) = &idler {
+            if let Some(rest) = idler.frame_time().checked_sub(frame_start.elapsed()) {
+                std::thread::sleep(rest);
+            }
+        }
+    });
+}
 
-    while quota != hotspots {
-        let rx = rng.gen_range(0..w);
-        let ry = rng.gen_range(0..h);
+// the window's frames written out as they're drawn, numbered from 0 so video
+// tools take them in order, each stamped with the step it shows
+struct FrameCapture {
+    dir: PathBuf,
+    on: bool,
+    every: usize,
+    drawn: usize,
+    written: usize,
+}
 
-        if board[[ry, rx]] != 0.0 {
-            continue;
+impl FrameCapture {
+    fn new(config: &Config) -> Self {
+        let mut capture = Self {
+            dir: PathBuf::from(config.frame_output_dir.as_deref().unwrap_or("frames")),
+            on: false,
+            every: config.frame_every,
+            drawn: 0,
+            written: 0,
+        };
+        if config.frame_output_dir.is_some() {
+            if let Err(e) = capture.toggle() {
+                eprintln!("Couldn't write frames to {}: {}", capture.dir.display(), e);
+                std::process::exit(1);
+            }
         }
+        capture
+    }
 
-        let (h, w, hotspots) = (h as f64, w as f64, hotspots as f64);
-        board[[ry, rx]] = (h * w * w / h) / hotspots;
-        quota += 1;
+    // the directory frames now go to, or None once they've stopped
+    fn toggle(&mut self) -> std::io::Result<Option<&Path>> {
+        if self.on {
+            self.on = false;
+            return Ok(None);
+        }
+        std::fs::create_dir_all(&self.dir)?;
+        self.on = true;
+        self.drawn = 0;
+        Ok(Some(&self.dir))
     }
 
-    board
+    fn observe(&mut self, image: &pixel_canvas::Image, config_hash: u64, seed: u64, step: usize) {
+        if !self.on {
+            return;
+        }
+        self.drawn += 1;
+        if !(self.drawn - 1).is_multiple_of(self.every) {
+            return;
+        }
+
+        let frame = images::from_canvas(image);
+        let path = self.dir.join(format!("frame-{:08}.png", self.written));
+        let provenance = Provenance {
+            config_hash,
+            seed,
+            step,
+        };
+        match images::save(&frame, &path, provenance) {
+            Ok(()) => self.written += 1,
+            Err(e) => {
+                eprintln!("Couldn't write {}, stopping: {}", path.display(), e);
+                self.on = false;
+            }
+        }
+    }
+}
+
+// retunes the running simulation with the config's randomizer, describing what
+// changed
+fn randomize(sim: &mut Simulation, config: &Config, rng: &mut SimRng) -> String {
+    let mut retuned = sim.config().clone();
+    let changes = config.randomizer.apply(&mut retuned, rng);
+    match sim.set_config(retuned) {
+        Ok(()) if changes.is_empty() => "nothing to randomize".to_string(),
+        Ok(()) => changes.join("  "),
+        Err(e) => format!("randomizer: {}", e),
+    }
+}
+
+// a stats file that can't be written to is given up on rather than ending the run
+fn observe_series(series: &mut Option<SeriesWriter>, sim: &Simulation) {
+    if let Some(writer) = series {
+        if let Err(e) = writer.push(&SeriesRow::of(sim.steps(), sim.board())) {
+            eprintln!("Couldn't write stats, stopping: {}", e);
+            *series = None;
+        }
+    }
+}
+
+// checkpoints that fail are retried at the next one, as what's on disk is still
+// the last that succeeded
+fn observe_checkpoint(sim: &Simulation) {
+    let config = sim.config();
+    if config
+        .checkpoint_every
+        .is_some_and(|every| sim.steps().is_multiple_of(every))
+    {
+        let path = Path::new(&config.checkpoint_path);
+        if let Err(e) = format::write_checkpoint(path, sim) {
+            eprintln!("Couldn't write checkpoint {}: {}", path.display(), e);
+        }
+    }
 }
 
-fn get_config() -> Config {
-    let path = "config.json";
-    let file = File::open(path).expect("Couldn't find config.json");
-    let reader = BufReader::new(file);
+// the keys that change the simulation: a reset, which starts the metrics over
+// too, energy put down by hand, or moving the focus to a clicked cell, keeping
+// its size, or dropping it
+fn sim_key(sim: &mut Simulation, metrics: &mut RunMetrics, key: Key) {
+    if key == Key::Reset {
+        sim.reset();
+        *metrics = RunMetrics::new(sim.board(), sim.config());
+        return;
+    }
+    if let Key::Preset { index } = key {
+        // a session may come from a build with other presets
+        let Some(name) = presets::NAMES.get(index) else {
+            eprintln!("Couldn't load preset {}: there is no such preset", index);
+            return;
+        };
+        match Simulation::new(preset_config(sim, name)) {
+            Ok(loaded) => {
+                *sim = loaded;
+                *metrics = RunMetrics::new(sim.board(), sim.config());
+            }
+            Err(e) => eprintln!("Couldn't load preset {}: {}", name, e),
+        }
+        return;
+    }
+    if let Key::Deposit { row, col } = key {
+        sim.deposit((row, col), sim.config().click_energy);
+        return;
+    }
+    let focus = match key {
+        Key::Pin { row, col } => Some(Focus {
+            center: (row, col),
+            radius: sim
+                .config()
+                .focus
+                .map_or(DEFAULT_FOCUS_RADIUS, |focus| focus.radius),
+        }),
+        Key::Unpin => None,
+        _ => return,
+    };
+    let mut config = sim.config().clone();
+    config.focus = focus;
+    if let Err(e) = sim.set_config(config) {
+        eprintln!("Couldn't pin the focus: {}", e);
+    }
+}
 
-    let config: Config = serde_json::from_reader(reader).expect("Couldn't parse json");
+// a preset as the window loads it, at the size the window is and with the seed
+// of the run it replaces
+fn preset_config(sim: &Simulation, name: &str) -> Config {
+    let mut preset = presets::by_name(name).expect("listed presets exist");
+    preset.dims = sim.config().dims;
+    preset.seed = Some(sim.seed());
+    preset
+}
 
-    config
+// a reset or preset starts the window over, from a history of the new board
+fn restarted(
+    sim: &Simulation,
+    key: Key,
+    history: &mut history::History,
+    scale: &mut palette::Scale,
+) {
+    // a preset that didn't load leaves the run as it was
+    if matches!(key, Key::Reset | Key::Preset { .. }) && sim.steps() == 0 {
+        history.clear();
+        history.push(sim.board());
+        *scale = sim.config().scale_for(sim.board());
+    }
+}
+
+fn marginal_sums(board: &Array2<f64>) -> (Array1<f64>, Array1<f64>) {
+    (board.sum_axis(Axis(1)), board.sum_axis(Axis(0)))
+}
+
+const DYE: Color = Color {
+    r: 0,
+    g: 255,
+    b: 160,
+};
+const BLACK: Color = Color { r: 0, g: 0, b: 0 };
+// a cool gray none of the colormaps pass through
+const WALL: Color = Color {
+    r: 96,
+    g: 104,
+    b: 128,
+};
+
+// `a` with a fraction t of the way to `b`
+fn blend(a: Color, b: Color, t: f64) -> Color {
+    let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+    Color {
+        r: mix(a.r, b.r),
+        g: mix(a.g, b.g),
+        b: mix(a.b, b.b),
+    }
+}
+
+fn darken(color: Color) -> Color {
+    Color {
+        r: color.r / 2,
+        g: color.g / 2,
+        b: color.b / 2 + 40,
+    }
+}
+
+// a bar of length proportional to value / max, growing away from the board
+#[inline(always)]
+fn marginal_pixel(value: f64, max: f64, offset: usize, length: usize) -> Color {
+    let filled = if max > 0.0 {
+        (value / max * length as f64) as usize
+    } else {
+        0
+    };
+
+    if offset < filled {
+        Color {
+            r: 220,
+            g: 220,
+            b: 220,
+        }
+    } else {
+        Color { r: 0, g: 0, b: 0 }
+    }
 }
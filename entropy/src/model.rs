@@ -0,0 +1,1977 @@
+use crate::field::{self, Spectrum};
+use crate::lattice::Lattice;
+use crate::transform::{load_field, load_image, load_luminance, Transform};
+use crate::{Board, Config, ConfigError};
+use image::imageops::FilterType;
+use itertools::iproduct;
+use ndarray::Array2;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex};
+
+pub type SimRng = ChaCha8Rng;
+
+// implementations of the time step; every one of them must agree with `Scalar`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Scalar,
+    // the scalar step split by rows across config.threads threads
+    Parallel,
+}
+
+impl Backend {
+    pub const ALL: &'static [Backend] = &[Backend::Scalar, Backend::Parallel];
+}
+
+// how a step moves energy: `Scatter` has each cell push its energy over its
+// clipped stencil, so edge and corner cells, with smaller stencils, keep more of
+// theirs and end up hotter than the rest; `Gather` exchanges energy between each
+// pair of neighbors with one shared weight, which settles to exactly uniform;
+// `Lbm` solves the advection-diffusion equation with a lattice Boltzmann scheme
+// instead of drawing weights
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheme {
+    #[default]
+    Scatter,
+    Gather,
+    Lbm,
+}
+
+// a square the user pins on the board: only its cells draw random weights, and
+// everywhere else takes the average step, so a huge board stays interactive
+// around the part being looked at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Focus {
+    pub center: (usize, usize),
+    // cells at most this far from the center, in either direction, are in it
+    #[serde(default = "default_focus_radius")]
+    pub radius: usize,
+}
+
+pub const DEFAULT_FOCUS_RADIUS: usize = 16;
+
+fn default_focus_radius() -> usize {
+    DEFAULT_FOCUS_RADIUS
+}
+
+impl Focus {
+    #[inline(always)]
+    pub fn contains(&self, (i, j): (usize, usize)) -> bool {
+        i.abs_diff(self.center.0) <= self.radius && j.abs_diff(self.center.1) <= self.radius
+    }
+
+    // the ring of cells just inside it
+    pub fn on_edge(&self, (i, j): (usize, usize)) -> bool {
+        self.contains((i, j))
+            && (i.abs_diff(self.center.0) == self.radius
+                || j.abs_diff(self.center.1) == self.radius)
+    }
+}
+
+// whether weights are drawn at random or fixed; fixed, even weights make a step
+// the discrete heat equation, to compare the model against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    #[default]
+    Stochastic,
+    Deterministic,
+}
+
+// whether a cell draws random weights this step
+#[inline(always)]
+fn stochastic(cell: (usize, usize), config: &Config) -> bool {
+    config.mode == Mode::Stochastic && config.focus.is_none_or(|focus| focus.contains(cell))
+}
+
+// what happens to energy that would leave the board
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Boundary {
+    // the stencil is clipped at the edges, so nothing leaves
+    #[default]
+    Closed,
+    // the board is a torus: what leaves one edge comes back in at the opposite one
+    Periodic,
+    // the edges open onto a reservoir at zero energy, and what crosses them is gone
+    Absorbing,
+}
+
+impl Boundary {
+    // the cell `offset` away from `cell`, or None past an absorbing edge
+    #[inline(always)]
+    pub(crate) fn neighbor(
+        self,
+        (i, j): (usize, usize),
+        (di, dj): (isize, isize),
+        (h, w): (usize, usize),
+    ) -> Option<(usize, usize)> {
+        let (ni, nj) = (i as isize + di, j as isize + dj);
+        match self {
+            Boundary::Periodic => Some((
+                ni.rem_euclid(h as isize) as usize,
+                nj.rem_euclid(w as isize) as usize,
+            )),
+            // a closed stencil never reaches past the edge
+            Boundary::Closed | Boundary::Absorbing => {
+                let inside = (0..h as isize).contains(&ni) && (0..w as isize).contains(&nj);
+                inside.then_some((ni as usize, nj as usize))
+            }
+        }
+    }
+}
+
+// the largest kernel radius, so a stencil fits in a fixed buffer
+pub const MAX_KERNEL_RADIUS: usize = 3;
+const MAX_STENCIL: usize = (2 * MAX_KERNEL_RADIUS + 1) * (2 * MAX_KERNEL_RADIUS + 1);
+
+// how far a cell spreads its energy in one step and how it favors each offset
+// within that; the weights a cell draws are multiplied by these and renormalized.
+// a matrix has an odd side, with the cell itself at its center
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kernel {
+    Uniform {
+        #[serde(default = "default_kernel_radius")]
+        radius: usize,
+    },
+    // falls off as a gaussian of the distance; sigma is half the radius if absent
+    Gaussian {
+        #[serde(default = "default_kernel_radius")]
+        radius: usize,
+        #[serde(default)]
+        sigma: Option<f64>,
+    },
+    // only offsets at most `radius` steps away along rows and columns together
+    #[serde(alias = "vonNeumann")]
+    VonNeumann {
+        #[serde(default = "default_kernel_radius")]
+        radius: usize,
+    },
+    Matrix(Vec<Vec<f64>>),
+}
+
+fn default_kernel_radius() -> usize {
+    1
+}
+
+impl Default for Kernel {
+    fn default() -> Self {
+        Kernel::Uniform { radius: 1 }
+    }
+}
+
+impl Kernel {
+    pub fn radius(&self) -> usize {
+        match self {
+            Kernel::Uniform { radius }
+            | Kernel::Gaussian { radius, .. }
+            | Kernel::VonNeumann { radius } => *radius,
+            Kernel::Matrix(m) => m.len() / 2,
+        }
+    }
+
+    // the side of the full stencil
+    pub fn side(&self) -> usize {
+        2 * self.radius() + 1
+    }
+
+    // every offset weighs the same, so biasing by it changes nothing
+    pub fn is_flat(&self) -> bool {
+        matches!(self, Kernel::Uniform { .. })
+    }
+
+    #[inline(always)]
+    pub fn weight(&self, (di, dj): (isize, isize)) -> f64 {
+        match self {
+            Kernel::Uniform { .. } => 1.0,
+            Kernel::Gaussian { radius, sigma } => {
+                let sigma = sigma.unwrap_or(*radius as f64 / 2.0);
+                (-((di * di + dj * dj) as f64) / (2.0 * sigma * sigma)).exp()
+            }
+            Kernel::VonNeumann { radius } => {
+                ((di.unsigned_abs() + dj.unsigned_abs()) <= *radius) as u8 as f64
+            }
+            Kernel::Matrix(m) => {
+                let r = (m.len() / 2) as isize;
+                m[(di + r) as usize][(dj + r) as usize]
+            }
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let radius_ok = (1..=MAX_KERNEL_RADIUS).contains(&self.radius());
+        match self {
+            Kernel::Uniform { .. } | Kernel::VonNeumann { .. } => radius_ok,
+            Kernel::Gaussian { sigma, .. } => radius_ok && sigma.is_none_or(|s| s > 0.0),
+            Kernel::Matrix(m) => {
+                let r = m.len() / 2;
+                radius_ok
+                    && m.len() % 2 == 1
+                    && m.iter().all(|row| row.len() == m.len())
+                    && m.iter().flatten().all(|w| w.is_finite() && *w >= 0.0)
+                    && m[r][r] > 0.0
+            }
+        }
+    }
+}
+
+// a passive dye moved by the same weights as the energy, so the window can show
+// how the board mixes; it never changes the energy. a dyed cell starts with one
+// unit of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tracer {
+    LeftHalf,
+    // toward the top of the window, the last rows
+    TopHalf,
+    // cells at most `radius` from `center` in either direction
+    Square {
+        center: (usize, usize),
+        radius: usize,
+    },
+}
+
+impl Tracer {
+    pub fn initial(&self, (h, w): (usize, usize)) -> Array2<f64> {
+        Array2::from_shape_fn((h, w), |(i, j)| {
+            let dyed = match *self {
+                Tracer::LeftHalf => 2 * j < w,
+                Tracer::TopHalf => 2 * i >= h,
+                Tracer::Square { center, radius } => {
+                    i.abs_diff(center.0) <= radius && j.abs_diff(center.1) <= radius
+                }
+            };
+            dyed as u8 as f64
+        })
+    }
+}
+
+// adds to the dye's next board, if there is a dye
+#[inline(always)]
+fn carry(dye: &mut Option<Array2<f64>>, cell: (usize, usize), amount: f64) {
+    if let Some(dye) = dye {
+        dye[cell] += amount;
+    }
+}
+
+// a thermostat: after each step, coupled cells relax toward the bath temperature
+// by `coupling` of the difference
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bath {
+    pub temperature: f64,
+    pub coupling: f64,
+    #[serde(default)]
+    pub region: BathRegion,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BathRegion {
+    #[default]
+    All,
+    // only the outermost ring of cells
+    Boundary,
+    // the left column is held at the bath temperature, the right one at another
+    LeftRight {
+        right_temperature: f64,
+    },
+    // the top row is held at the bath temperature, the bottom one at another
+    TopBottom {
+        bottom_temperature: f64,
+    },
+}
+
+impl Bath {
+    // returns the net energy added, in total and at the two held edges of a
+    // left_right or top_bottom bath; walls aren't coupled
+    pub fn apply(&self, board: &mut impl Board, walls: Option<&Array2<bool>>) -> (f64, [f64; 2]) {
+        let (h, w) = board.dims();
+        let mut total = 0.0;
+        let mut edges = [0.0; 2];
+
+        for cell in iproduct!(0..h, 0..w) {
+            let (i, j) = cell;
+            let (temperature, edge) = match self.region {
+                BathRegion::All => (self.temperature, None),
+                BathRegion::Boundary if i == 0 || j == 0 || i == h - 1 || j == w - 1 => {
+                    (self.temperature, None)
+                }
+                BathRegion::LeftRight { .. } if j == 0 => (self.temperature, Some(0)),
+                BathRegion::LeftRight { right_temperature } if j == w - 1 => {
+                    (right_temperature, Some(1))
+                }
+                BathRegion::TopBottom { .. } if i == 0 => (self.temperature, Some(0)),
+                BathRegion::TopBottom { bottom_temperature } if i == h - 1 => {
+                    (bottom_temperature, Some(1))
+                }
+                _ => continue,
+            };
+            if walls.is_some_and(|walls| walls[cell]) {
+                continue;
+            }
+
+            let e = board.get(cell);
+            let delta = self.coupling * (temperature - e);
+            board.set(cell, e + delta);
+
+            total += delta;
+            if let Some(edge) = edge {
+                edges[edge] += delta;
+            }
+        }
+
+        (total, edges)
+    }
+}
+
+// long-range transport: `fraction` of each cell's energy jumps a distance r drawn
+// from p(r) ~ r^-(1 + exponent), r >= 1, in a uniformly random direction
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Levy {
+    pub fraction: f64,
+    pub exponent: f64,
+}
+
+impl Levy {
+    // the landing cell of a jump from `cell`, reflected back into a closed board
+    // and wrapped around a periodic one; None if it leaves an absorbing one
+    #[inline(always)]
+    fn target(
+        &self,
+        (i, j): (usize, usize),
+        (h, w): (usize, usize),
+        boundary: Boundary,
+        rng: &mut SimRng,
+    ) -> Option<(usize, usize)> {
+        let u: f64 = rng.gen();
+        let r = (1.0 - u).powf(-1.0 / self.exponent);
+        let theta = rng.gen::<f64>() * std::f64::consts::TAU;
+
+        let (x, y) = (i as f64 + r * theta.sin(), j as f64 + r * theta.cos());
+        match boundary {
+            Boundary::Closed => Some((reflect(x, h), reflect(y, w))),
+            Boundary::Periodic => Some((wrap(x, h), wrap(y, w))),
+            Boundary::Absorbing => {
+                let (x, y) = (x.round(), y.round());
+                let inside = (0.0..h as f64).contains(&x) && (0.0..w as f64).contains(&y);
+                inside.then_some((x as usize, y as usize))
+            }
+        }
+    }
+}
+
+#[inline(always)]
+fn wrap(x: f64, n: usize) -> usize {
+    (x.round().clamp(-1e15, 1e15) as i64).rem_euclid(n as i64) as usize
+}
+
+// folds a coordinate into 0..n as if the edges were mirrors
+#[inline(always)]
+fn reflect(x: f64, n: usize) -> usize {
+    let period = 2 * (n - 1);
+    let x = (x.round().clamp(-1e15, 1e15) as i64).rem_euclid(period as i64) as usize;
+
+    if x < n {
+        x
+    } else {
+        period - x
+    }
+}
+
+// continuous-time random walk: a cell only releases its energy when its timer
+// runs out, after which a new wait t >= 1 is drawn from p(t) ~ t^-(1 + exponent).
+// exponents below 1 have no mean wait and spread subdiffusively
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Waiting {
+    pub exponent: f64,
+}
+
+impl Waiting {
+    #[inline(always)]
+    fn draw(&self, rng: &mut SimRng) -> u32 {
+        let u: f64 = rng.gen();
+        (1.0 - u).powf(-1.0 / self.exponent).min(u32::MAX as f64) as u32
+    }
+}
+
+// the steps each cell still has to wait under `Waiting`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WaitingTimers(Array2<u32>);
+
+impl WaitingTimers {
+    pub fn new(dims: (usize, usize), waiting: &Waiting, rng: &mut SimRng) -> Self {
+        Self(Array2::from_shape_simple_fn(dims, || waiting.draw(rng)))
+    }
+
+    // counts every timer down and takes the energy of the cells that are still
+    // waiting out of `board`, to be put back after the step. cells whose timer
+    // ran out draw a new one and are left to release
+    pub fn withhold(
+        &mut self,
+        board: &mut Array2<f64>,
+        waiting: &Waiting,
+        rng: &mut SimRng,
+    ) -> Array2<f64> {
+        let mut held = Array2::zeros(board.dim());
+
+        for ((timer, e), h) in self.0.iter_mut().zip(board.iter_mut()).zip(held.iter_mut()) {
+            *timer = timer.saturating_sub(1);
+            if *timer == 0 {
+                *timer = waiting.draw(rng);
+            } else {
+                *h = std::mem::take(e);
+            }
+        }
+
+        held
+    }
+}
+
+// cells that capture part of their energy after each step and give it back
+// `hold_steps` steps later
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Traps {
+    pub fraction: f64,
+    pub hold_steps: usize,
+    // probability of each cell being a trap, unless there is a mask
+    #[serde(default)]
+    pub density: f64,
+    // .npy array shaped like the board, nonzero at traps
+    #[serde(default)]
+    pub mask: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrapSites {
+    cells: Vec<(usize, usize)>,
+    // what the traps captured on each of the last hold_steps steps, oldest first
+    held: VecDeque<Vec<f64>>,
+}
+
+impl TrapSites {
+    pub fn place(
+        traps: &Traps,
+        dims: (usize, usize),
+        transform: &Transform,
+        rng: &mut SimRng,
+    ) -> Result<Self, ConfigError> {
+        let cells = match &traps.mask {
+            Some(path) => {
+                let mask = load_field(path, transform).map_err(ConfigError::BadTrapMask)?;
+                if mask.dim() != dims {
+                    return Err(ConfigError::BadTrapMask(format!(
+                        "{} is {:?} after transforming, the board is {:?}",
+                        path,
+                        mask.dim(),
+                        dims
+                    )));
+                }
+                mask.indexed_iter()
+                    .filter(|(_, &m)| m != 0.0)
+                    .map(|(cell, _)| cell)
+                    .collect()
+            }
+            None => iproduct!(0..dims.0, 0..dims.1)
+                .filter(|_| rng.gen::<f64>() < traps.density)
+                .collect(),
+        };
+
+        Ok(Self {
+            cells,
+            held: VecDeque::new(),
+        })
+    }
+
+    pub fn cells(&self) -> &[(usize, usize)] {
+        &self.cells
+    }
+
+    pub fn held_energy(&self) -> f64 {
+        self.held.iter().flatten().sum()
+    }
+
+    // lets go of everything without putting it back on the board
+    pub fn clear(&mut self) {
+        self.held.clear();
+    }
+
+    // captures this step's share and releases what was captured hold_steps ago
+    pub fn apply(&mut self, board: &mut impl Board, traps: &Traps) {
+        let captured = self
+            .cells
+            .iter()
+            .map(|&cell| {
+                let e = board.get(cell);
+                board.set(cell, e * (1.0 - traps.fraction));
+                e * traps.fraction
+            })
+            .collect();
+        self.held.push_back(captured);
+
+        if self.held.len() > traps.hold_steps {
+            let released = self.held.pop_front().unwrap();
+            for (&cell, e) in self.cells.iter().zip(released) {
+                board.add(cell, e);
+            }
+        }
+    }
+}
+
+// a heater that adds `power` per step to the cell under a point moving along `path`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Source {
+    pub power: f64,
+    pub path: SourcePath,
+}
+
+// positions are (row, col) and may be fractional; periods are in steps
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourcePath {
+    Fixed {
+        at: (f64, f64),
+    },
+    Circle {
+        center: (f64, f64),
+        radius: f64,
+        period: f64,
+    },
+    // back and forth between the two ends
+    Line {
+        from: (f64, f64),
+        to: (f64, f64),
+        period: f64,
+    },
+    RandomWalk {
+        start: (f64, f64),
+        step_size: f64,
+    },
+}
+
+impl SourcePath {
+    // where the source is at `step`, given where it was on the step before
+    fn position(
+        &self,
+        step: usize,
+        previous: Option<(f64, f64)>,
+        (h, w): (usize, usize),
+        rng: &mut SimRng,
+    ) -> (f64, f64) {
+        let t = step as f64;
+
+        match *self {
+            SourcePath::Fixed { at } => at,
+            SourcePath::Circle {
+                center,
+                radius,
+                period,
+            } => {
+                let theta = std::f64::consts::TAU * t / period;
+                (
+                    center.0 + radius * theta.sin(),
+                    center.1 + radius * theta.cos(),
+                )
+            }
+            SourcePath::Line { from, to, period } => {
+                // triangle wave from 0 to 1 and back
+                let phase = (t / period).fract();
+                let s = 1.0 - (2.0 * phase - 1.0).abs();
+                (from.0 + s * (to.0 - from.0), from.1 + s * (to.1 - from.1))
+            }
+            SourcePath::RandomWalk { start, step_size } => match previous {
+                None => start,
+                Some((i, j)) => {
+                    let theta = rng.gen::<f64>() * std::f64::consts::TAU;
+                    (
+                        (i + step_size * theta.sin()).clamp(0.0, (h - 1) as f64),
+                        (j + step_size * theta.cos()).clamp(0.0, (w - 1) as f64),
+                    )
+                }
+            },
+        }
+    }
+}
+
+// moves every source along its path and heats the cell under it, unless it's a
+// wall; `positions` remembers where each one was. returns the energy added
+pub fn heat_sources(
+    board: &mut impl Board,
+    sources: &[Source],
+    positions: &mut Vec<Option<(f64, f64)>>,
+    step: usize,
+    rng: &mut SimRng,
+    walls: Option<&Array2<bool>>,
+) -> f64 {
+    let (h, w) = board.dims();
+    positions.resize(sources.len(), None);
+    let mut added = 0.0;
+
+    for (source, position) in sources.iter().zip(positions.iter_mut()) {
+        let (i, j) = source.path.position(step, *position, (h, w), rng);
+        *position = Some((i, j));
+
+        let cell = (
+            (i.round().max(0.0) as usize).min(h - 1),
+            (j.round().max(0.0) as usize).min(w - 1),
+        );
+        if walls.is_none_or(|walls| !walls[cell]) {
+            board.add(cell, source.power);
+            added += source.power;
+        }
+    }
+
+    added
+}
+
+// cells given one by one as (row, col), or as a rectangle of rows and columns,
+// each range's end excluded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cells {
+    List(Vec<(usize, usize)>),
+    Rect {
+        rows: (usize, usize),
+        cols: (usize, usize),
+    },
+}
+
+impl Cells {
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (usize, usize)> + '_> {
+        match self {
+            Cells::List(cells) => Box::new(cells.iter().copied()),
+            Cells::Rect { rows, cols } => Box::new(iproduct!(rows.0..rows.1, cols.0..cols.1)),
+        }
+    }
+
+    pub fn fits(&self, (h, w): (usize, usize)) -> bool {
+        match self {
+            Cells::List(cells) => cells.iter().all(|&(i, j)| i < h && j < w),
+            Cells::Rect { rows, cols } => rows.1 <= h && cols.1 <= w,
+        }
+    }
+}
+
+// cells that gain `power` each every step, after the diffusion
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FixedSource {
+    pub cells: Cells,
+    pub power: f64,
+}
+
+// the energy the fixed sources add; a cell listed twice gets it twice, and a
+// wall none
+pub fn fixed_sources(
+    board: &mut impl Board,
+    sources: &[FixedSource],
+    walls: Option<&Array2<bool>>,
+) -> f64 {
+    let mut added = 0.0;
+    for source in sources {
+        for cell in source.cells.iter() {
+            if walls.is_some_and(|walls| walls[cell]) {
+                continue;
+            }
+            board.add(cell, source.power);
+            added += source.power;
+        }
+    }
+    added
+}
+
+// empties the sink cells, returning the energy taken out
+pub fn drain_sinks(board: &mut impl Board, sinks: &[Cells]) -> f64 {
+    let mut drained = 0.0;
+    for cell in sinks.iter().flat_map(Cells::iter) {
+        drained += board.get(cell);
+        board.set(cell, 0.0);
+    }
+    drained
+}
+
+// impermeable cells, such as the walls of a maze: nothing flows into them, and
+// what a neighbor would have sent one is shared out over the rest of its stencil
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Walls {
+    #[serde(default)]
+    pub cells: Vec<Cells>,
+    // a black-and-white image, resized to the board, whose dark pixels are walls
+    #[serde(default)]
+    pub image: Option<String>,
+    // the last mask worked out, with what it was worked out from
+    #[serde(skip)]
+    mask: Mutex<Option<(MaskKey, Arc<Array2<bool>>)>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct MaskKey {
+    dims: (usize, usize),
+    transform: Transform,
+    cells: Vec<Cells>,
+    image: Option<String>,
+}
+
+impl Clone for Walls {
+    fn clone(&self) -> Self {
+        Self {
+            cells: self.cells.clone(),
+            image: self.image.clone(),
+            mask: Mutex::new(self.mask.lock().unwrap().clone()),
+        }
+    }
+}
+
+impl Walls {
+    pub fn new(cells: Vec<Cells>, image: Option<String>) -> Self {
+        Self {
+            cells,
+            image,
+            mask: Mutex::new(None),
+        }
+    }
+
+    // true at the walls; the image is transformed like the other files read.
+    // made again only when the cells, image, dims or transform have changed
+    pub fn mask(
+        &self,
+        dims: (usize, usize),
+        transform: &Transform,
+    ) -> Result<Arc<Array2<bool>>, String> {
+        let key = MaskKey {
+            dims,
+            transform: *transform,
+            cells: self.cells.clone(),
+            image: self.image.clone(),
+        };
+        let mut cached = self.mask.lock().unwrap();
+        if let Some((_, mask)) = cached.as_ref().filter(|(k, _)| *k == key) {
+            return Ok(Arc::clone(mask));
+        }
+
+        let mut mask = match &self.image {
+            // nearest, so a maze's corridors don't close up when it's scaled down
+            Some(path) => load_luminance(path, dims, FilterType::Nearest, transform)?
+                .mapv(|luma| luma < 128.0),
+            None => Array2::from_elem(dims, false),
+        };
+        for cell in self.cells.iter().flat_map(Cells::iter) {
+            mask[cell] = true;
+        }
+        let mask = Arc::new(mask);
+        *cached = Some((key, Arc::clone(&mask)));
+        Ok(mask)
+    }
+}
+
+// biases the weights each cell draws so more of its energy moves along the local
+// velocity; components are (row, col) within [-1, 1], and at 1 nothing moves back
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Drift {
+    Uniform { velocity: (f64, f64) },
+    // the left half of the board drifts right and the right half left
+    Opposing { speed: f64 },
+    // cells hotter than the board's mean rise (toward the top of the window, the
+    // last row) and cooler ones sink, at `coefficient` times the difference,
+    // clamped to `max_speed` so hot spots can't push everything one way
+    Buoyancy { coefficient: f64, max_speed: f64 },
+}
+
+impl Drift {
+    // `excess` is the cell's energy above the board's mean
+    pub fn velocity(
+        &self,
+        (_, j): (usize, usize),
+        (_, w): (usize, usize),
+        excess: f64,
+    ) -> (f64, f64) {
+        match *self {
+            Drift::Uniform { velocity } => velocity,
+            Drift::Opposing { speed } if 2 * j + 1 < w => (0.0, speed),
+            Drift::Opposing { speed } if 2 * j + 1 > w => (0.0, -speed),
+            Drift::Opposing { .. } => (0.0, 0.0),
+            Drift::Buoyancy {
+                coefficient,
+                max_speed,
+            } => ((coefficient * excess).clamp(-max_speed, max_speed), 0.0),
+        }
+    }
+
+    #[inline(always)]
+    fn bias(
+        &self,
+        weights: &mut [f64],
+        cell: (usize, usize),
+        (rows, cols): (RangeInclusive<isize>, RangeInclusive<isize>),
+        dims: (usize, usize),
+        excess: f64,
+    ) {
+        let (vi, vj) = self.velocity(cell, dims, excess);
+        let mut s = 0.0;
+
+        for (x, (di, dj)) in weights.iter_mut().zip(iproduct!(rows, cols)) {
+            // past one step away a fast drift would make weights negative
+            *x *= (1.0 + vi * di as f64).max(0.0) * (1.0 + vj * dj as f64).max(0.0);
+            s += *x;
+        }
+
+        for x in weights.iter_mut() {
+            *x /= s;
+        }
+    }
+}
+
+// energy per unit temperature of each cell. transport favors cells that can hold
+// more, so the board settles at a uniform temperature rather than a uniform energy
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeatCapacity {
+    #[default]
+    Uniform,
+    // two materials side by side
+    Halves {
+        left: f64,
+        right: f64,
+    },
+    // one capacity per cell
+    Field(Array2<f64>),
+}
+
+impl HeatCapacity {
+    #[inline(always)]
+    pub fn at(&self, (i, j): (usize, usize), (_, w): (usize, usize)) -> f64 {
+        match self {
+            HeatCapacity::Uniform => 1.0,
+            HeatCapacity::Halves { left, .. } if 2 * j < w => *left,
+            HeatCapacity::Halves { right, .. } => *right,
+            HeatCapacity::Field(c) => c[[i, j]],
+        }
+    }
+
+    pub fn temperature(&self, board: &Array2<f64>) -> Array2<f64> {
+        let dims = board.dim();
+        Array2::from_shape_fn(dims, |cell| board[cell] / self.at(cell, dims))
+    }
+
+    #[inline(always)]
+    fn bias(
+        &self,
+        weights: &mut [f64],
+        cell: (usize, usize),
+        (rows, cols): (RangeInclusive<isize>, RangeInclusive<isize>),
+        dims: (usize, usize),
+        boundary: Boundary,
+    ) {
+        let mut s = 0.0;
+
+        for (x, offset) in weights.iter_mut().zip(iproduct!(rows, cols)) {
+            // the reservoir past an absorbing edge is taken to be like the cell
+            let target = boundary.neighbor(cell, offset, dims).unwrap_or(cell);
+            *x *= self.at(target, dims);
+            s += *x;
+        }
+
+        for x in weights.iter_mut() {
+            *x /= s;
+        }
+    }
+}
+
+// melting: a cell's enthalpy is its energy plus what it holds as latent heat.
+// below `melting_point` it is solid and all of it shows as energy; above, the
+// first `latent_heat` goes into the latent store before the energy rises again,
+// and freezing gives it back
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PhaseChange {
+    pub melting_point: f64,
+    pub latent_heat: f64,
+}
+
+impl PhaseChange {
+    pub fn apply(&self, board: &mut Array2<f64>, latent: &mut Array2<f64>) {
+        for (e, l) in board.iter_mut().zip(latent.iter_mut()) {
+            let enthalpy = *e + *l;
+
+            (*e, *l) = if enthalpy <= self.melting_point {
+                (enthalpy, 0.0)
+            } else if enthalpy <= self.melting_point + self.latent_heat {
+                (self.melting_point, enthalpy - self.melting_point)
+            } else {
+                (enthalpy - self.latent_heat, self.latent_heat)
+            };
+        }
+    }
+
+    pub fn is_solid(&self, energy: f64) -> bool {
+        energy < self.melting_point
+    }
+}
+
+// what a stochastic reset puts back to the initial condition
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResetScope {
+    // every cell resets independently
+    #[default]
+    Cell,
+    // the whole board resets at once
+    Field,
+}
+
+// with probability `rate`, each cell (or the whole board) goes back to `initial`,
+// walls aside; returns the net energy added
+pub fn stochastic_reset<B: Board>(
+    board: &mut B,
+    initial: &B,
+    rate: f64,
+    scope: ResetScope,
+    rng: &mut SimRng,
+    walls: Option<&Array2<bool>>,
+) -> f64 {
+    let (h, w) = board.dims();
+    let mut added = 0.0;
+
+    match scope {
+        ResetScope::Cell => {
+            for cell in iproduct!(0..h, 0..w) {
+                // drawn for walls too, so the draws don't depend on where they are
+                if rng.gen::<f64>() < rate && walls.is_none_or(|walls| !walls[cell]) {
+                    added += initial.get(cell) - board.get(cell);
+                    board.set(cell, initial.get(cell));
+                }
+            }
+        }
+        ResetScope::Field => {
+            if rng.gen::<f64>() < rate {
+                let before = board.total();
+                for cell in iproduct!(0..h, 0..w) {
+                    if walls.is_none_or(|walls| !walls[cell]) {
+                        board.set(cell, initial.get(cell));
+                    }
+                }
+                added = board.total() - before;
+            }
+        }
+    }
+
+    added
+}
+
+// adds zero-mean gaussian noise of standard deviation `sigma` to every cell, a
+// thermal background that keeps equilibrium fluctuating; white unless given a
+// spectrum. cells don't go below zero, so on average this adds energy, and
+// walls are left out. returns the net energy added
+pub fn thermal_noise<B: Board>(
+    board: &mut B,
+    sigma: f64,
+    spectrum: Option<&Spectrum>,
+    rng: &mut SimRng,
+    walls: Option<&Array2<bool>>,
+) -> f64 {
+    let (h, w) = board.dims();
+    let mut added = 0.0;
+    let correlated = spectrum.map(|spectrum| field::gaussian_field((h, w), spectrum, rng));
+
+    for cell in iproduct!(0..h, 0..w) {
+        let z = match &correlated {
+            Some(field) => field[cell],
+            None => field::standard_normal(rng),
+        };
+        if walls.is_some_and(|walls| walls[cell]) {
+            continue;
+        }
+        let energy = board.get(cell);
+        let noisy = (energy + sigma * z).max(0.0);
+        added += noisy - energy;
+        board.set(cell, noisy);
+    }
+
+    added
+}
+
+// what to do with the tiny negative or subnormal energies that rounding leaves
+// behind; larger negatives are bugs, and are left for `debug` to catch
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClampPolicy {
+    // leave them be
+    #[default]
+    Keep,
+    // set them to zero, adding or removing a little energy
+    Zero,
+    // set them to zero and take the energy that made up from the rest of the board
+    Redistribute,
+    // stop the run
+    Error,
+}
+
+// how negative an energy may be and still count as rounding
+pub const CLAMP_EPSILON: f64 = 1e-12;
+
+impl ClampPolicy {
+    // returns the energy added to the board, or the first offending cell under Error
+    pub fn apply<B: Board>(&self, board: &mut B) -> Result<f64, ((usize, usize), f64)> {
+        if *self == ClampPolicy::Keep {
+            return Ok(0.0);
+        }
+        let (h, w) = board.dims();
+
+        let mut added = 0.0;
+        for cell in iproduct!(0..h, 0..w) {
+            let e = board.get(cell);
+            let tiny = (-CLAMP_EPSILON..0.0).contains(&e) || e.is_subnormal();
+            if !tiny {
+                continue;
+            }
+            if *self == ClampPolicy::Error {
+                return Err((cell, e));
+            }
+            board.set(cell, 0.0);
+            added -= e;
+        }
+
+        if *self == ClampPolicy::Redistribute && added != 0.0 {
+            let total = board.total();
+            if total > 0.0 {
+                let scale = (total - added) / total;
+                for cell in iproduct!(0..h, 0..w) {
+                    board.set(cell, board.get(cell) * scale);
+                }
+            }
+            return Ok(board.total() - total);
+        }
+
+        Ok(added)
+    }
+}
+
+// what the kernel noticed about each cell on a step, as bits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelFlags(pub Array2<u8>);
+
+impl KernelFlags {
+    // the weights drawn didn't sum to 1 within assertion_tolerance, or one was
+    // negative or NaN
+    pub const UNNORMALIZED: u8 = 1;
+    // the stencil was cut off by a wall, or the drift speed was clamped
+    pub const CLAMPED: u8 = 2;
+
+    pub fn count(&self, flag: u8) -> usize {
+        self.0.iter().filter(|&&f| f & flag != 0).count()
+    }
+}
+
+// what happened during a step besides the board update itself
+#[derive(Debug, Clone, Default)]
+pub struct StepReport {
+    // the weights drawn for the watched cell, if any
+    pub watched: Option<Array2<f64>>,
+    // net energy added by the bath
+    pub bath_in: f64,
+    // net energy added at the two held edges of a gradient bath
+    pub held_edge_in: [f64; 2],
+    // net energy added by stochastic resetting
+    pub reset_in: f64,
+    // energy added by heat sources, moving and fixed, and by hand
+    pub source_in: f64,
+    // energy taken out by sinks
+    pub sink_out: f64,
+    // energy held in traps after the step
+    pub trapped: f64,
+    // latent heat held by melted cells after the step
+    pub latent: f64,
+    // cells below active_threshold, which kept their energy this step
+    pub skipped: usize,
+    // only when config.checks_kernel()
+    pub flags: Option<KernelFlags>,
+    // net energy added by the clamp policy
+    pub clamped_in: f64,
+    // energy lost past an absorbing boundary
+    pub escaped: f64,
+    // net energy added by thermal noise
+    pub noise_in: f64,
+}
+
+// advances `lagged_board` by one step, using `board` as scratch space
+pub fn board_time_step<B: Board>(
+    board: &mut B,
+    lagged_board: &mut B,
+    config: &Config,
+    rng: &mut SimRng,
+    watch: Option<(usize, usize)>,
+) -> StepReport {
+    traced_time_step(board, lagged_board, None, config, rng, watch)
+}
+
+// a step that also moves `tracer`, a dye over the board, along with the energy;
+// the gather scheme has no per-cell weights to move it with. the lbm scheme
+// carries its populations from step to step, so it's stepped with
+// lattice_time_step instead, as Simulation does
+pub fn traced_time_step<B: Board>(
+    board: &mut B,
+    lagged_board: &mut B,
+    tracer: Option<&mut Array2<f64>>,
+    config: &Config,
+    rng: &mut SimRng,
+    watch: Option<(usize, usize)>,
+) -> StepReport {
+    let mut parallel = ParallelScratch::default();
+    scratch_time_step(
+        board,
+        lagged_board,
+        tracer,
+        config,
+        rng,
+        watch,
+        &mut parallel,
+    )
+}
+
+// traced_time_step with the parallel backend's buffers and thread pool kept in
+// `parallel`, for a run of many steps
+pub fn scratch_time_step<B: Board>(
+    board: &mut B,
+    lagged_board: &mut B,
+    tracer: Option<&mut Array2<f64>>,
+    config: &Config,
+    rng: &mut SimRng,
+    watch: Option<(usize, usize)>,
+    parallel: &mut ParallelScratch,
+) -> StepReport {
+    let skipped = if config.active_threshold > 0.0 {
+        let (h, w) = config.dims;
+        iproduct!(0..h, 0..w)
+            .filter(|&cell| lagged_board.get(cell) < config.active_threshold)
+            .count()
+    } else {
+        0
+    };
+
+    let (watched, flags, escaped) = match (config.scheme, config.backend) {
+        (Scheme::Gather, _) => (
+            None,
+            None,
+            gather_time_step(board, lagged_board, config, rng),
+        ),
+        (Scheme::Lbm, _) => {
+            panic!("the lbm scheme keeps a lattice between steps; use lattice_time_step")
+        }
+        (Scheme::Scatter, Backend::Scalar) => {
+            scalar_time_step(board, lagged_board, tracer, config, rng, watch)
+        }
+        (Scheme::Scatter, Backend::Parallel) => {
+            parallel_time_step(lagged_board, tracer, config, rng, watch, parallel)
+        }
+    };
+    let mut report = StepReport {
+        skipped,
+        watched,
+        flags,
+        escaped,
+        ..StepReport::default()
+    };
+    settle(lagged_board, config, &mut report);
+    report
+}
+
+// a lattice Boltzmann step that carries `lattice` over from the last one
+pub fn lattice_time_step(
+    board: &mut Array2<f64>,
+    lattice: &mut Lattice,
+    config: &Config,
+) -> StepReport {
+    let mut report = StepReport {
+        escaped: lattice.step(board, config),
+        ..StepReport::default()
+    };
+    settle(board, config, &mut report);
+    report
+}
+
+// the bath and the clamp policy, after every scheme
+fn settle<B: Board>(lagged_board: &mut B, config: &Config, report: &mut StepReport) {
+    if let Some(bath) = &config.bath {
+        let walls = config.wall_mask();
+        (report.bath_in, report.held_edge_in) = bath.apply(lagged_board, walls.as_deref());
+    }
+
+    report.clamped_in = match config.clamp_policy.apply(lagged_board) {
+        Ok(added) => added,
+        Err((cell, energy)) => panic!(
+            "cell {:?} has energy {}, which clamp_policy \"error\" doesn't allow",
+            cell, energy
+        ),
+    };
+}
+
+#[inline(always)]
+fn scalar_time_step<B: Board>(
+    board: &mut B,
+    lagged_board: &mut B,
+    tracer: Option<&mut Array2<f64>>,
+    config: &Config,
+    rng: &mut SimRng,
+    watch: Option<(usize, usize)>,
+) -> (Option<Array2<f64>>, Option<KernelFlags>, f64) {
+    let (h, w) = config.dims;
+
+    // remembers the weights drawn for the watched cell, if any
+    let mut watched = None;
+    let mut escaped = 0.0;
+    let mut flags = config
+        .checks_kernel()
+        .then(|| KernelFlags(Array2::zeros((h, w))));
+    let mut weights = [0.0; MAX_STENCIL];
+    let mean = mean_for_drift(lagged_board, config);
+    let radius = config.kernel.radius();
+    let mut dye_next = tracer.as_ref().map(|dye| Array2::zeros(dye.dim()));
+    let walls = config.wall_mask();
+    let walls = walls.as_deref();
+
+    for cell in sweep_order(h, w) {
+        let mut energy = lagged_board.get(cell);
+        let mut dye = tracer.as_ref().map_or(0.0, |dye| dye[cell]);
+        // cold cells keep what they have until enough flows in to pass the
+        // threshold, and walls keep what they have for good
+        let cold = config.active_threshold > 0.0 && energy < config.active_threshold;
+        if cold || walls.is_some_and(|walls| walls[cell]) {
+            board.add(cell, energy);
+            carry(&mut dye_next, cell, dye);
+            continue;
+        }
+
+        let (rows, cols) = stencil(cell, config.dims, config.boundary, radius);
+        let shape = (rows.clone().count(), cols.clone().count());
+        let weights = &mut weights[..shape.0 * shape.1];
+        if stochastic(cell, config) {
+            probability_weights(weights, rng);
+        } else {
+            weights.fill(1.0 / weights.len() as f64);
+        }
+        bias_weights(
+            weights,
+            cell,
+            (&rows, &cols),
+            lagged_board,
+            mean,
+            walls,
+            config,
+        );
+        if let Some(flags) = &mut flags {
+            flags.0[cell] = check_weights(weights, shape, energy - mean, config);
+        }
+
+        if let Some(levy) = &config.levy {
+            let jump = energy * levy.fraction;
+            match levy.target(cell, config.dims, config.boundary, rng) {
+                Some(target) => {
+                    board.add(target, jump);
+                    carry(&mut dye_next, target, dye * levy.fraction);
+                }
+                None => escaped += jump,
+            }
+            energy -= jump;
+            dye -= dye * levy.fraction;
+        }
+        // a cell keeps 1 - heat of what's left and spreads the rest
+        let kept = energy * (1.0 - config.heat);
+        board.add(cell, kept);
+        energy -= kept;
+        carry(&mut dye_next, cell, dye * (1.0 - config.heat));
+        dye *= config.heat;
+        for (k, offset) in iproduct!(rows, cols).enumerate() {
+            match config.boundary.neighbor(cell, offset, config.dims) {
+                Some(target) => {
+                    board.add(target, energy * weights[k]);
+                    carry(&mut dye_next, target, dye * weights[k]);
+                }
+                None => escaped += energy * weights[k],
+            }
+        }
+
+        if watch == Some(cell) {
+            watched = Some(Array2::from_shape_vec(shape, weights.to_vec()).unwrap());
+        }
+    }
+
+    // the scratch board becomes the new one, and the old one is cleared to be
+    // the next step's scratch
+    std::mem::swap(lagged_board, board);
+    board.clear();
+    if let (Some(tracer), Some(next)) = (tracer, dye_next) {
+        *tracer = next;
+    }
+
+    (watched, flags, escaped)
+}
+
+// the scalar step in three passes: the random numbers are drawn first, in the
+// scalar sweep order so that both backends use the same ones; then every row
+// works out what its cells send each neighbor, and every row gathers what its
+// cells are sent, so no two threads write to the same cell
+// what the parallel backend keeps between steps: the weights every cell sends
+// along, the board it gathers into, and a pool of config.threads threads
+#[derive(Default)]
+pub struct ParallelScratch {
+    cells: Vec<f64>,
+    next: Array2<f64>,
+    pool: Option<(usize, Arc<ThreadPool>)>,
+}
+
+impl ParallelScratch {
+    // rayon's global pool when config.threads isn't set
+    fn pool(&mut self, threads: Option<usize>) -> Option<Arc<ThreadPool>> {
+        let threads = threads?;
+        if self.pool.as_ref().is_none_or(|(n, _)| *n != threads) {
+            self.pool = match ThreadPoolBuilder::new().num_threads(threads).build() {
+                Ok(pool) => Some((threads, Arc::new(pool))),
+                Err(e) => {
+                    eprintln!("Couldn't start {} threads: {}", threads, e);
+                    None
+                }
+            };
+        }
+        self.pool.as_ref().map(|(_, pool)| Arc::clone(pool))
+    }
+}
+
+fn in_pool<T: Send>(pool: Option<&ThreadPool>, f: impl FnOnce() -> T + Send) -> T {
+    match pool {
+        Some(pool) => pool.install(f),
+        None => f(),
+    }
+}
+
+fn parallel_time_step<B: Board>(
+    lagged_board: &mut B,
+    tracer: Option<&mut Array2<f64>>,
+    config: &Config,
+    rng: &mut SimRng,
+    watch: Option<(usize, usize)>,
+    scratch: &mut ParallelScratch,
+) -> (Option<Array2<f64>>, Option<KernelFlags>, f64) {
+    let (h, w) = config.dims;
+    let lagged = lagged_board.to_dense();
+    let mean = mean_for_drift(&lagged, config);
+    let walls = config.wall_mask();
+    let walls = walls.as_deref();
+    let active = |cell: (usize, usize)| {
+        !(config.active_threshold > 0.0 && lagged[cell] < config.active_threshold)
+            && walls.is_none_or(|walls| !walls[cell])
+    };
+    let pool = scratch.pool(config.threads);
+    let pool = pool.as_deref();
+
+    let radius = config.kernel.radius() as isize;
+    let side = config.kernel.side();
+    let n = side * side;
+    // where along the stencil `offset` is, in the full stencil's row-major order
+    let index = |(di, dj): (isize, isize)| ((di + radius) * side as isize + dj + radius) as usize;
+
+    // energy and dye that don't go through the stencil: cold cells and Levy jumps
+    let next = &mut scratch.next;
+    if next.dim() == (h, w) {
+        next.fill(0.0);
+    } else {
+        *next = Array2::zeros((h, w));
+    }
+    let mut dye_next = tracer.as_ref().map(|dye| Array2::zeros(dye.dim()));
+    // each cell's drawn weights, replaced by the fraction of its energy it sends
+    // along each offset, n to a cell
+    let cells = &mut scratch.cells;
+    cells.clear();
+    cells.resize(h * w * n, 0.0);
+    let mut escaped = 0.0;
+    for cell in sweep_order(h, w) {
+        let energy = lagged[cell];
+        let dye = tracer.as_ref().map_or(0.0, |dye| dye[cell]);
+        if !active(cell) {
+            next[cell] += energy;
+            carry(&mut dye_next, cell, dye);
+            continue;
+        }
+        if stochastic(cell, config) {
+            let (rows, cols) = stencil(cell, config.dims, config.boundary, radius as usize);
+            let start = (cell.0 * w + cell.1) * n;
+            probability_weights(&mut cells[start..start + rows.count() * cols.count()], rng);
+        }
+        if let Some(levy) = &config.levy {
+            let jump = energy * levy.fraction;
+            match levy.target(cell, config.dims, config.boundary, rng) {
+                Some(target) => {
+                    next[target] += jump;
+                    carry(&mut dye_next, target, dye * levy.fraction);
+                }
+                None => escaped += jump,
+            }
+        }
+    }
+
+    // a cell's weights from the ones it drew, which fill the front of `drawn`
+    let weights_of = |cell: (usize, usize), drawn: &[f64]| {
+        let (rows, cols) = stencil(cell, config.dims, config.boundary, radius as usize);
+        let shape = (rows.clone().count(), cols.clone().count());
+        let k = shape.0 * shape.1;
+        let mut weights = [0.0; MAX_STENCIL];
+        if stochastic(cell, config) {
+            weights[..k].copy_from_slice(&drawn[..k]);
+        } else {
+            weights[..k].fill(1.0 / k as f64);
+        }
+        bias_weights(
+            &mut weights[..k],
+            cell,
+            (&rows, &cols),
+            &lagged,
+            mean,
+            walls,
+            config,
+        );
+        (weights, shape, rows, cols)
+    };
+
+    let watched = watch.filter(|&cell| active(cell)).map(|cell| {
+        let start = (cell.0 * w + cell.1) * n;
+        let (weights, shape, _, _) = weights_of(cell, &cells[start..start + n]);
+        Array2::from_shape_vec(shape, weights[..shape.0 * shape.1].to_vec()).unwrap()
+    });
+
+    let rows: Vec<(Vec<u8>, f64)> = in_pool(pool, || {
+        cells
+            .par_chunks_mut(w * n)
+            .enumerate()
+            .map(|(i, row)| {
+                let mut flags = vec![0; w];
+                let mut escaped = 0.0;
+                for (j, sent) in row.chunks_mut(n).enumerate() {
+                    let cell = (i, j);
+                    if !active(cell) {
+                        continue;
+                    }
+                    let (weights, shape, rows, cols) = weights_of(cell, sent);
+                    sent.fill(0.0);
+                    let energy = lagged[cell];
+                    if config.checks_kernel() {
+                        flags[j] = check_weights(
+                            &weights[..shape.0 * shape.1],
+                            shape,
+                            energy - mean,
+                            config,
+                        );
+                    }
+                    // what's left after the Levy jump, as a fraction of the cell's own
+                    let mut left = 1.0;
+                    if let Some(levy) = &config.levy {
+                        left -= levy.fraction;
+                    }
+                    let kept = left * (1.0 - config.heat);
+                    left -= kept;
+                    for (k, offset) in iproduct!(rows, cols).enumerate() {
+                        match config.boundary.neighbor(cell, offset, config.dims) {
+                            Some(_) => sent[index(offset)] = left * weights[k],
+                            None => escaped += energy * left * weights[k],
+                        }
+                    }
+                    sent[n / 2] += kept;
+                }
+                (flags, escaped)
+            })
+            .collect()
+    });
+    let cells = &*cells;
+
+    // adds to `next` what every cell sends of `from`
+    let gather = |next: &mut Array2<f64>, from: &Array2<f64>| {
+        in_pool(pool, || {
+            next.as_slice_mut()
+                .expect("a new board is contiguous")
+                .par_chunks_mut(w)
+                .enumerate()
+                .for_each(|(i, row)| {
+                    for (j, e) in row.iter_mut().enumerate() {
+                        for (di, dj) in iproduct!(-radius..=radius, -radius..=radius) {
+                            // the cell that reaches (i, j) by stepping (di, dj)
+                            let source = config.boundary.neighbor((i, j), (-di, -dj), config.dims);
+                            if let Some((si, sj)) = source {
+                                *e += from[(si, sj)] * cells[(si * w + sj) * n + index((di, dj))];
+                            }
+                        }
+                    }
+                })
+        })
+    };
+    gather(next, &lagged);
+
+    // every cell is written, so there is no scratch board to swap in
+    for cell in iproduct!(0..h, 0..w) {
+        lagged_board.set(cell, next[cell]);
+    }
+    if let (Some(tracer), Some(mut dye_next)) = (tracer, dye_next) {
+        gather(&mut dye_next, tracer);
+        *tracer = dye_next;
+    }
+
+    let flags = config.checks_kernel().then(|| {
+        let flags = rows.iter().flat_map(|(flags, _)| flags.iter().copied());
+        KernelFlags(Array2::from_shape_vec((h, w), flags.collect()).unwrap())
+    });
+    escaped += rows.iter().map(|(_, escaped)| escaped).sum::<f64>();
+
+    (watched, flags, escaped)
+}
+
+#[inline(always)]
+fn check_weights(weights: &[f64], shape: (usize, usize), excess: f64, config: &Config) -> u8 {
+    let mut flags = 0;
+
+    let sum: f64 = weights.iter().sum();
+    let bad = weights.iter().any(|w| w.is_nan() || *w < 0.0);
+    let off = (sum - 1.0).abs();
+    if bad || off.is_nan() || off > config.assertion_tolerance {
+        flags |= KernelFlags::UNNORMALIZED;
+    }
+
+    let clamped = match config.drift {
+        Some(Drift::Buoyancy {
+            coefficient,
+            max_speed,
+        }) => (coefficient * excess).abs() > max_speed,
+        _ => false,
+    };
+    let side = config.kernel.side();
+    if shape != (side, side) || clamped {
+        flags |= KernelFlags::CLAMPED;
+    }
+    flags
+}
+
+// the half of the neighborhood after a cell in row-major order, so that every
+// pair of neighbors is visited once
+const FORWARD: [(usize, isize); 4] = [(0, 1), (1, -1), (1, 0), (1, 1)];
+
+#[inline(always)]
+fn gather_time_step<B: Board>(
+    board: &mut B,
+    lagged_board: &mut B,
+    config: &Config,
+    rng: &mut SimRng,
+) -> f64 {
+    // each cell has at most 8 neighbors, so weights of at most 1/8 never move
+    // more than a cell has; they average 1/9, like an interior scatter weight
+    exchange(
+        board,
+        lagged_board,
+        config.dims,
+        config.boundary,
+        config.active_threshold,
+        config.wall_mask().as_deref(),
+        |a, b| {
+            let weight = if stochastic(a, config) || stochastic(b, config) {
+                (1.0 + (rng.gen::<f64>() - 0.5) / 4.0) / 9.0
+            } else {
+                1.0 / 9.0
+            };
+            weight * config.heat
+        },
+    )
+}
+
+// moves weight * (difference) between every pair of neighbors, with `weight`
+// drawn once per pair of cells; pairs of cells both below `threshold`, and pairs
+// with a wall, are left alone. past an absorbing edge every cell has a neighbor
+// at zero energy, and what flows to those is returned
+#[inline(always)]
+fn exchange<B: Board>(
+    board: &mut B,
+    lagged_board: &mut B,
+    (h, w): (usize, usize),
+    boundary: Boundary,
+    threshold: f64,
+    walls: Option<&Array2<bool>>,
+    mut weight: impl FnMut((usize, usize), (usize, usize)) -> f64,
+) -> f64 {
+    board.clone_from(lagged_board);
+    let wall = |cell: (usize, usize)| walls.is_some_and(|walls| walls[cell]);
+
+    for (i, j) in iproduct!(0..h, 0..w) {
+        for (di, dj) in FORWARD {
+            let (ni, nj) = (i + di, j as isize + dj);
+            let inside = ni < h && nj >= 0 && (nj as usize) < w;
+            let neighbor = match boundary {
+                _ if inside => (ni, nj as usize),
+                Boundary::Closed | Boundary::Absorbing => continue,
+                Boundary::Periodic => (ni % h, nj.rem_euclid(w as isize) as usize),
+            };
+            if lagged_board.get((i, j)) < threshold && lagged_board.get(neighbor) < threshold {
+                continue;
+            }
+            if wall((i, j)) || wall(neighbor) {
+                continue;
+            }
+
+            let flow =
+                weight((i, j), neighbor) * (lagged_board.get(neighbor) - lagged_board.get((i, j)));
+            board.add((i, j), flow);
+            board.add(neighbor, -flow);
+        }
+    }
+
+    let mut escaped = 0.0;
+    if boundary == Boundary::Absorbing {
+        let edges =
+            iproduct!(0..h, 0..w).filter(|&(i, j)| i == 0 || j == 0 || i == h - 1 || j == w - 1);
+        for cell in edges {
+            let e = lagged_board.get(cell);
+            if e < threshold || wall(cell) {
+                continue;
+            }
+            let (rows, cols) = stencil(cell, (h, w), Boundary::Closed, 1);
+            let outside = 9 - rows.count() * cols.count();
+            for _ in 0..outside {
+                let flow = weight(cell, cell) * e;
+                board.add(cell, -flow);
+                escaped += flow;
+            }
+        }
+    }
+
+    std::mem::swap(lagged_board, board);
+    board.clear();
+    escaped
+}
+
+// what a step does on average: under scatter every cell spreads evenly over its
+// stencil, biased the same way, and under gather every pair of neighbors
+// exchanges 1/9 of their difference. long-range jumps are left out
+pub fn deterministic_time_step(
+    board: &mut Array2<f64>,
+    lagged_board: &mut Array2<f64>,
+    config: &Config,
+) {
+    let (h, w) = config.dims;
+    if config.scheme == Scheme::Gather {
+        exchange(
+            board,
+            lagged_board,
+            config.dims,
+            config.boundary,
+            0.0,
+            config.wall_mask().as_deref(),
+            |_, _| config.heat / 9.0,
+        );
+        if let Some(bath) = &config.bath {
+            bath.apply(lagged_board, config.wall_mask().as_deref());
+        }
+        let _ = config.clamp_policy.apply(lagged_board);
+        return;
+    }
+
+    let mut weights = [0.0; MAX_STENCIL];
+    let mean = mean_for_drift(lagged_board, config);
+    let walls = config.wall_mask();
+    let walls = walls.as_deref();
+
+    for cell in sweep_order(h, w) {
+        if walls.is_some_and(|walls| walls[cell]) {
+            board[cell] += lagged_board[cell];
+            continue;
+        }
+        let (rows, cols) = stencil(cell, config.dims, config.boundary, config.kernel.radius());
+        let n = rows.clone().count() * cols.clone().count();
+        let weights = &mut weights[..n];
+        weights.fill(1.0 / n as f64);
+        bias_weights(
+            weights,
+            cell,
+            (&rows, &cols),
+            lagged_board,
+            mean,
+            walls,
+            config,
+        );
+
+        let kept = lagged_board[cell] * (1.0 - config.heat);
+        let energy = lagged_board[cell] - kept;
+        board[cell] += kept;
+        for (k, offset) in iproduct!(rows, cols).enumerate() {
+            if let Some(target) = config.boundary.neighbor(cell, offset, config.dims) {
+                board[target] += energy * weights[k];
+            }
+        }
+    }
+
+    std::mem::swap(lagged_board, board);
+    board.fill(0.0);
+
+    if let Some(bath) = &config.bath {
+        bath.apply(lagged_board, walls);
+    }
+    // an error here is the stochastic board's to report
+    let _ = config.clamp_policy.apply(lagged_board);
+}
+
+// the board's mean energy, if the drift needs it
+fn mean_for_drift(lagged_board: &impl Board, config: &Config) -> f64 {
+    let (h, w) = config.dims;
+    match config.drift {
+        Some(Drift::Buoyancy { .. }) => lagged_board.total() / (h * w) as f64,
+        _ => 0.0,
+    }
+}
+
+#[inline(always)]
+fn bias_weights(
+    weights: &mut [f64],
+    cell: (usize, usize),
+    (rows, cols): (&RangeInclusive<isize>, &RangeInclusive<isize>),
+    lagged_board: &impl Board,
+    mean: f64,
+    walls: Option<&Array2<bool>>,
+    config: &Config,
+) {
+    let dims = config.dims;
+    if !config.kernel.is_flat() {
+        let mut s = 0.0;
+        for (x, offset) in weights
+            .iter_mut()
+            .zip(iproduct!(rows.clone(), cols.clone()))
+        {
+            *x *= config.kernel.weight(offset);
+            s += *x;
+        }
+        for x in weights.iter_mut() {
+            *x /= s;
+        }
+    }
+    if let Some(drift) = &config.drift {
+        let excess = lagged_board.get(cell) - mean;
+        drift.bias(weights, cell, (rows.clone(), cols.clone()), dims, excess);
+    }
+    if !matches!(config.capacity, HeatCapacity::Uniform) {
+        config.capacity.bias(
+            weights,
+            cell,
+            (rows.clone(), cols.clone()),
+            dims,
+            config.boundary,
+        );
+    }
+    if let Some(walls) = walls {
+        block_walls(weights, cell, (rows, cols), dims, walls, config.boundary);
+    }
+}
+
+// takes the weights toward walls away and scales up the rest to make up for
+// them; if nothing else was left, the cell keeps its energy
+#[inline(always)]
+fn block_walls(
+    weights: &mut [f64],
+    cell: (usize, usize),
+    (rows, cols): (&RangeInclusive<isize>, &RangeInclusive<isize>),
+    dims: (usize, usize),
+    walls: &Array2<bool>,
+    boundary: Boundary,
+) {
+    let (mut s, mut blocked) = (0.0, false);
+    for (x, offset) in weights
+        .iter_mut()
+        .zip(iproduct!(rows.clone(), cols.clone()))
+    {
+        if boundary
+            .neighbor(cell, offset, dims)
+            .is_some_and(|target| walls[target])
+        {
+            *x = 0.0;
+            blocked = true;
+        }
+        s += *x;
+    }
+
+    if !blocked {
+        return;
+    }
+    if s > 0.0 {
+        for x in weights.iter_mut() {
+            *x /= s;
+        }
+    } else {
+        weights.fill(0.0);
+        let center = -rows.start() * cols.clone().count() as isize - cols.start();
+        weights[center as usize] = 1.0;
+    }
+}
+
+// corners, then the top and bottom borders, then the remaining rows left to right
+fn sweep_order(h: usize, w: usize) -> impl Iterator<Item = (usize, usize)> {
+    let corners = [(0, 0), (0, w - 1), (h - 1, 0), (h - 1, w - 1)];
+    let top = (1..w - 1).map(|j| (0, j));
+    let bottom = (1..w - 1).map(move |j| (h - 1, j));
+    let rows = (1..h - 1).flat_map(move |i| (0..w).map(move |j| (i, j)));
+
+    corners.into_iter().chain(top).chain(bottom).chain(rows)
+}
+
+// the offsets of a cell's neighborhood `radius` cells out, clipped to a closed board
+#[inline(always)]
+fn stencil(
+    (i, j): (usize, usize),
+    (h, w): (usize, usize),
+    boundary: Boundary,
+    radius: usize,
+) -> (RangeInclusive<isize>, RangeInclusive<isize>) {
+    let r = radius as isize;
+    match boundary {
+        Boundary::Closed => (
+            -(i.min(radius) as isize)..=(h - 1 - i).min(radius) as isize,
+            -(j.min(radius) as isize)..=(w - 1 - j).min(radius) as isize,
+        ),
+        Boundary::Periodic | Boundary::Absorbing => (-r..=r, -r..=r),
+    }
+}
+
+// fills `p` with random weights summing to 1
+#[inline(always)]
+fn probability_weights(p: &mut [f64], rng: &mut SimRng) {
+    let mut s = 0.0;
+
+    for x in p.iter_mut() {
+        *x = rng.gen();
+        s += *x;
+    }
+
+    for x in p.iter_mut() {
+        *x /= s;
+    }
+}
+
+// how the board starts out
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InitialCondition {
+    // `hotspots` cells at random, sharing the board's energy
+    #[default]
+    Hotspots,
+    // bands spanning every row, each a gaussian across the columns
+    Fronts(Vec<Front>),
+    // a .npy array, put through `transform`; the board takes its shape
+    File(String),
+    // one unit of energy a cell on average, varied by a correlated gaussian
+    // field with standard deviation `contrast` and cut off at zero
+    Field {
+        spectrum: Spectrum,
+        #[serde(default = "default_contrast")]
+        contrast: f64,
+    },
+    // these hotspots and nothing else, for a start that doesn't hang on the seed
+    Placed(Vec<Hotspot>),
+    // a PNG or GIF resized to the board, its brighter pixels holding more of
+    // `energy`, one unit a cell on average if that isn't set
+    Image {
+        path: String,
+        #[serde(default)]
+        energy: Option<f64>,
+    },
+}
+
+fn default_contrast() -> f64 {
+    0.5
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Front {
+    pub column: f64,
+    pub width: f64,
+    pub peak: f64,
+}
+
+// x is the column and y the row, counted from the bottom as the window draws
+// them; hotspots in the same cell add up
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Hotspot {
+    pub x: usize,
+    pub y: usize,
+    pub energy: f64,
+}
+
+pub fn init_board<B: Board>(config: &Config, rng: &mut SimRng) -> B {
+    let (h, w) = config.dims;
+    let hotspots = config.hotspots;
+
+    let mut board = B::zeros((h, w));
+
+    if let InitialCondition::File(path) = &config.initial {
+        let field = load_field(path, &config.transform).expect("Couldn't load the initial field");
+        for (cell, &e) in field.indexed_iter() {
+            board.set(cell, e);
+        }
+        return board;
+    }
+
+    if let InitialCondition::Image { path, energy } = &config.initial {
+        let energy = energy.unwrap_or((h * w) as f64);
+        let field = load_image(path, (h, w), energy, &config.transform)
+            .expect("Couldn't load the initial image");
+        for (cell, &e) in field.indexed_iter() {
+            board.set(cell, e);
+        }
+        return board;
+    }
+
+    if let InitialCondition::Field { spectrum, contrast } = &config.initial {
+        let field = field::gaussian_field((h, w), spectrum, rng);
+        for (cell, &z) in field.indexed_iter() {
+            board.set(cell, (1.0 + contrast * z).max(0.0));
+        }
+        return board;
+    }
+
+    if let InitialCondition::Placed(hotspots) = &config.initial {
+        for spot in hotspots {
+            let cell = (spot.y, spot.x);
+            board.set(cell, board.get(cell) + spot.energy);
+        }
+        return board;
+    }
+
+    if let InitialCondition::Fronts(fronts) = &config.initial {
+        for (i, j) in iproduct!(0..h, 0..w) {
+            let e = fronts
+                .iter()
+                .map(|f| f.peak * (-(j as f64 - f.column).powi(2) / (2.0 * f.width.powi(2))).exp())
+                .sum();
+            board.set((i, j), e);
+        }
+        return board;
+    }
+
+    let mut quota = 0;
+
+    // pad board with negative infinities in its borders
+    // board.slice_mut(s![0, 0..w + 2]).fill(-f64::INFINITY);
+    // board.slice_mut(s![h + 1, 0..w + 2]).fill(-f64::INFINITY);
+    // board.slice_mut(s![0..h + 2, 0]).fill(-f64::INFINITY);
+    // board.slice_mut(s![0..h + 2, w + 1]).fill(-f64::INFINITY);
+
+    while quota != hotspots {
+        let rx = rng.gen_range(0..w);
+        let ry = rng.gen_range(0..h);
+
+        if board.get((ry, rx)) != 0.0 {
+            continue;
+        }
+
+        let (h, w, hotspots) = (h as f64, w as f64, hotspots as f64);
+        board.set((ry, rx), (h * w * w / h) / hotspots);
+        quota += 1;
+    }
+
+    board
+}
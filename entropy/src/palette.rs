@@ -0,0 +1,516 @@
+use crate::config::data_dir;
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+// a color ramp for the board, saved as <name>.json under palettes/ in data_dir()
+// and picked in the config by name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "PaletteFile", into = "PaletteFile")]
+pub struct Palette {
+    // sorted by position
+    stops: Vec<Stop>,
+    blend: Blend,
+}
+
+// how a palette blends between its stops
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Blend {
+    // channel by channel in sRGB
+    #[default]
+    Rgb,
+    // channel by channel in linear light, converted to sRGB only at the end, so
+    // the ramp doesn't darken between bright stops
+    Linear,
+    // in the OkLab perceptual space, so lightness changes evenly along the ramp
+    // and blends of complementary colors don't go muddy
+    Oklab,
+}
+
+// the built-in color maps, or stops given in the config itself
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Colormap {
+    // blue through red on the hue circle
+    #[default]
+    Hue,
+    // perceptually uniform and readable with most kinds of color blindness
+    Viridis,
+    // perceptually uniform from black through red to pale yellow, and fine in
+    // grayscale print
+    Inferno,
+    Grayscale,
+    // the stops of a palette, as it would be saved
+    Custom(Palette),
+}
+
+// nine samples of matplotlib's maps, close enough blended in sRGB
+const VIRIDIS: [&str; 9] = [
+    "#440154", "#472c7a", "#3b518b", "#2c718e", "#21908d", "#27ad81", "#5cc863", "#aadc32",
+    "#fde725",
+];
+const INFERNO: [&str; 9] = [
+    "#000004", "#1f0c48", "#550f6d", "#88226a", "#ba3655", "#e35933", "#f98c0a", "#f9c932",
+    "#fcffa4",
+];
+const GRAYSCALE: [&str; 2] = ["#000000", "#ffffff"];
+
+impl Colormap {
+    // the palette to draw with; the hue map has none, so the ramp is drawn
+    // straight from hue_ramp
+    pub fn palette(&self) -> Option<Palette> {
+        let stops = match self {
+            Colormap::Hue => return None,
+            Colormap::Custom(palette) => return Some(palette.clone()),
+            Colormap::Viridis => &VIRIDIS[..],
+            Colormap::Inferno => &INFERNO[..],
+            Colormap::Grayscale => &GRAYSCALE[..],
+        };
+        let last = (stops.len() - 1) as f64;
+        let stops = stops
+            .iter()
+            .enumerate()
+            .map(|(k, hex)| Stop {
+                at: k as f64 / last,
+                color: Rgb::try_from(hex.to_string()).expect("the built-in colors parse"),
+            })
+            .collect();
+        Some(Palette::new(stops).expect("the built-in stops are on the ramp"))
+    }
+}
+
+// just the stops for an sRGB blend, as palettes were first saved
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum PaletteFile {
+    Stops(Vec<Stop>),
+    Blended { stops: Vec<Stop>, blend: Blend },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Stop {
+    // where on the ramp, from 0 to 1
+    pub at: f64,
+    pub color: Rgb,
+}
+
+// written as "#rrggbb"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rgb(pub [u8; 3]);
+
+impl TryFrom<String> for Rgb {
+    type Error = PaletteError;
+
+    fn try_from(s: String) -> Result<Self, PaletteError> {
+        let hex = s.strip_prefix('#').unwrap_or(&s);
+        let channel = |k: usize| {
+            hex.get(2 * k..2 * k + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+        };
+        match (hex.len(), channel(0), channel(1), channel(2)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Rgb([r, g, b])),
+            _ => Err(PaletteError::BadColor(s)),
+        }
+    }
+}
+
+impl From<Rgb> for String {
+    fn from(Rgb([r, g, b]): Rgb) -> String {
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    }
+}
+
+impl Stop {
+    // "<at>=<color>", e.g. "0.5=#ff8800"
+    pub fn parse(s: &str) -> Result<Self, PaletteError> {
+        let (at, color) = s
+            .split_once('=')
+            .ok_or_else(|| PaletteError::BadStop(s.to_string()))?;
+        let at = at
+            .trim()
+            .parse()
+            .map_err(|_| PaletteError::BadStop(s.to_string()))?;
+        Ok(Stop {
+            at,
+            color: Rgb::try_from(color.trim().to_string())?,
+        })
+    }
+}
+
+impl TryFrom<PaletteFile> for Palette {
+    type Error = PaletteError;
+
+    fn try_from(file: PaletteFile) -> Result<Self, PaletteError> {
+        match file {
+            PaletteFile::Stops(stops) => Palette::new(stops),
+            PaletteFile::Blended { stops, blend } => Ok(Palette::new(stops)?.with_blend(blend)),
+        }
+    }
+}
+
+impl From<Palette> for PaletteFile {
+    fn from(palette: Palette) -> PaletteFile {
+        match palette.blend {
+            Blend::Rgb => PaletteFile::Stops(palette.stops),
+            blend => PaletteFile::Blended {
+                stops: palette.stops,
+                blend,
+            },
+        }
+    }
+}
+
+impl Palette {
+    pub fn new(mut stops: Vec<Stop>) -> Result<Self, PaletteError> {
+        if stops.len() < 2 {
+            return Err(PaletteError::TooFewStops);
+        }
+        if let Some(stop) = stops.iter().find(|s| !(0.0..=1.0).contains(&s.at)) {
+            return Err(PaletteError::StopOffRamp(stop.at));
+        }
+        stops.sort_by(|a, b| a.at.total_cmp(&b.at));
+        Ok(Self {
+            stops,
+            blend: Blend::default(),
+        })
+    }
+
+    pub fn with_blend(self, blend: Blend) -> Self {
+        Self { blend, ..self }
+    }
+
+    // the built-in ramp: blue, cyan, green, yellow and red, evenly spaced, which
+    // blended in sRGB is exactly hue_ramp
+    pub fn hue() -> Self {
+        let stops = [0.0, 0.25, 0.5, 0.75, 1.0].map(|at| Stop {
+            at,
+            color: Rgb(hue_ramp(at)),
+        });
+        Self::new(stops.to_vec()).expect("the hue stops are on the ramp")
+    }
+
+    // an sRGB blend done in linear light instead; other blends are kept
+    pub fn in_linear_light(self) -> Self {
+        match self.blend {
+            Blend::Rgb => self.with_blend(Blend::Linear),
+            _ => self,
+        }
+    }
+
+    pub fn stops(&self) -> &[Stop] {
+        &self.stops
+    }
+
+    pub fn blend(&self) -> Blend {
+        self.blend
+    }
+
+    // the color at t, blending the stops either side; past the end stops the
+    // color stays put
+    #[inline(always)]
+    pub fn sample(&self, t: f64) -> [u8; 3] {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let after = self.stops.partition_point(|s| s.at < t);
+        let (a, b) = match after {
+            0 => return self.stops[0].color.0,
+            n if n == self.stops.len() => return self.stops[n - 1].color.0,
+            n => (self.stops[n - 1], self.stops[n]),
+        };
+
+        let f = if b.at > a.at {
+            (t - a.at) / (b.at - a.at)
+        } else {
+            1.0
+        };
+        match self.blend {
+            Blend::Rgb => {
+                let mix = |k: usize| {
+                    let (x, y) = (a.color.0[k] as f64, b.color.0[k] as f64);
+                    (x + f * (y - x)).round() as u8
+                };
+                [mix(0), mix(1), mix(2)]
+            }
+            Blend::Linear => {
+                let (x, y) = (a.color.0.map(srgb_to_linear), b.color.0.map(srgb_to_linear));
+                [0, 1, 2].map(|k| linear_to_srgb(x[k] + f * (y[k] - x[k])))
+            }
+            Blend::Oklab => {
+                let (x, y) = (to_oklab(a.color.0), to_oklab(b.color.0));
+                from_oklab([0, 1, 2].map(|k| x[k] + f * (y[k] - x[k])))
+            }
+        }
+    }
+
+    pub fn load(dir: &Path, name: &str) -> Result<Self, PaletteError> {
+        let reader = BufReader::new(File::open(palette_path(dir, name)?)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn save(&self, dir: &Path, name: &str) -> Result<PathBuf, PaletteError> {
+        let path = palette_path(dir, name)?;
+        fs::create_dir_all(dir)?;
+        serde_json::to_writer_pretty(BufWriter::new(File::create(&path)?), self)?;
+        Ok(path)
+    }
+}
+
+// the energy drawn at the top of the ramp when the config doesn't set one: twice
+// the equilibrium value, the mean, which is what a square board of hotspots was
+// always drawn with, or the initial peak if that's lower so a smooth start still
+// spans the ramp
+pub fn auto_max_energy(initial: &Array2<f64>) -> f64 {
+    let mean = initial.mean().unwrap_or(0.0);
+    let peak = initial.iter().copied().fold(0.0, f64::max);
+    let max = (2.0 * mean).min(peak);
+    if max > 0.0 && max.is_finite() {
+        max
+    } else {
+        1.0
+    }
+}
+
+// how an energy is placed along the ramp, between zero and the max
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorScale {
+    #[default]
+    Linear,
+    // by log(1 + energy), so hotspots and the diffuse background both show
+    Log,
+}
+
+impl ColorScale {
+    // where `energy` sits on the ramp up to `max`, from 0 to 1
+    #[inline(always)]
+    pub fn position(self, energy: f64, max: f64) -> f64 {
+        match self {
+            ColorScale::Linear => energy / max,
+            ColorScale::Log => energy.max(0.0).ln_1p() / max.ln_1p(),
+        }
+    }
+}
+
+// how the energy at the top of the ramp is picked as a run goes on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Normalization {
+    // max_energy, or what auto_max_energy makes of the initial board
+    #[default]
+    Fixed,
+    // the highest cell energy seen so far in the run
+    RunningMax,
+    // this percentile, from 0 to 100, of the cell energies on each frame
+    Percentile(f64),
+}
+
+impl Normalization {
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            Normalization::Percentile(p) => (0.0..=100.0).contains(&p),
+            _ => true,
+        }
+    }
+}
+
+// the top of the ramp from frame to frame; a board with nothing to scale by,
+// such as a percentile landing on empty cells, is drawn with the fixed max
+#[derive(Debug, Clone)]
+pub struct Scale {
+    normalization: Normalization,
+    fixed: f64,
+    running: f64,
+}
+
+impl Scale {
+    pub fn new(normalization: Normalization, fixed: f64) -> Self {
+        Self {
+            normalization,
+            fixed,
+            running: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, board: &Array2<f64>) -> f64 {
+        let max = match self.normalization {
+            Normalization::Fixed => self.fixed,
+            Normalization::RunningMax => {
+                self.running = board.iter().copied().fold(self.running, f64::max);
+                self.running
+            }
+            Normalization::Percentile(p) => percentile(board, p),
+        };
+        if max > 0.0 && max.is_finite() {
+            max
+        } else {
+            self.fixed
+        }
+    }
+}
+
+// the nearest-rank percentile `p` of the cell energies
+pub fn percentile(board: &Array2<f64>, p: f64) -> f64 {
+    let mut values: Vec<f64> = board.iter().copied().collect();
+    if values.is_empty() {
+        return 0.0;
+    }
+    let rank = ((p / 100.0 * values.len() as f64).ceil() as usize).clamp(1, values.len());
+    *values.select_nth_unstable_by(rank - 1, f64::total_cmp).1
+}
+
+// the color at hue `h` in degrees, saturation `s` and value `v`, as sRGB
+// channels from 0 to 1; any hue is taken round the circle, so 360 is red again
+// and -120 is blue
+pub fn hsv_to_rgb(h: f64, s: f64, v: f64) -> [f64; 3] {
+    let (s, v) = (s.clamp(0.0, 1.0), v.clamp(0.0, 1.0));
+    let h_prime = h.rem_euclid(360.0) / 60.0;
+    let c = v * s;
+    let x = c * (1.0 - (h_prime % 2.0 - 1.0).abs());
+
+    // rem_euclid can round up to exactly 360
+    let [r, g, b] = match (h_prime as usize).min(5) {
+        0 => [c, x, 0.0],
+        1 => [x, c, 0.0],
+        2 => [0.0, c, x],
+        3 => [0.0, x, c],
+        4 => [x, 0.0, c],
+        _ => [c, 0.0, x],
+    };
+    let m = v - c;
+    [r + m, g + m, b + m]
+}
+
+// the built-in ramp, from blue at t = 0 to red at t = 1; t past either end, or
+// NaN, gets the end color
+pub fn hue_ramp(t: f64) -> [u8; 3] {
+    let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+    hsv_to_rgb(240.0 * (1.0 - t), 1.0, 1.0).map(|c| (c * 255.0).round() as u8)
+}
+
+fn srgb_to_linear(c: u8) -> f64 {
+    let c = c as f64 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f64) -> u8 {
+    let c = c.clamp(0.0, 1.0);
+    let c = if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (c * 255.0).round() as u8
+}
+
+// Björn Ottosson's OkLab, as [L, a, b]
+pub fn to_oklab(rgb: [u8; 3]) -> [f64; 3] {
+    let [r, g, b] = rgb.map(srgb_to_linear);
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+// the nearest sRGB color to an OkLab one
+pub fn from_oklab([lightness, a, b]: [f64; 3]) -> [u8; 3] {
+    let l = (lightness + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m = (lightness - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s = (lightness - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+    [
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    ]
+    .map(linear_to_srgb)
+}
+
+pub fn palettes_dir() -> PathBuf {
+    data_dir().join("palettes")
+}
+
+// the names of the saved palettes, sorted
+pub fn list(dir: &Path) -> Result<Vec<String>, PaletteError> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut names: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "json" {
+                return None;
+            }
+            Some(path.file_stem()?.to_str()?.to_string())
+        })
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+// a name is a file name without its .json, so it can't reach outside `dir`
+fn palette_path(dir: &Path, name: &str) -> Result<PathBuf, PaletteError> {
+    let plain = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_'));
+    if !plain {
+        return Err(PaletteError::BadName(name.to_string()));
+    }
+    Ok(dir.join(format!("{}.json", name)))
+}
+
+#[derive(Debug)]
+pub enum PaletteError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+    BadName(String),
+    BadStop(String),
+    BadColor(String),
+    TooFewStops,
+    StopOffRamp(f64),
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaletteError::Io(e) => write!(f, "{}", e),
+            PaletteError::Parse(e) => write!(f, "{}", e),
+            PaletteError::BadName(name) => write!(
+                f,
+                "palette names are letters, digits, - and _, got {:?}",
+                name
+            ),
+            PaletteError::BadStop(s) => write!(f, "expected a stop like 0.5=#ff8800, got {:?}", s),
+            PaletteError::BadColor(s) => write!(f, "expected a color like #ff8800, got {:?}", s),
+            PaletteError::TooFewStops => write!(f, "a palette needs at least 2 stops"),
+            PaletteError::StopOffRamp(at) => {
+                write!(f, "stops must be within [0, 1], got {}", at)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {}
+
+impl From<io::Error> for PaletteError {
+    fn from(e: io::Error) -> Self {
+        PaletteError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for PaletteError {
+    fn from(e: serde_json::Error) -> Self {
+        PaletteError::Parse(e)
+    }
+}
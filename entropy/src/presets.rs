@@ -0,0 +1,30 @@
+use crate::model::{Drift, Front, InitialCondition};
+use crate::Config;
+
+// ready-made scenarios, run with `--preset <name>` instead of config.json
+pub const NAMES: &[&str] = &["collision"];
+
+pub fn by_name(name: &str) -> Option<Config> {
+    match name {
+        "collision" => Some(collision()),
+        _ => None,
+    }
+}
+
+// two broad fronts pushed toward each other by opposing drift, meeting in the middle
+pub fn collision() -> Config {
+    let (h, w) = (60, 160);
+    let front = |column| Front {
+        column,
+        width: 8.0,
+        peak: 1.5,
+    };
+
+    Config {
+        dims: (h, w),
+        size_factor: 5,
+        initial: InitialCondition::Fronts(vec![front(30.0), front(w as f64 - 30.0)]),
+        drift: Some(Drift::Opposing { speed: 0.3 }),
+        ..Config::default()
+    }
+}
@@ -0,0 +1,69 @@
+use entropy::{Config, Simulation};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const FREQUENCY: i32 = 997;
+
+// runs `steps` steps without a window under a sampling profiler and writes the
+// samples as collapsed stacks, one "frame;frame;... count" line per stack,
+// which inferno-flamegraph and flamegraph.pl both read
+pub fn run(config: Config, steps: usize, output: &Path) {
+    let mut sim = Simulation::new(config).unwrap_or_else(|e| {
+        eprintln!("Invalid config: {}", e);
+        std::process::exit(1);
+    });
+
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(FREQUENCY)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+        .expect("Couldn't start the profiler");
+    let start = std::time::Instant::now();
+    for _ in 0..steps {
+        sim.step();
+    }
+    let elapsed = start.elapsed();
+    let report = guard.report().build().expect("Couldn't build the profile");
+
+    let mut out = BufWriter::new(File::create(output).expect("Couldn't create profile file"));
+    let (mut total, mut in_rng) = (0, 0);
+    for (frames, &count) in &report.data {
+        let mut line = frames.thread_name_or_id();
+        let mut rng = false;
+        for symbol in frames
+            .frames
+            .iter()
+            .rev()
+            .flat_map(|frame| frame.iter().rev())
+        {
+            let name = symbol.to_string();
+            rng |= name.contains("rand_chacha") || name.contains("rand::");
+            write!(line, ";{}", name).unwrap();
+        }
+        writeln!(out, "{} {}", line, count).expect("Couldn't write profile");
+
+        total += count;
+        if rng {
+            in_rng += count;
+        }
+    }
+    out.flush().expect("Couldn't write profile");
+
+    println!(
+        "{} steps in {:.3?} ({:.3?} per step), {} samples written to {}",
+        steps,
+        elapsed,
+        elapsed / steps.max(1) as u32,
+        total,
+        output.display()
+    );
+    if total > 0 {
+        println!(
+            "random number generation: {:.1}% of samples, the rest of the step: {:.1}%",
+            100.0 * in_rng as f64 / total as f64,
+            100.0 * (total - in_rng) as f64 / total as f64
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use crate::model::{Drift, Levy};
+use crate::Config;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// [low, high] ranges the randomizer hotkey draws from; parameters without a
+// range are left alone
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Randomizer {
+    // for each component of a uniform drift velocity
+    #[serde(default)]
+    pub drift: Option<(f64, f64)>,
+    // only used when there is a bath
+    #[serde(default)]
+    pub bath_temperature: Option<(f64, f64)>,
+    #[serde(default)]
+    pub levy_fraction: Option<(f64, f64)>,
+    #[serde(default)]
+    pub reset_rate: Option<(f64, f64)>,
+}
+
+impl Randomizer {
+    // redraws every parameter that has a range, describing each change
+    pub fn apply(&self, config: &mut Config, rng: &mut impl Rng) -> Vec<String> {
+        let mut draw = |(low, high): (f64, f64)| low + (high - low) * rng.gen::<f64>();
+        let mut changes = Vec::new();
+
+        if let Some(range) = self.drift {
+            let velocity = (draw(range), draw(range));
+            changes.push(format!(
+                "drift: {} -> ({:.3}, {:.3})",
+                describe_drift(config.drift),
+                velocity.0,
+                velocity.1
+            ));
+            config.drift = Some(Drift::Uniform { velocity });
+        }
+        if let (Some(range), Some(bath)) = (self.bath_temperature, &mut config.bath) {
+            let temperature = draw(range);
+            changes.push(format!(
+                "bath temperature: {:.3} -> {:.3}",
+                bath.temperature, temperature
+            ));
+            bath.temperature = temperature;
+        }
+        if let Some(range) = self.levy_fraction {
+            let fraction = draw(range);
+            let levy = config.levy.get_or_insert(Levy {
+                fraction: 0.0,
+                exponent: 1.5,
+            });
+            changes.push(format!(
+                "levy fraction: {:.3} -> {:.3}",
+                levy.fraction, fraction
+            ));
+            levy.fraction = fraction;
+        }
+        if let Some(range) = self.reset_rate {
+            let rate = draw(range);
+            changes.push(format!(
+                "reset rate: {:.4} -> {:.4}",
+                config.reset_rate, rate
+            ));
+            config.reset_rate = rate;
+        }
+
+        changes
+    }
+}
+
+fn describe_drift(drift: Option<Drift>) -> String {
+    match drift {
+        None => "none".to_string(),
+        Some(Drift::Uniform { velocity }) => format!("({:.3}, {:.3})", velocity.0, velocity.1),
+        Some(Drift::Opposing { speed }) => format!("opposing {:.3}", speed),
+        Some(Drift::Buoyancy { coefficient, .. }) => format!("buoyancy {:.3}", coefficient),
+    }
+}
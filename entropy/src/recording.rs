@@ -0,0 +1,152 @@
+use crate::format::FormatError;
+use crate::simulation::Frame;
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// a recording is a directory of keyframes: full-resolution .npy boards under
+// frames/, which `entropy diff` reads, and a pyramid of downsampled copies under
+// level<n>/ as raw little-endian f32, row-major, so a browser can fetch a coarse
+// level to scrub and only load finer ones for the frame on screen
+pub const INDEX: &str = "index.json";
+pub const INDEX_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordingIndex {
+    pub version: u32,
+    pub dims: (usize, usize),
+    // steps between keyframes
+    pub every: usize,
+    // level 0 is full resolution, each one after it half the size of the last
+    pub levels: Vec<Level>,
+    pub frames: Vec<FrameInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Level {
+    pub dims: (usize, usize),
+    // file of a frame at this level, with {step} replaced by its 8-digit step
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FrameInfo {
+    pub step: usize,
+    pub entropy: f64,
+    pub total_energy: f64,
+}
+
+impl RecordingIndex {
+    pub fn read(dir: &Path) -> Result<Self, FormatError> {
+        let reader = BufReader::new(File::open(dir.join(INDEX))?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn frame_path(&self, dir: &Path, level: usize, step: usize) -> PathBuf {
+        dir.join(
+            self.levels[level]
+                .path
+                .replace("{step}", &format!("{:08}", step)),
+        )
+    }
+}
+
+// averages 2x2 blocks; a leftover row or column at an odd edge is averaged on
+// its own
+pub fn downsample(board: &Array2<f64>) -> Array2<f64> {
+    let (h, w) = board.dim();
+    Array2::from_shape_fn((h.div_ceil(2), w.div_ceil(2)), |(i, j)| {
+        let rows = 2 * i..(2 * i + 2).min(h);
+        let cols = 2 * j..(2 * j + 2).min(w);
+        let n = rows.len() * cols.len();
+        let sum: f64 = rows
+            .flat_map(|r| cols.clone().map(move |c| (r, c)))
+            .map(|cell| board[cell])
+            .sum();
+        sum / n as f64
+    })
+}
+
+// halvings until the shorter side would drop below `min_side`
+pub fn default_levels((h, w): (usize, usize), min_side: usize) -> usize {
+    let mut side = h.min(w);
+    let mut levels = 1;
+    while side / 2 >= min_side {
+        side /= 2;
+        levels += 1;
+    }
+    levels
+}
+
+pub struct RecordingWriter {
+    dir: PathBuf,
+    index: RecordingIndex,
+}
+
+impl RecordingWriter {
+    pub fn create(
+        dir: &Path,
+        dims: (usize, usize),
+        every: usize,
+        levels: usize,
+    ) -> Result<Self, FormatError> {
+        fs::create_dir_all(dir.join("frames"))?;
+
+        let mut level_dims = dims;
+        let mut index_levels = Vec::new();
+        for n in 0..levels.max(1) {
+            fs::create_dir_all(dir.join(format!("level{}", n)))?;
+            index_levels.push(Level {
+                dims: level_dims,
+                path: format!("level{}/{{step}}.f32", n),
+            });
+            level_dims = (level_dims.0.div_ceil(2), level_dims.1.div_ceil(2));
+        }
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            index: RecordingIndex {
+                version: INDEX_VERSION,
+                dims,
+                every,
+                levels: index_levels,
+                frames: Vec::new(),
+            },
+        })
+    }
+
+    pub fn push(&mut self, frame: &Frame) -> Result<(), FormatError> {
+        let npy = self.dir.join(format!("frames/{:08}.npy", frame.step));
+        ndarray_npy::write_npy(&npy, &frame.board)
+            .map_err(|e| io::Error::other(format!("{}: {}", npy.display(), e)))?;
+
+        let mut board = frame.board.clone();
+        for level in 0..self.index.levels.len() {
+            if level > 0 {
+                board = downsample(&board);
+            }
+            let path = self.index.frame_path(&self.dir, level, frame.step);
+            let mut out = BufWriter::new(File::create(path)?);
+            for &energy in board.iter() {
+                out.write_all(&(energy as f32).to_le_bytes())?;
+            }
+            out.flush()?;
+        }
+
+        self.index.frames.push(FrameInfo {
+            step: frame.step,
+            entropy: frame.entropy(),
+            total_energy: frame.total_energy(),
+        });
+        Ok(())
+    }
+
+    // writes the index, which lists only the frames pushed so far
+    pub fn finish(self) -> Result<RecordingIndex, FormatError> {
+        let writer = BufWriter::new(File::create(self.dir.join(INDEX))?);
+        serde_json::to_writer_pretty(writer, &self.index)?;
+        Ok(self.index)
+    }
+}
@@ -0,0 +1,278 @@
+use crate::Config;
+use image::codecs::png::PngEncoder;
+use image::{ExtendedColorType, ImageEncoder, RgbImage};
+use serde_json::Value;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// points drawn per curve; longer runs are thinned to about this many
+pub const MAX_POINTS: usize = 2000;
+const CHART: (f64, f64) = (640.0, 320.0);
+// left, right, top and bottom
+const MARGINS: (f64, f64, f64, f64) = (64.0, 16.0, 12.0, 40.0);
+const TICKS: usize = 5;
+// the final frame is drawn at least this wide, whatever the board's size
+const FRAME_WIDTH: u32 = 480;
+
+// what a run leaves behind, as one HTML file with everything inlined, so it can
+// be sent on without the run's other files
+pub struct Report {
+    pub title: String,
+    pub seed: u64,
+    pub config: Config,
+    // (step, entropy) from the first step to the last
+    pub entropy: Vec<(usize, f64)>,
+    // from EnergyBudget::rows, then the drift
+    pub budget: Vec<(&'static str, f64)>,
+    pub drift: f64,
+    pub final_frame: RgbImage,
+}
+
+impl Report {
+    pub fn html(&self) -> io::Result<String> {
+        let mut s = String::new();
+        let steps = self.entropy.last().map_or(0, |&(step, _)| step);
+        let (first, last) = match (self.entropy.first(), self.entropy.last()) {
+            (Some(&(_, first)), Some(&(_, last))) => (first, last),
+            _ => (f64::NAN, f64::NAN),
+        };
+
+        writeln!(
+            s,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">"
+        )
+        .unwrap();
+        writeln!(s, "<title>{}</title>", escape(&self.title)).unwrap();
+        writeln!(s, "<style>{}</style>\n</head>\n<body>", STYLE).unwrap();
+        writeln!(s, "<h1>{}</h1>", escape(&self.title)).unwrap();
+        writeln!(
+            s,
+            "<p>{} steps, seed {}, config hash {:016x}; entropy {:.6} &rarr; {:.6}</p>",
+            steps,
+            self.seed,
+            self.config.hash(),
+            first,
+            last
+        )
+        .unwrap();
+
+        writeln!(s, "<h2>Entropy</h2>").unwrap();
+        let curve: Vec<(f64, f64)> = thin(&self.entropy)
+            .map(|(step, e)| (step as f64, e))
+            .collect();
+        s.push_str(&line_chart(&curve, "step", "entropy"));
+
+        writeln!(s, "<h2>Final frame</h2>").unwrap();
+        let (w, h) = self.final_frame.dimensions();
+        let width = w.max(FRAME_WIDTH.min(w * 8));
+        writeln!(
+            s,
+            "<img alt=\"the board at step {}\" width=\"{}\" height=\"{}\" src=\"data:image/png;base64,{}\">",
+            steps,
+            width,
+            h * width / w.max(1),
+            base64(&png(&self.final_frame)?)
+        )
+        .unwrap();
+
+        writeln!(s, "<h2>Energy budget</h2>\n<table>").unwrap();
+        for (name, value) in &self.budget {
+            writeln!(
+                s,
+                "<tr><td>{}</td><td>{:+.9}</td></tr>",
+                escape(name),
+                value
+            )
+            .unwrap();
+        }
+        writeln!(
+            s,
+            "<tr><td>numerical drift</td><td>{:+.3e}</td></tr>\n</table>",
+            self.drift
+        )
+        .unwrap();
+
+        writeln!(s, "<h2>Parameters</h2>\n<table>").unwrap();
+        let config = serde_json::to_value(&self.config).expect("a config always serializes");
+        if let Value::Object(fields) = config {
+            for (name, value) in fields {
+                let value = match value {
+                    Value::String(s) => s,
+                    value => value.to_string(),
+                };
+                writeln!(
+                    s,
+                    "<tr><td>{}</td><td><code>{}</code></td></tr>",
+                    escape(&name),
+                    escape(&value)
+                )
+                .unwrap();
+            }
+        }
+        writeln!(s, "</table>\n</body>\n</html>").unwrap();
+        Ok(s)
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.html()?)
+    }
+}
+
+const STYLE: &str = "body { font-family: sans-serif; max-width: 720px; margin: 2em auto; } \
+    table { border-collapse: collapse; } \
+    td { padding: 2px 12px 2px 0; vertical-align: top; font-variant-numeric: tabular-nums; } \
+    img { image-rendering: pixelated; } \
+    svg text { font-size: 11px; }";
+
+// every point of short curves; on long ones every k-th, and the last
+fn thin(points: &[(usize, f64)]) -> impl Iterator<Item = (usize, f64)> + '_ {
+    let every = points.len().div_ceil(MAX_POINTS).max(1);
+    let last = points.len().saturating_sub(1);
+    points
+        .iter()
+        .enumerate()
+        .filter(move |&(i, _)| i.is_multiple_of(every) || i == last)
+        .map(|(_, &p)| p)
+}
+
+// an SVG line chart with ticks on both axes, scaled to fit the points
+pub fn line_chart(points: &[(f64, f64)], x_label: &str, y_label: &str) -> String {
+    let (width, height) = CHART;
+    let (left, right, top, bottom) = MARGINS;
+    let range = |values: &mut dyn Iterator<Item = f64>| {
+        let (lo, hi) = values
+            .filter(|v| v.is_finite())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                (lo.min(v), hi.max(v))
+            });
+        match (lo, hi) {
+            _ if lo > hi => (0.0, 1.0),
+            // a flat line is drawn through the middle
+            _ if lo == hi => (lo - 1.0, hi + 1.0),
+            _ => (lo, hi),
+        }
+    };
+    let (x0, x1) = range(&mut points.iter().map(|p| p.0));
+    let (y0, y1) = range(&mut points.iter().map(|p| p.1));
+    let x = |v: f64| left + (v - x0) / (x1 - x0) * (width - left - right);
+    let y = |v: f64| height - bottom - (v - y0) / (y1 - y0) * (height - top - bottom);
+
+    let mut s = String::new();
+    writeln!(
+        s,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+        w = width,
+        h = height
+    )
+    .unwrap();
+    writeln!(
+        s,
+        "<path d=\"M{l} {t}V{b}H{r}\" fill=\"none\" stroke=\"#444\"/>",
+        l = left,
+        t = top,
+        b = height - bottom,
+        r = width - right
+    )
+    .unwrap();
+    for i in 0..=TICKS {
+        let t = i as f64 / TICKS as f64;
+        let (vx, vy) = (x0 + t * (x1 - x0), y0 + t * (y1 - y0));
+        writeln!(
+            s,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+            x(vx),
+            height - bottom + 16.0,
+            tick(vx)
+        )
+        .unwrap();
+        writeln!(
+            s,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\" dominant-baseline=\"middle\">{}</text>",
+            left - 6.0,
+            y(vy),
+            tick(vy)
+        )
+        .unwrap();
+    }
+    writeln!(
+        s,
+        "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+        (left + width - right) / 2.0,
+        height - 6.0,
+        escape(x_label)
+    )
+    .unwrap();
+    writeln!(
+        s,
+        "<text transform=\"translate(14 {:.1}) rotate(-90)\" text-anchor=\"middle\">{}</text>",
+        (top + height - bottom) / 2.0,
+        escape(y_label)
+    )
+    .unwrap();
+
+    let line: Vec<String> = points
+        .iter()
+        .filter(|p| p.0.is_finite() && p.1.is_finite())
+        .map(|&(px, py)| format!("{:.1},{:.1}", x(px), y(py)))
+        .collect();
+    writeln!(
+        s,
+        "<polyline points=\"{}\" fill=\"none\" stroke=\"#c33\" stroke-width=\"1.5\"/>\n</svg>",
+        line.join(" ")
+    )
+    .unwrap();
+    s
+}
+
+// short enough for an axis: whole numbers as they are, the rest to 4 figures
+fn tick(v: f64) -> String {
+    if v.fract() == 0.0 && v.abs() < 1e9 {
+        format!("{}", v as i64)
+    } else {
+        format!("{:.4}", v)
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string()
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn png(image: &RgbImage) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    PngEncoder::new(&mut out)
+        .write_image(
+            image.as_raw(),
+            image.width(),
+            image.height(),
+            ExtendedColorType::Rgb8,
+        )
+        .map_err(io::Error::other)?;
+    Ok(out)
+}
+
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut s = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
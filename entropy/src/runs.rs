@@ -0,0 +1,181 @@
+use crate::config::data_dir;
+use crate::format::{self, FormatError};
+use crate::session::Session;
+use crate::{ClampPolicy, Config, InitialCondition, Mode, Simulation};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+// every archived run is a directory holding these
+pub const MANIFEST: &str = "manifest.json";
+pub const FINAL_STATE: &str = "final.state";
+pub const SESSION: &str = "session.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub name: String,
+    // seconds since the unix epoch
+    pub started: u64,
+    pub duration_secs: f64,
+    pub steps: usize,
+    pub seed: u64,
+    pub initial_entropy: f64,
+    pub final_entropy: f64,
+    pub config: Config,
+}
+
+impl Manifest {
+    // the parameters that tell runs apart at a glance
+    pub fn config_summary(&self) -> String {
+        let config = &self.config;
+        let (h, w) = config.dims;
+        let mut parts = vec![
+            format!("{}x{}", h, w),
+            match &config.initial {
+                InitialCondition::Placed(hotspots) => format!("{} placed hotspots", hotspots.len()),
+                _ => format!("{} hotspots", config.hotspots),
+            },
+            format!("{:?}", config.boundary).to_lowercase(),
+        ];
+        if config.bath.is_some() {
+            parts.push("bath".to_string());
+        }
+        if config.drift.is_some() {
+            parts.push("drift".to_string());
+        }
+        if !config.sources.is_empty() {
+            parts.push(format!("{} sources", config.sources.len()));
+        }
+        if !config.fixed_sources.is_empty() {
+            parts.push(format!("{} fixed sources", config.fixed_sources.len()));
+        }
+        if !config.sinks.is_empty() {
+            parts.push(format!("{} sinks", config.sinks.len()));
+        }
+        if config.walls.is_some() {
+            parts.push("walls".to_string());
+        }
+        if config.mode == Mode::Deterministic {
+            parts.push("deterministic".to_string());
+        }
+        if config.thermal_noise > 0.0 {
+            parts.push(format!("noise {}", config.thermal_noise));
+        }
+        if config.clamp_policy != ClampPolicy::Keep {
+            parts.push(format!("clamp {:?}", config.clamp_policy).to_lowercase());
+        }
+        parts.join(", ")
+    }
+}
+
+// "YYYY-MM-DD HH:MM:SS" in UTC for seconds since the unix epoch
+pub fn utc_time(secs: u64) -> String {
+    let (days, rem) = (secs / 86400, secs % 86400);
+    // days to a civil date, from Howard Hinnant's date algorithms
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+// runs are named after when they started, e.g. 2026-10-14_09-30-00
+pub fn run_name(started: u64) -> String {
+    utc_time(started).replace(' ', "_").replace(':', "-")
+}
+
+// the archive in the data directory
+pub fn runs_dir() -> PathBuf {
+    data_dir().join("runs")
+}
+
+// writes a run into `root`/`manifest.name`, picking a free name if it is taken
+pub fn archive(
+    root: &Path,
+    manifest: &mut Manifest,
+    sim: &Simulation,
+    session: Option<&Session>,
+) -> Result<PathBuf, FormatError> {
+    fs::create_dir_all(root)?;
+    let base = manifest.name.clone();
+    let mut dir = root.join(&base);
+    for n in 2.. {
+        if !dir.exists() {
+            break;
+        }
+        manifest.name = format!("{}-{}", base, n);
+        dir = root.join(&manifest.name);
+    }
+    fs::create_dir(&dir)?;
+
+    let writer = BufWriter::new(File::create(dir.join(MANIFEST))?);
+    serde_json::to_writer_pretty(writer, manifest)?;
+    format::write_state(&dir.join(FINAL_STATE), sim)?;
+    if let Some(session) = session {
+        session.write(&dir.join(SESSION))?;
+    }
+
+    Ok(dir)
+}
+
+pub fn load(root: &Path, name: &str) -> Result<Manifest, FormatError> {
+    let reader = BufReader::new(File::open(root.join(name).join(MANIFEST))?);
+    Ok(serde_json::from_reader(reader)?)
+}
+
+// every readable manifest under `root`, oldest first; a missing archive is empty
+pub fn list(root: &Path) -> Result<Vec<Manifest>, FormatError> {
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut manifests = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if let Some(name) = entry.file_name().to_str() {
+            // directories that aren't runs, or half-written ones, are skipped
+            if let Ok(manifest) = load(root, name) {
+                manifests.push(manifest);
+            }
+        }
+    }
+    manifests.sort_by(|a, b| (a.started, &a.name).cmp(&(b.started, &b.name)));
+
+    Ok(manifests)
+}
+
+// copies a run's artifacts into `dest`, returning the files copied
+pub fn export(root: &Path, name: &str, dest: &Path) -> Result<Vec<PathBuf>, FormatError> {
+    let dir = root.join(name);
+    load(root, name)?;
+    fs::create_dir_all(dest)?;
+
+    let mut copied = Vec::new();
+    for file in [MANIFEST, FINAL_STATE, SESSION] {
+        let from = dir.join(file);
+        if from.is_file() {
+            let to = dest.join(file);
+            fs::copy(&from, &to)?;
+            copied.push(to);
+        }
+    }
+
+    Ok(copied)
+}
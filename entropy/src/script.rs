@@ -0,0 +1,81 @@
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+
+// something done to the run when it reaches `step`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScriptedEvent {
+    pub step: usize,
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    // scales the field about the board's center, then shifts it by (rows, cols).
+    // a scale of 0.5 zooms out 2x, leaving cold board around the old field
+    Resample {
+        #[serde(default = "default_scale")]
+        scale: f64,
+        #[serde(default)]
+        shift: (f64, f64),
+    },
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+impl Action {
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            Action::Resample { scale, shift } => {
+                scale > 0.0 && scale.is_finite() && shift.0.is_finite() && shift.1.is_finite()
+            }
+        }
+    }
+}
+
+// moves each cell's energy to the cells its transformed square overlaps, in
+// proportion to the overlap. energy mapped past an edge is added to the edge
+// cell instead, so the total is unchanged
+pub fn resample(board: &Array2<f64>, scale: f64, shift: (f64, f64)) -> Array2<f64> {
+    let (h, w) = board.dim();
+    let mut out = Array2::zeros((h, w));
+
+    for ((i, j), &e) in board.indexed_iter() {
+        if e == 0.0 {
+            continue;
+        }
+        let rows = overlaps(i, h, scale, shift.0);
+        let cols = overlaps(j, w, scale, shift.1);
+
+        for &(ti, fi) in &rows {
+            for &(tj, fj) in &cols {
+                out[[ti, tj]] += e * fi * fj;
+            }
+        }
+    }
+
+    out
+}
+
+// the cells along one axis that the image of [k, k + 1] covers, with the share of
+// it each one gets
+fn overlaps(k: usize, n: usize, scale: f64, shift: f64) -> Vec<(usize, f64)> {
+    let center = n as f64 / 2.0;
+    let start = center + scale * (k as f64 - center) + shift;
+    let end = start + scale;
+
+    let mut shares = Vec::new();
+    let mut t = start.floor();
+    while t < end {
+        let overlap = end.min(t + 1.0) - start.max(t);
+        if overlap > 0.0 {
+            let cell = (t.max(0.0) as usize).min(n - 1);
+            shares.push((cell, overlap / scale));
+        }
+        t += 1.0;
+    }
+
+    shares
+}
@@ -0,0 +1,98 @@
+use crate::stats::shannon_entropy;
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// rows written between flushes, so `tail -f` keeps up with a long run without
+// a write per step
+pub const FLUSH_EVERY: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SeriesRow {
+    pub step: usize,
+    pub total_energy: f64,
+    pub max_energy: f64,
+    pub variance: f64,
+    pub entropy: f64,
+}
+
+impl SeriesRow {
+    pub const HEADER: &'static str = "step,total_energy,max_energy,variance,entropy";
+
+    pub fn of(step: usize, board: &Array2<f64>) -> Self {
+        Self {
+            step,
+            total_energy: board.sum(),
+            max_energy: board.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            variance: board.var(0.0),
+            entropy: shannon_entropy(board),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeriesFormat {
+    Csv,
+    // one JSON object per line
+    Json,
+}
+
+impl SeriesFormat {
+    // .json and .jsonl files get JSON lines, anything else CSV
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json" | "jsonl") => Self::Json,
+            _ => Self::Csv,
+        }
+    }
+}
+
+// per-step statistics of a run, written as it goes
+pub struct SeriesWriter {
+    out: BufWriter<File>,
+    format: SeriesFormat,
+    unflushed: usize,
+}
+
+impl SeriesWriter {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let format = SeriesFormat::from_path(path);
+        let mut out = BufWriter::new(File::create(path)?);
+        if format == SeriesFormat::Csv {
+            writeln!(out, "{}", SeriesRow::HEADER)?;
+        }
+        // the header shows up before the first rows do
+        out.flush()?;
+        Ok(Self {
+            out,
+            format,
+            unflushed: 0,
+        })
+    }
+
+    pub fn push(&mut self, row: &SeriesRow) -> io::Result<()> {
+        match self.format {
+            SeriesFormat::Csv => writeln!(
+                self.out,
+                "{},{},{},{},{}",
+                row.step, row.total_energy, row.max_energy, row.variance, row.entropy
+            )?,
+            SeriesFormat::Json => {
+                serde_json::to_writer(&mut self.out, row)?;
+                writeln!(self.out)?;
+            }
+        }
+        self.unflushed += 1;
+        if self.unflushed >= FLUSH_EVERY {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.unflushed = 0;
+        self.out.flush()
+    }
+}
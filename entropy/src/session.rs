@@ -0,0 +1,155 @@
+use crate::format::FormatError;
+use crate::Config;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+// the hotkeys the window understands, independent of the windowing library
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Key {
+    TogglePause,
+    // step through history while paused
+    Back,
+    Forward,
+    NextDisplay,
+    Randomize,
+    RotateView,
+    NextField,
+    NextTracerView,
+    // a click on the board, in board coordinates
+    Pin { row: usize, col: usize },
+    Unpin,
+    // a shift-click, or a frame of a shift-drag, over this cell
+    Deposit { row: usize, col: usize },
+    // back to step 0, from the command palette
+    Reset,
+    // one of presets::NAMES by position, loaded from the command palette
+    Preset { index: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionEvent {
+    // how many steps the simulation had taken when the key was pressed
+    pub step: usize,
+    pub key: Key,
+}
+
+// the keys pressed during a seeded run, so the run can be replayed with the
+// same interventions at the same steps
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub seed: u64,
+    // what the run started with; sessions from before it was kept have none
+    #[serde(default)]
+    pub config: Option<Config>,
+    pub events: Vec<SessionEvent>,
+}
+
+// config fields that only change how a run is shown, paced or saved, which a
+// replay may set as it likes
+const PRESENTATION: &[&str] = &[
+    "sleep_interval_ms",
+    "steps_per_frame",
+    "auto_speed",
+    "power_save",
+    "size_factor",
+    "marginals",
+    "marginal_size",
+    "debug_dump_path",
+    "frame_output_dir",
+    "frame_every",
+    "video",
+    "checkpoint_every",
+    "checkpoint_path",
+    "debug_overlay",
+    "history_memory_mb",
+    "seed",
+    "display",
+    "headless",
+    "palette",
+    "colormap",
+    "max_energy",
+    "normalization",
+    "scale",
+    "linear_light",
+    "local_entropy_window",
+    "kl_threshold",
+    "mi_partition",
+    "mi_bins",
+    "entropy_estimator",
+    "archive",
+];
+
+impl Session {
+    pub fn new(seed: u64, config: Config) -> Self {
+        Self {
+            seed,
+            config: Some(config),
+            events: Vec::new(),
+        }
+    }
+
+    // the fields that would make a run with `config` go differently from the
+    // recorded one, none if it's the same run or the session has no config
+    pub fn mismatches(&self, config: &Config) -> Vec<String> {
+        let Some(recorded) = &self.config else {
+            return Vec::new();
+        };
+        let fields = |config: &Config| match serde_json::to_value(config) {
+            Ok(serde_json::Value::Object(fields)) => fields,
+            _ => unreachable!("a config serializes to an object"),
+        };
+        let (recorded, current) = (fields(recorded), fields(config));
+        recorded
+            .keys()
+            .chain(current.keys().filter(|k| !recorded.contains_key(*k)))
+            .filter(|field| !PRESENTATION.contains(&field.as_str()))
+            .filter(|field| recorded.get(*field) != current.get(*field))
+            .cloned()
+            .collect()
+    }
+
+    pub fn record(&mut self, step: usize, key: Key) {
+        self.events.push(SessionEvent { step, key });
+    }
+
+    pub fn read(path: &Path) -> Result<Self, FormatError> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), FormatError> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    pub fn replay(self) -> Replay {
+        Replay {
+            events: self.events.into(),
+        }
+    }
+}
+
+pub struct Replay {
+    events: VecDeque<SessionEvent>,
+}
+
+impl Replay {
+    // the recorded keys that were pressed by `step`, in order; a reset or a
+    // preset ends the batch, since the steps after it count from 0 again
+    pub fn due(&mut self, step: usize) -> Vec<Key> {
+        let mut keys = Vec::new();
+        while let Some(event) = self.events.front().filter(|e| e.step <= step) {
+            keys.push(event.key);
+            self.events.pop_front();
+            if matches!(keys.last(), Some(Key::Reset | Key::Preset { .. })) {
+                break;
+            }
+        }
+        keys
+    }
+}
@@ -0,0 +1,539 @@
+use crate::config::Assertions;
+use crate::lattice::Lattice;
+use crate::model::{
+    deterministic_time_step, drain_sinks, fixed_sources, heat_sources, init_board,
+    lattice_time_step, scratch_time_step, stochastic_reset, thermal_noise, Backend, Boundary,
+    InitialCondition, KernelFlags, ParallelScratch, Scheme, SimRng, StepReport, TrapSites,
+    WaitingTimers,
+};
+use crate::script::{self, Action};
+use crate::transform::{load_field, load_image};
+use crate::{debug, stats, Board, Config, ConfigError};
+use ndarray::Array2;
+use rand::SeedableRng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// all of the state needed to continue a run exactly, rng included
+#[derive(Serialize, Deserialize)]
+#[serde(from = "SimulationState")]
+pub struct Simulation {
+    config: Config,
+    seed: u64,
+    rng: SimRng,
+    // the rng as it was before the first step, for reset()
+    start_rng: SimRng,
+    board: Array2<f64>,
+    // what stochastic resetting goes back to
+    initial: Array2<f64>,
+    // where the next step is accumulated
+    #[serde(skip)]
+    scratch: Array2<f64>,
+    // the parallel backend's buffers and thread pool
+    #[serde(skip)]
+    parallel: ParallelScratch,
+    // drawn on the first step of a run with `waiting`
+    timers: Option<WaitingTimers>,
+    traps: Option<TrapSites>,
+    source_positions: Vec<Option<(f64, f64)>>,
+    // latent heat per cell under `phase_change`, set up on the first step
+    latent: Option<Array2<f64>>,
+    // the deterministic counterpart of the board under `shadow`
+    shadow: Option<Array2<f64>>,
+    // how much dye each cell holds under `tracer`
+    tracer: Option<Array2<f64>>,
+    // the populations of the lbm scheme, set up on its first step
+    lattice: Option<Lattice>,
+    steps: usize,
+    #[serde(skip)]
+    last_report: StepReport,
+    // put down by deposit() since the last step, which the next step's report
+    // counts with the sources
+    #[serde(skip)]
+    deposited: f64,
+}
+
+#[derive(Deserialize)]
+struct SimulationState {
+    config: Config,
+    seed: u64,
+    rng: SimRng,
+    // absent from states saved before reset() existed; those reset to the
+    // initial board but carry on with the rng they were saved with
+    #[serde(default)]
+    start_rng: Option<SimRng>,
+    board: Array2<f64>,
+    // absent from states saved before resetting existed
+    #[serde(default)]
+    initial: Option<Array2<f64>>,
+    #[serde(default)]
+    timers: Option<WaitingTimers>,
+    #[serde(default)]
+    traps: Option<TrapSites>,
+    #[serde(default)]
+    source_positions: Vec<Option<(f64, f64)>>,
+    #[serde(default)]
+    latent: Option<Array2<f64>>,
+    #[serde(default)]
+    shadow: Option<Array2<f64>>,
+    #[serde(default)]
+    tracer: Option<Array2<f64>>,
+    #[serde(default)]
+    lattice: Option<Lattice>,
+    steps: usize,
+}
+
+impl From<SimulationState> for Simulation {
+    fn from(state: SimulationState) -> Self {
+        Self {
+            scratch: Array2::zeros(state.board.dim()),
+            parallel: ParallelScratch::default(),
+            config: state.config,
+            seed: state.seed,
+            start_rng: state.start_rng.unwrap_or_else(|| state.rng.clone()),
+            rng: state.rng,
+            initial: state.initial.unwrap_or_else(|| state.board.clone()),
+            board: state.board,
+            timers: state.timers,
+            traps: state.traps,
+            source_positions: state.source_positions,
+            latent: state.latent,
+            shadow: state.shadow,
+            tracer: state.tracer,
+            lattice: state.lattice,
+            steps: state.steps,
+            last_report: StepReport::default(),
+            deposited: 0.0,
+        }
+    }
+}
+
+impl Simulation {
+    pub fn new(config: Config) -> Result<Self, ConfigError> {
+        if let InitialCondition::File(path) = &config.initial {
+            let board =
+                load_field(path, &config.transform).map_err(ConfigError::BadInitialField)?;
+            return Self::from_board(config, board);
+        }
+        if let InitialCondition::Image { path, energy } = &config.initial {
+            config.validate()?;
+            let (h, w) = config.dims;
+            let energy = energy.unwrap_or((h * w) as f64);
+            let board = load_image(path, config.dims, energy, &config.transform)
+                .map_err(ConfigError::BadInitialField)?;
+            return Self::from_board(config, board);
+        }
+        config.validate()?;
+
+        let seed = config.seed.unwrap_or_else(rand::random);
+        let mut rng = SimRng::seed_from_u64(seed);
+        let mut board: Array2<f64> = init_board(&config, &mut rng);
+        clear_walls(&config, &mut board);
+        let scratch = Array2::zeros(config.dims);
+        let traps = place_traps(&config, &mut rng)?;
+
+        Ok(Self {
+            tracer: config.tracer.map(|tracer| tracer.initial(config.dims)),
+            config,
+            seed,
+            start_rng: rng.clone(),
+            rng,
+            initial: board.clone(),
+            board,
+            scratch,
+            parallel: ParallelScratch::default(),
+            timers: None,
+            traps,
+            source_positions: Vec::new(),
+            latent: None,
+            shadow: None,
+            lattice: None,
+            steps: 0,
+            last_report: StepReport::default(),
+            deposited: 0.0,
+        })
+    }
+
+    // starts from a given board instead of placing hotspots; dims come from the board
+    pub fn from_board(mut config: Config, mut board: Array2<f64>) -> Result<Self, ConfigError> {
+        config.dims = board.dim();
+        config.validate()?;
+        clear_walls(&config, &mut board);
+
+        let seed = config.seed.unwrap_or_else(rand::random);
+        let mut rng = SimRng::seed_from_u64(seed);
+        let traps = place_traps(&config, &mut rng)?;
+
+        Ok(Self {
+            scratch: Array2::zeros(config.dims),
+            parallel: ParallelScratch::default(),
+            tracer: config.tracer.map(|tracer| tracer.initial(config.dims)),
+            config,
+            seed,
+            start_rng: rng.clone(),
+            rng,
+            initial: board.clone(),
+            board,
+            timers: None,
+            traps,
+            source_positions: Vec::new(),
+            latent: None,
+            shadow: None,
+            lattice: None,
+            steps: 0,
+            last_report: StepReport::default(),
+            deposited: 0.0,
+        })
+    }
+
+    pub fn step(&mut self) {
+        self.steps += 1;
+
+        if self.config.shadow {
+            let shadow = self.shadow.get_or_insert_with(|| self.board.clone());
+            deterministic_time_step(&mut self.scratch, shadow, &self.config);
+        }
+
+        let held = match &self.config.waiting {
+            Some(waiting) => {
+                let rng = &mut self.rng;
+                let timers = self
+                    .timers
+                    .get_or_insert_with(|| WaitingTimers::new(self.board.dim(), waiting, rng));
+                Some(timers.withhold(&mut self.board, waiting, &mut self.rng))
+            }
+            None => None,
+        };
+
+        // a tracer added with set_config starts out as it would have at step 0
+        let dims = self.board.dim();
+        let tracer = match self.config.tracer {
+            Some(tracer) => Some(self.tracer.get_or_insert_with(|| tracer.initial(dims))),
+            None => None,
+        };
+        if self.config.scheme != Scheme::Lbm {
+            self.lattice = None;
+        }
+        self.last_report = if self.config.scheme == Scheme::Lbm {
+            let lattice = self
+                .lattice
+                .get_or_insert_with(|| Lattice::at_equilibrium(&self.board, &self.config));
+            lattice_time_step(&mut self.board, lattice, &self.config)
+        } else if self.config.debug {
+            debug::checked_time_step(
+                &mut self.scratch,
+                &mut self.board,
+                tracer,
+                &self.config,
+                &mut self.rng,
+                self.seed,
+                self.steps,
+            )
+        } else {
+            scratch_time_step(
+                &mut self.scratch,
+                &mut self.board,
+                tracer,
+                &self.config,
+                &mut self.rng,
+                None,
+                &mut self.parallel,
+            )
+        };
+
+        self.check_kernel();
+
+        if let Some(held) = held {
+            self.board += &held;
+        }
+
+        // nothing put into the board afterward goes into a wall
+        let walls = self.config.wall_mask();
+        if !self.config.sources.is_empty() {
+            self.last_report.source_in = heat_sources(
+                &mut self.board,
+                &self.config.sources,
+                &mut self.source_positions,
+                self.steps,
+                &mut self.rng,
+                walls.as_deref(),
+            );
+        }
+
+        if !self.config.fixed_sources.is_empty() {
+            self.last_report.source_in += fixed_sources(
+                &mut self.board,
+                &self.config.fixed_sources,
+                walls.as_deref(),
+            );
+        }
+        self.last_report.source_in += std::mem::take(&mut self.deposited);
+        if !self.config.sinks.is_empty() {
+            self.last_report.sink_out = drain_sinks(&mut self.board, &self.config.sinks);
+        }
+
+        if let (Some(sites), Some(traps)) = (&mut self.traps, &self.config.traps) {
+            sites.apply(&mut self.board, traps);
+            self.last_report.trapped = sites.held_energy();
+        }
+
+        if let Some(phase) = &self.config.phase_change {
+            let latent = self
+                .latent
+                .get_or_insert_with(|| Array2::zeros(self.board.dim()));
+            phase.apply(&mut self.board, latent);
+            self.last_report.latent = latent.sum();
+        }
+
+        if self.config.reset_rate > 0.0 {
+            self.last_report.reset_in = stochastic_reset(
+                &mut self.board,
+                &self.initial,
+                self.config.reset_rate,
+                self.config.reset_scope,
+                &mut self.rng,
+                walls.as_deref(),
+            );
+        }
+
+        if self.config.thermal_noise > 0.0 {
+            self.last_report.noise_in = thermal_noise(
+                &mut self.board,
+                self.config.thermal_noise,
+                self.config.noise_spectrum.as_ref(),
+                &mut self.rng,
+                walls.as_deref(),
+            );
+        }
+
+        // only the board is resampled; traps, timers and latent heat stay in place
+        for event in self.config.events.iter().filter(|e| e.step == self.steps) {
+            match event.action {
+                Action::Resample { scale, shift } => {
+                    self.board = script::resample(&self.board, scale, shift);
+                    clear_walls(&self.config, &mut self.board);
+                }
+            }
+        }
+    }
+
+    fn check_kernel(&self) {
+        let bad = match &self.last_report.flags {
+            Some(flags) => flags.count(KernelFlags::UNNORMALIZED),
+            None => 0,
+        };
+        if bad == 0 {
+            return;
+        }
+
+        let message = format!(
+            "step {}: the weights of {} cells don't sum to 1 within {}",
+            self.steps, bad, self.config.assertion_tolerance
+        );
+        match self.config.assertions {
+            Assertions::Off => {}
+            Assertions::Warn => eprintln!("{}", message),
+            Assertions::Panic => panic!("{}", message),
+        }
+    }
+
+    pub fn board(&self) -> &Array2<f64> {
+        &self.board
+    }
+
+    pub fn shadow(&self) -> Option<&Array2<f64>> {
+        self.shadow.as_ref()
+    }
+
+    // the dye, while the config has a tracer
+    pub fn tracer(&self) -> Option<&Array2<f64>> {
+        self.tracer
+            .as_ref()
+            .filter(|_| self.config.tracer.is_some())
+    }
+
+    pub fn last_report(&self) -> &StepReport {
+        &self.last_report
+    }
+
+    pub fn traps(&self) -> Option<&TrapSites> {
+        self.traps.as_ref()
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    // swaps in new parameters mid-run; traps and sources already placed stay put
+    pub fn set_config(&mut self, config: Config) -> Result<(), ConfigError> {
+        config.validate()?;
+        if config.dims != self.config.dims {
+            return Err(ConfigError::DimsChanged {
+                from: self.config.dims,
+                to: config.dims,
+            });
+        }
+
+        self.config = config;
+        Ok(())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // back to step 0 with the same seed, so the run repeats exactly; traps stay
+    // where they were placed and parameters changed since are kept
+    pub fn reset(&mut self) {
+        self.rng = self.start_rng.clone();
+        self.board.assign(&self.initial);
+        self.timers = None;
+        if let Some(traps) = &mut self.traps {
+            traps.clear();
+        }
+        self.source_positions.clear();
+        self.latent = None;
+        self.shadow = None;
+        self.lattice = None;
+        self.tracer = self
+            .config
+            .tracer
+            .map(|tracer| tracer.initial(self.board.dim()));
+        self.steps = 0;
+        self.last_report = StepReport::default();
+        self.deposited = 0.0;
+    }
+
+    // adds `energy` to a cell between steps, e.g. the one under the mouse;
+    // walls and cells off the board take none
+    pub fn deposit(&mut self, cell: (usize, usize), energy: f64) {
+        let (h, w) = self.board.dim();
+        if cell.0 >= h || cell.1 >= w {
+            return;
+        }
+        if self.config.wall_mask().is_some_and(|walls| walls[cell]) {
+            return;
+        }
+        self.board[cell] += energy;
+        self.deposited += energy;
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+}
+
+// walls start out empty, whatever the initial condition put there
+fn clear_walls(config: &Config, board: &mut Array2<f64>) {
+    if let Some(walls) = config.wall_mask() {
+        board.zip_mut_with(&walls, |e, &wall| {
+            if wall {
+                *e = 0.0;
+            }
+        });
+    }
+}
+
+fn place_traps(config: &Config, rng: &mut SimRng) -> Result<Option<TrapSites>, ConfigError> {
+    config
+        .traps
+        .as_ref()
+        .map(|traps| TrapSites::place(traps, config.dims, &config.transform, rng))
+        .transpose()
+}
+
+// steps forever, yielding a copy of the board after each step
+impl Iterator for Simulation {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Frame> {
+        self.step();
+
+        Some(Frame {
+            step: self.steps,
+            board: self.board.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub step: usize,
+    pub board: Array2<f64>,
+}
+
+impl Frame {
+    pub fn entropy(&self) -> f64 {
+        stats::shannon_entropy(&self.board)
+    }
+
+    pub fn total_energy(&self) -> f64 {
+        self.board.total()
+    }
+}
+
+// `n` independent runs of `config`; with a seed, run k is seeded with seed + k
+pub fn par_runs(
+    config: &Config,
+    n: usize,
+) -> Result<impl ParallelIterator<Item = Simulation> + '_, ConfigError> {
+    config.validate()?;
+
+    Ok((0..n).into_par_iter().map(move |k| {
+        let mut config = config.clone();
+        config.seed = config.seed.map(|s| s.wrapping_add(k as u64));
+        Simulation::new(config).expect("config was already validated")
+    }))
+}
+
+// every parameter is optional and starts at `Config::default()`; `build` checks
+// the combination
+#[derive(Debug, Clone, Default)]
+pub struct SimulationBuilder {
+    config: Config,
+}
+
+impl SimulationBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn dims(mut self, h: usize, w: usize) -> Self {
+        self.config.dims = (h, w);
+        self
+    }
+
+    pub fn hotspots(mut self, hotspots: usize) -> Self {
+        self.config.hotspots = hotspots;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.config.seed = Some(seed);
+        self
+    }
+
+    pub fn heat(mut self, heat: f64) -> Self {
+        self.config.heat = heat;
+        self
+    }
+
+    pub fn boundary(mut self, boundary: Boundary) -> Self {
+        self.config.boundary = boundary;
+        self
+    }
+
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.config.backend = backend;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.config.threads = Some(threads);
+        self
+    }
+
+    pub fn build(self) -> Result<Simulation, ConfigError> {
+        Simulation::new(self.config)
+    }
+}
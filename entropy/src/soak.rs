@@ -0,0 +1,229 @@
+use crate::debug::find_anomaly;
+use crate::format::{self, FormatError};
+use crate::stats::EnergyBudget;
+use crate::Simulation;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// drift in the energy budget, relative to what the board should hold, past
+// which a check fails
+pub const DRIFT_TOLERANCE: f64 = 1e-6;
+pub const LOG: &str = "soak.tsv";
+const LOG_HEADER: &str = "elapsed_secs\tstep\ttotal_energy\tdrift\tresident_bytes\tproblems";
+
+// how long a soak runs and how often it looks at itself, parsed from e.g.
+// "hours=24 check=1000"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SoakSpec {
+    pub hours: f64,
+    // stop early after this many steps, for short trial soaks
+    pub steps: Option<usize>,
+    // steps between integrity checks, each of which logs a row
+    pub check: usize,
+    // checks between checkpoints
+    pub checkpoint: usize,
+    // checkpoints kept, and log rows before the log is rotated
+    pub keep: usize,
+    pub log_rows: usize,
+}
+
+impl Default for SoakSpec {
+    fn default() -> Self {
+        Self {
+            hours: 24.0,
+            steps: None,
+            check: 1000,
+            checkpoint: 10,
+            keep: 3,
+            log_rows: 10_000,
+        }
+    }
+}
+
+impl std::str::FromStr for SoakSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut spec = SoakSpec::default();
+        for part in s.split([' ', ',']).filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, got {:?}", part))?;
+            let bad = |e: &dyn std::fmt::Display| format!("{:?}: {}", part, e);
+            match key {
+                "hours" => spec.hours = value.parse().map_err(|e| bad(&e))?,
+                "steps" => spec.steps = Some(value.parse().map_err(|e| bad(&e))?),
+                "check" => spec.check = value.parse().map_err(|e| bad(&e))?,
+                "checkpoint" => spec.checkpoint = value.parse().map_err(|e| bad(&e))?,
+                "keep" => spec.keep = value.parse().map_err(|e| bad(&e))?,
+                "log_rows" => spec.log_rows = value.parse().map_err(|e| bad(&e))?,
+                _ => return Err(format!("unknown soak setting {:?}", key)),
+            }
+        }
+        if !(spec.hours >= 0.0 && spec.hours.is_finite()) {
+            return Err("hours must be a non-negative number".to_string());
+        }
+        if spec.check == 0 || spec.checkpoint == 0 || spec.keep == 0 || spec.log_rows == 0 {
+            return Err("check, checkpoint, keep and log_rows must be at least 1".to_string());
+        }
+        Ok(spec)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SoakReport {
+    pub steps: usize,
+    pub checks: usize,
+    // (step, what went wrong) for every failed check
+    pub failures: Vec<(usize, String)>,
+    pub first_resident: Option<u64>,
+    pub last_resident: Option<u64>,
+}
+
+impl SoakReport {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+// resident memory of this process, where /proc says
+pub fn resident_bytes() -> Option<u64> {
+    let statm = fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4096)
+}
+
+// what's wrong with the run right now; `checkpoint`, if given, is read back and
+// has to match the run it was written from
+pub fn check(sim: &Simulation, budget: &EnergyBudget, checkpoint: Option<&Path>) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some((cell, value)) = find_anomaly(sim.board()) {
+        problems.push(format!("cell {:?} has energy {}", cell, value));
+    }
+    if sim
+        .tracer()
+        .is_some_and(|dye| dye.iter().any(|c| !c.is_finite()))
+    {
+        problems.push("the dye isn't finite".to_string());
+    }
+    let relative = budget.drift().abs() / budget.expected().abs().max(f64::MIN_POSITIVE);
+    if relative.is_nan() || relative > DRIFT_TOLERANCE {
+        problems.push(format!("energy drifted by {:.3e} relative", relative));
+    }
+    if let Some(path) = checkpoint {
+        match format::read_state(path) {
+            Ok(read) if read.board() == sim.board() && read.steps() == sim.steps() => {}
+            Ok(_) => problems.push(format!("{} doesn't match the run", path.display())),
+            Err(e) => problems.push(format!("couldn't read {}: {}", path.display(), e)),
+        }
+    }
+    problems
+}
+
+// runs `sim` for the spec's hours, or its steps, checking it every `check` steps
+// and writing the log and checkpoints under `dir`
+pub fn run(sim: &mut Simulation, spec: &SoakSpec, dir: &Path) -> Result<SoakReport, FormatError> {
+    fs::create_dir_all(dir)?;
+    let started = Instant::now();
+    let deadline = Duration::from_secs_f64(spec.hours * 3600.0);
+    let mut log = Log::create(dir, spec)?;
+    let mut budget = EnergyBudget::new(sim.board());
+    let mut checkpoints: Vec<PathBuf> = Vec::new();
+    let mut report = SoakReport::default();
+    let start_step = sim.steps();
+
+    loop {
+        let done = sim.steps() - start_step;
+        if spec.steps.is_some_and(|steps| done >= steps) || started.elapsed() >= deadline {
+            break;
+        }
+        sim.step();
+        budget.update(sim.board(), sim.last_report());
+        if !(sim.steps() - start_step).is_multiple_of(spec.check) {
+            continue;
+        }
+
+        report.checks += 1;
+        let checkpoint = report
+            .checks
+            .is_multiple_of(spec.checkpoint)
+            .then(|| dir.join(format!("checkpoint-{:010}.state", sim.steps())));
+        if let Some(path) = &checkpoint {
+            format::write_state(path, sim)?;
+            checkpoints.push(path.clone());
+            if checkpoints.len() > spec.keep {
+                fs::remove_file(checkpoints.remove(0))?;
+            }
+        }
+
+        let problems = check(sim, &budget, checkpoint.as_deref());
+        let resident = resident_bytes();
+        report.first_resident = report.first_resident.or(resident);
+        report.last_resident = resident;
+        log.row(&format!(
+            "{:.1}\t{}\t{}\t{:e}\t{}\t{}",
+            started.elapsed().as_secs_f64(),
+            sim.steps(),
+            sim.board().sum(),
+            budget.drift(),
+            resident.map(|r| r.to_string()).unwrap_or_default(),
+            problems.join("; ")
+        ))?;
+        report
+            .failures
+            .extend(problems.into_iter().map(|p| (sim.steps(), p)));
+    }
+
+    report.steps = sim.steps() - start_step;
+    log.out.flush()?;
+    Ok(report)
+}
+
+// soak.tsv, moved to soak.1.tsv and so on once it holds `log_rows` rows, with
+// only the newest `keep` old logs kept
+struct Log {
+    dir: PathBuf,
+    out: BufWriter<File>,
+    rows: usize,
+    max_rows: usize,
+    keep: usize,
+}
+
+impl Log {
+    fn create(dir: &Path, spec: &SoakSpec) -> Result<Self, FormatError> {
+        let mut log = Self {
+            dir: dir.to_path_buf(),
+            out: BufWriter::new(File::create(dir.join(LOG))?),
+            rows: 0,
+            max_rows: spec.log_rows,
+            keep: spec.keep,
+        };
+        writeln!(log.out, "{}", LOG_HEADER)?;
+        Ok(log)
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        self.dir.join(format!("soak.{}.tsv", n))
+    }
+
+    fn row(&mut self, row: &str) -> Result<(), FormatError> {
+        if self.rows == self.max_rows {
+            self.out.flush()?;
+            let _ = fs::remove_file(self.rotated(self.keep));
+            for n in (1..self.keep).rev() {
+                let _ = fs::rename(self.rotated(n), self.rotated(n + 1));
+            }
+            fs::rename(self.dir.join(LOG), self.rotated(1))?;
+            self.out = BufWriter::new(File::create(self.dir.join(LOG))?);
+            writeln!(self.out, "{}", LOG_HEADER)?;
+            self.rows = 0;
+        }
+        writeln!(self.out, "{}", row)?;
+        // each row is a check, so few enough to write straight through
+        self.out.flush()?;
+        self.rows += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+use crate::script::ScriptedEvent;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// runs more steps per frame as the entropy settles, so the window lingers on
+// the fast early dynamics and hurries through equilibration
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AutoSpeed {
+    // entropy change per frame to aim for
+    #[serde(default = "default_target_change")]
+    pub target_change: f64,
+    #[serde(default = "default_max_steps_per_frame")]
+    pub max_steps_per_frame: usize,
+    // how many steps ahead of a scripted event to drop back to the slowest speed
+    #[serde(default = "default_event_lead")]
+    pub event_lead: usize,
+}
+
+fn default_target_change() -> f64 {
+    1e-3
+}
+
+fn default_max_steps_per_frame() -> usize {
+    64
+}
+
+fn default_event_lead() -> usize {
+    20
+}
+
+impl AutoSpeed {
+    pub fn is_valid(&self, steps_per_frame: usize) -> bool {
+        self.target_change > 0.0 && self.max_steps_per_frame >= steps_per_frame
+    }
+}
+
+// slows the window down to a few frames a second once the board has settled,
+// so a static picture doesn't keep a laptop busy
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PowerSave {
+    // entropy change per step below which the board counts as settled
+    #[serde(default = "default_settled_change")]
+    pub settled_change: f64,
+    // frames it has to stay settled for before the window slows down
+    #[serde(default = "default_settle_frames")]
+    pub settle_frames: usize,
+    #[serde(default = "default_idle_fps")]
+    pub idle_fps: f64,
+}
+
+fn default_settled_change() -> f64 {
+    1e-6
+}
+
+fn default_settle_frames() -> usize {
+    120
+}
+
+fn default_idle_fps() -> f64 {
+    1.0
+}
+
+impl PowerSave {
+    pub fn is_valid(&self) -> bool {
+        self.settled_change > 0.0 && self.idle_fps > 0.0 && self.idle_fps.is_finite()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Idler {
+    save: PowerSave,
+    // frames in a row the board has been settled for
+    calm: usize,
+}
+
+impl Idler {
+    pub fn new(save: PowerSave) -> Self {
+        Self { save, calm: 0 }
+    }
+
+    // a frame in which the entropy changed by `change` per step on average
+    pub fn observe(&mut self, change: f64) {
+        if change.abs() < self.save.settled_change {
+            self.calm += 1;
+        } else {
+            self.calm = 0;
+        }
+    }
+
+    // back to full speed, on input or an upcoming scripted event
+    pub fn wake(&mut self) {
+        self.calm = 0;
+    }
+
+    // wakes if a scripted event falls in the `steps` steps after `step`
+    pub fn expect(&mut self, step: usize, steps: usize, events: &[ScriptedEvent]) {
+        if events
+            .iter()
+            .any(|e| e.step > step && e.step <= step + steps)
+        {
+            self.wake();
+        }
+    }
+
+    pub fn idle(&self) -> bool {
+        self.calm >= self.save.settle_frames
+    }
+
+    // the least time a frame should take
+    pub fn frame_time(&self) -> Duration {
+        if self.idle() {
+            Duration::from_secs_f64(1.0 / self.save.idle_fps)
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Governor {
+    speed: AutoSpeed,
+    // the slowest it goes
+    min_steps: usize,
+    // running average of |entropy change| per step
+    change: Option<f64>,
+}
+
+impl Governor {
+    // weight of the newest step in the running average
+    const ALPHA: f64 = 0.05;
+
+    pub fn new(speed: AutoSpeed, min_steps: usize) -> Self {
+        Self {
+            speed,
+            min_steps,
+            change: None,
+        }
+    }
+
+    pub fn observe(&mut self, production: f64) {
+        let change = production.abs();
+        self.change = Some(match self.change {
+            Some(c) => c + Self::ALPHA * (change - c),
+            None => change,
+        });
+    }
+
+    // how many steps the next frame should run, starting from `step`
+    pub fn steps_per_frame(&self, step: usize, events: &[ScriptedEvent]) -> usize {
+        let (min, max) = (self.min_steps, self.speed.max_steps_per_frame);
+        let event_near = events
+            .iter()
+            .any(|e| e.step > step && e.step <= step + self.speed.event_lead);
+        if event_near {
+            return min;
+        }
+
+        let steps = match self.change {
+            Some(c) if c > 0.0 => (self.speed.target_change / c) as usize,
+            Some(_) => max,
+            None => min,
+        };
+        steps.clamp(min, max)
+    }
+}
@@ -0,0 +1,599 @@
+use crate::config::Partition;
+use crate::estimator::{EntropyEstimator, Estimator};
+use crate::model::{BathRegion, StepReport};
+use crate::{Board, Config};
+use itertools::iproduct;
+use ndarray::{Array1, Array2, Axis};
+use std::fmt::Write;
+
+// Shannon entropy (in nats) of the board normalized into a probability distribution
+pub fn shannon_entropy(board: &impl Board) -> f64 {
+    let total = board.total();
+    if total <= 0.0 {
+        return 0.0;
+    }
+
+    let (h, w) = board.dims();
+    let mut entropy = 0.0;
+
+    for i in 0..h {
+        for j in 0..w {
+            let p = board.get((i, j)) / total;
+            if p > 0.0 {
+                entropy -= p * p.ln();
+            }
+        }
+    }
+
+    entropy
+}
+
+// KL divergence of the normalized board from the uniform distribution, ln N - H
+pub fn kl_from_uniform(board: &impl Board) -> f64 {
+    let (h, w) = board.dims();
+    ((h * w) as f64).ln() - shannon_entropy(board)
+}
+
+// each cell's -p ln p term of the Shannon entropy
+pub fn entropy_density(board: &impl Board) -> Array2<f64> {
+    let total = board.total();
+
+    Array2::from_shape_fn(board.dims(), |cell| {
+        let p = board.get(cell) / total;
+        if p > 0.0 {
+            -p * p.ln()
+        } else {
+            0.0
+        }
+    })
+}
+
+// where entropy was produced (or destroyed) going from `previous` to `board`
+pub fn entropy_production_map(board: &impl Board, previous: Option<&impl Board>) -> Array2<f64> {
+    match previous {
+        Some(previous) => entropy_density(board) - entropy_density(previous),
+        None => Array2::zeros(board.dims()),
+    }
+}
+
+// per-step entropy change, and its average since the first update
+#[derive(Debug, Clone, Default)]
+pub struct EntropyProduction {
+    initial: Option<f64>,
+    last: Option<f64>,
+    steps: usize,
+}
+
+impl EntropyProduction {
+    // the first call only records the starting entropy
+    pub fn update(&mut self, entropy: f64) -> (f64, f64) {
+        let initial = *self.initial.get_or_insert(entropy);
+
+        let rate = match self.last {
+            Some(last) => {
+                self.steps += 1;
+                entropy - last
+            }
+            None => 0.0,
+        };
+        self.last = Some(entropy);
+
+        let average = if self.steps > 0 {
+            (entropy - initial) / self.steps as f64
+        } else {
+            0.0
+        };
+
+        (rate, average)
+    }
+}
+
+// fits the approach to equilibrium as KL(t) = A exp(-t / tau), i.e. a line
+// through ln KL, by exponentially weighted least squares so the fit follows the
+// recent part of the curve; the entropy approaches ln N at the same rate
+#[derive(Debug, Clone, Default)]
+pub struct EquilibriumFit {
+    samples: usize,
+    mean_t: f64,
+    mean_y: f64,
+    var_t: f64,
+    cov: f64,
+}
+
+impl EquilibriumFit {
+    // weight of the newest sample, so the fit spans the last ~200 steps
+    const ALPHA: f64 = 0.01;
+    // samples taken before any estimate is given
+    const MIN_SAMPLES: usize = 20;
+
+    pub fn update(&mut self, step: usize, kl_divergence: f64) {
+        if kl_divergence <= 0.0 {
+            return;
+        }
+        let (t, y) = (step as f64, kl_divergence.ln());
+
+        if self.samples == 0 {
+            (self.mean_t, self.mean_y) = (t, y);
+        } else {
+            let (dt, dy) = (t - self.mean_t, y - self.mean_y);
+            self.mean_t += Self::ALPHA * dt;
+            self.mean_y += Self::ALPHA * dy;
+            self.var_t = (1.0 - Self::ALPHA) * (self.var_t + Self::ALPHA * dt * dt);
+            self.cov = (1.0 - Self::ALPHA) * (self.cov + Self::ALPHA * dt * dy);
+        }
+        self.samples += 1;
+    }
+
+    // steps from `step` until the fitted KL drops below `threshold`; None while
+    // there is too little data or KL isn't falling, e.g. when a bath holds the
+    // board away from uniform
+    pub fn remaining(&self, step: usize, threshold: f64) -> Option<f64> {
+        if self.samples < Self::MIN_SAMPLES || self.var_t <= 0.0 {
+            return None;
+        }
+        let slope = self.cov / self.var_t;
+        if slope >= 0.0 {
+            return None;
+        }
+
+        let fitted = self.mean_y + slope * (step as f64 - self.mean_t);
+        Some(((threshold.ln() - fitted) / slope).max(0.0))
+    }
+}
+
+// Shannon entropy of the energy inside the k x k window around each cell (clipped
+// at the edges), using H = ln S - sum(e ln e) / S over summed-area tables
+pub fn local_entropy_map(board: &impl Board, k: usize) -> Array2<f64> {
+    let (h, w) = board.dims();
+    let r = k / 2;
+
+    // sums[[i, j]] holds the sum over cells above and to the left of (i, j)
+    let mut sums = Array2::<f64>::zeros((h + 1, w + 1));
+    let mut e_ln_e = Array2::<f64>::zeros((h + 1, w + 1));
+    for i in 0..h {
+        for j in 0..w {
+            let e = board.get((i, j));
+            let t = if e > 0.0 { e * e.ln() } else { 0.0 };
+            sums[[i + 1, j + 1]] = e + sums[[i, j + 1]] + sums[[i + 1, j]] - sums[[i, j]];
+            e_ln_e[[i + 1, j + 1]] = t + e_ln_e[[i, j + 1]] + e_ln_e[[i + 1, j]] - e_ln_e[[i, j]];
+        }
+    }
+
+    let window = |table: &Array2<f64>, (i0, j0): (usize, usize), (i1, j1): (usize, usize)| {
+        table[[i1, j1]] - table[[i0, j1]] - table[[i1, j0]] + table[[i0, j0]]
+    };
+
+    Array2::from_shape_fn((h, w), |(i, j)| {
+        let lo = (i.saturating_sub(r), j.saturating_sub(r));
+        let hi = ((i + r + 1).min(h), (j + r + 1).min(w));
+        let s = window(&sums, lo, hi);
+        if s <= 0.0 {
+            return 0.0;
+        }
+        // clamp away the rounding error of the table differences
+        (s.ln() - window(&e_ln_e, lo, hi) / s).max(0.0)
+    })
+}
+
+// mutual information (in nats) between the two halves of the board, treating the
+// energies of corresponding cells in either half as paired samples, estimated
+// from a bins x bins joint histogram
+pub fn halves_mutual_information(board: &impl Board, partition: Partition, bins: usize) -> f64 {
+    let (h, w) = board.dims();
+    let pairs: Vec<(f64, f64)> = match partition {
+        Partition::Vertical => iproduct!(0..h, 0..w / 2)
+            .map(|(i, j)| (board.get((i, j)), board.get((i, j + w / 2))))
+            .collect(),
+        Partition::Horizontal => iproduct!(0..h / 2, 0..w)
+            .map(|(i, j)| (board.get((i, j)), board.get((i + h / 2, j))))
+            .collect(),
+    };
+    if pairs.is_empty() || bins == 0 {
+        return 0.0;
+    }
+
+    let (lo, hi) = pairs
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &(a, b)| {
+            (lo.min(a).min(b), hi.max(a).max(b))
+        });
+    let width = (hi - lo) / bins as f64;
+    let bin = |e: f64| {
+        if width > 0.0 {
+            (((e - lo) / width) as usize).min(bins - 1)
+        } else {
+            0
+        }
+    };
+
+    let mut joint = Array2::<f64>::zeros((bins, bins));
+    for &(a, b) in &pairs {
+        joint[[bin(a), bin(b)]] += 1.0;
+    }
+    joint /= pairs.len() as f64;
+
+    let px = joint.sum_axis(Axis(1));
+    let py = joint.sum_axis(Axis(0));
+
+    joint
+        .indexed_iter()
+        .filter(|(_, &p)| p > 0.0)
+        .map(|((x, y), &p)| p * (p / (px[x] * py[y])).ln())
+        .sum()
+}
+
+// heat current through interior cross-sections of a board held between two bath
+// temperatures. the step conserves energy, so the current through a cut is what
+// the bath put into the near edge minus what the near side gained
+#[derive(Debug, Clone)]
+pub struct HeatCurrent {
+    axis: Axis,
+    // a cut at c separates lines 0..=c from the rest
+    cuts: Vec<usize>,
+    previous: Array1<f64>,
+    // conductivity per unit cross-section for a unit current
+    scale: f64,
+    warmup: usize,
+    total: Vec<f64>,
+    samples: usize,
+}
+
+impl HeatCurrent {
+    pub fn new(board: &Array2<f64>, config: &Config) -> Option<Self> {
+        let bath = config.bath?;
+        let (h, w) = board.dim();
+
+        let (axis, cold, length, cross_section) = match bath.region {
+            BathRegion::LeftRight { right_temperature } => (Axis(0), right_temperature, w, h),
+            BathRegion::TopBottom { bottom_temperature } => (Axis(1), bottom_temperature, h, w),
+            _ => return None,
+        };
+        let gradient = (bath.temperature - cold) / (length - 1) as f64;
+        let cuts: Vec<usize> = [length / 4, length / 2, 3 * length / 4]
+            .into_iter()
+            .filter(|&c| c > 0 && c < length - 1)
+            .collect();
+
+        Some(Self {
+            axis,
+            total: vec![0.0; cuts.len()],
+            cuts,
+            previous: board.sum_axis(axis),
+            scale: 1.0 / (cross_section as f64 * gradient),
+            warmup: config.current_warmup,
+            samples: 0,
+        })
+    }
+
+    // this step's current, averaged over the cuts
+    pub fn update(&mut self, step: usize, board: &Array2<f64>, report: &StepReport) -> f64 {
+        let lines = board.sum_axis(self.axis);
+        let mut mean = 0.0;
+
+        for (k, &cut) in self.cuts.iter().enumerate() {
+            let gained = lines.slice(ndarray::s![..=cut]).sum()
+                - self.previous.slice(ndarray::s![..=cut]).sum();
+            let current = report.held_edge_in[0] - gained;
+
+            if step > self.warmup {
+                self.total[k] += current;
+            }
+            mean += current / self.cuts.len() as f64;
+        }
+
+        if step > self.warmup {
+            self.samples += 1;
+        }
+        self.previous = lines;
+
+        mean
+    }
+
+    // mean current through each cut since the warmup ended
+    pub fn steady_currents(&self) -> Vec<f64> {
+        let n = self.samples.max(1) as f64;
+        self.total.iter().map(|t| t / n).collect()
+    }
+
+    pub fn conductivity(&self) -> Option<f64> {
+        if self.samples == 0 || self.cuts.is_empty() {
+            return None;
+        }
+        let currents = self.steady_currents();
+        let mean = currents.iter().sum::<f64>() / currents.len() as f64;
+        Some(mean * self.scale)
+    }
+}
+
+// where the board's energy came from and went, summed over a run; whatever the
+// flows don't account for is numerical drift
+#[derive(Debug, Clone, Default)]
+pub struct EnergyBudget {
+    initial: f64,
+    sources: f64,
+    // net bath exchange, split by the sign of each step's total
+    bath_in: f64,
+    bath_out: f64,
+    sinks: f64,
+    resets: f64,
+    // made up or written off by the clamp policy
+    clamped: f64,
+    // lost past an absorbing boundary
+    escaped: f64,
+    noise: f64,
+    // energy held off the board right now
+    trapped: f64,
+    latent: f64,
+    board: f64,
+}
+
+impl EnergyBudget {
+    pub fn new(board: &impl Board) -> Self {
+        let total = board.total();
+        Self {
+            initial: total,
+            board: total,
+            ..Self::default()
+        }
+    }
+
+    pub fn update(&mut self, board: &impl Board, report: &StepReport) {
+        self.sources += report.source_in;
+        if report.bath_in >= 0.0 {
+            self.bath_in += report.bath_in;
+        } else {
+            self.bath_out -= report.bath_in;
+        }
+        self.sinks += report.sink_out;
+        self.resets += report.reset_in;
+        self.clamped += report.clamped_in;
+        self.escaped += report.escaped;
+        self.noise += report.noise_in;
+        self.trapped = report.trapped;
+        self.latent = report.latent;
+        self.board = board.total();
+    }
+
+    // what the board should hold if every flow is accounted for
+    pub fn expected(&self) -> f64 {
+        self.initial + self.sources + self.bath_in - self.bath_out - self.sinks
+            + self.resets
+            + self.clamped
+            - self.escaped
+            + self.noise
+            - self.trapped
+            - self.latent
+    }
+
+    pub fn escaped(&self) -> f64 {
+        self.escaped
+    }
+
+    pub fn drift(&self) -> f64 {
+        self.board - self.expected()
+    }
+
+    // each flow, signed as it adds to the board, then what the board should
+    // and does hold
+    pub fn rows(&self) -> [(&'static str, f64); 13] {
+        [
+            ("initial board", self.initial),
+            ("+ sources", self.sources),
+            ("+ from bath", self.bath_in),
+            ("- to bath", -self.bath_out),
+            ("- to sinks", -self.sinks),
+            ("+ resets", self.resets),
+            ("+ clamping", self.clamped),
+            ("- escaped", -self.escaped),
+            ("+ thermal noise", self.noise),
+            ("- in traps", -self.trapped),
+            ("- latent heat", -self.latent),
+            ("= expected board", self.expected()),
+            ("final board", self.board),
+        ]
+    }
+
+    pub fn table(&self) -> String {
+        let mut s = String::new();
+        for (name, value) in self.rows() {
+            writeln!(s, "  {:<18}{:>+20.9}", name, value).unwrap();
+        }
+        let relative = self.drift() / self.expected().abs().max(f64::MIN_POSITIVE);
+        write!(
+            s,
+            "  {:<18}{:>+20.3e}  ({:+.3e} relative)",
+            "numerical drift",
+            self.drift(),
+            relative
+        )
+        .unwrap();
+        s
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StepMetrics {
+    pub step: usize,
+    pub entropy: f64,
+    pub production: f64,
+    pub mean_production: f64,
+    pub kl_divergence: f64,
+    pub mutual_information: f64,
+    // only with a left_right or top_bottom bath
+    pub heat_current: Option<f64>,
+    pub conductivity: Option<f64>,
+    // estimated steps until KL drops below kl_threshold; 0 once it has
+    pub steps_to_equilibrium: Option<f64>,
+    pub total_energy: f64,
+}
+
+impl StepMetrics {
+    pub const HEADER: &'static str = "step\tentropy\tproduction\tmean_production\tkl_divergence\tmutual_information\theat_current\tconductivity\tsteps_to_equilibrium\ttotal_energy";
+
+    pub fn row(&self) -> String {
+        let optional = |v: Option<f64>| v.map(|v| v.to_string()).unwrap_or_default();
+
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.step,
+            self.entropy,
+            self.production,
+            self.mean_production,
+            self.kl_divergence,
+            self.mutual_information,
+            optional(self.heat_current),
+            optional(self.conductivity),
+            optional(self.steps_to_equilibrium.map(f64::round)),
+            self.total_energy
+        )
+    }
+}
+
+// the per-step metrics of a whole run, plus what goes in its summary
+#[derive(Debug, Clone)]
+pub struct RunMetrics {
+    estimator: Estimator,
+    production: EntropyProduction,
+    kl_threshold: f64,
+    mi_partition: Partition,
+    mi_bins: usize,
+    heat_current: Option<HeatCurrent>,
+    equilibrium: EquilibriumFit,
+    budget: EnergyBudget,
+    initial: StepMetrics,
+    last: StepMetrics,
+    // first step at which the KL divergence from uniform fell below the threshold
+    kl_threshold_step: Option<usize>,
+}
+
+impl RunMetrics {
+    pub fn new(board: &Array2<f64>, config: &Config) -> Self {
+        let mut production = EntropyProduction::default();
+        let entropy = config.entropy_estimator.estimate(board);
+        production.update(entropy);
+
+        let kl_divergence = kl_from_uniform(board);
+        let kl_threshold_step = (kl_divergence < config.kl_threshold).then_some(0);
+
+        let initial = StepMetrics {
+            step: 0,
+            entropy,
+            production: 0.0,
+            mean_production: 0.0,
+            kl_divergence,
+            mutual_information: halves_mutual_information(
+                board,
+                config.mi_partition,
+                config.mi_bins,
+            ),
+            heat_current: None,
+            conductivity: None,
+            steps_to_equilibrium: kl_threshold_step.map(|_| 0.0),
+            total_energy: board.sum(),
+        };
+
+        Self {
+            estimator: config.entropy_estimator,
+            production,
+            kl_threshold: config.kl_threshold,
+            mi_partition: config.mi_partition,
+            mi_bins: config.mi_bins,
+            heat_current: HeatCurrent::new(board, config),
+            equilibrium: EquilibriumFit::default(),
+            budget: EnergyBudget::new(board),
+            initial,
+            last: initial,
+            kl_threshold_step,
+        }
+    }
+
+    pub fn update(&mut self, step: usize, board: &Array2<f64>, report: &StepReport) -> StepMetrics {
+        let entropy = self.estimator.estimate(board);
+        let (production, mean_production) = self.production.update(entropy);
+        let kl_divergence = kl_from_uniform(board);
+        let mutual_information = halves_mutual_information(board, self.mi_partition, self.mi_bins);
+        let heat_current = self
+            .heat_current
+            .as_mut()
+            .map(|current| current.update(step, board, report));
+        let conductivity = self
+            .heat_current
+            .as_ref()
+            .and_then(HeatCurrent::conductivity);
+
+        if self.kl_threshold_step.is_none() && kl_divergence < self.kl_threshold {
+            self.kl_threshold_step = Some(step);
+        }
+        self.equilibrium.update(step, kl_divergence);
+        self.budget.update(board, report);
+        let steps_to_equilibrium = match self.kl_threshold_step {
+            Some(_) => Some(0.0),
+            None => self.equilibrium.remaining(step, self.kl_threshold),
+        };
+
+        self.last = StepMetrics {
+            step,
+            entropy,
+            production,
+            mean_production,
+            kl_divergence,
+            mutual_information,
+            heat_current,
+            conductivity,
+            steps_to_equilibrium,
+            total_energy: board.sum(),
+        };
+        self.last
+    }
+
+    pub fn initial(&self) -> &StepMetrics {
+        &self.initial
+    }
+
+    pub fn last(&self) -> &StepMetrics {
+        &self.last
+    }
+
+    pub fn budget(&self) -> &EnergyBudget {
+        &self.budget
+    }
+
+    pub fn kl_threshold_step(&self) -> Option<usize> {
+        self.kl_threshold_step
+    }
+
+    pub fn summary(&self) -> String {
+        let mut s = String::new();
+        let (first, last) = (&self.initial, &self.last);
+
+        writeln!(s, "steps:            {}", last.step).unwrap();
+        writeln!(s, "entropy:          {} -> {}", first.entropy, last.entropy).unwrap();
+        writeln!(s, "mean production:  {}", last.mean_production).unwrap();
+        writeln!(
+            s,
+            "KL from uniform:  {} -> {}",
+            first.kl_divergence, last.kl_divergence
+        )
+        .unwrap();
+        writeln!(
+            s,
+            "halves MI:        {} -> {}",
+            first.mutual_information, last.mutual_information
+        )
+        .unwrap();
+        if let Some(current) = &self.heat_current {
+            writeln!(s, "heat current:     {:?}", current.steady_currents()).unwrap();
+            if let Some(k) = current.conductivity() {
+                writeln!(s, "conductivity:     {}", k).unwrap();
+            }
+        }
+        match self.kl_threshold_step {
+            Some(step) => write!(s, "KL < {} at step: {}", self.kl_threshold, step),
+            None => write!(s, "KL < {} at step: not reached", self.kl_threshold),
+        }
+        .unwrap();
+        write!(s, "\nenergy budget:\n{}", self.budget.table()).unwrap();
+
+        s
+    }
+}
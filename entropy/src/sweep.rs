@@ -0,0 +1,268 @@
+use crate::estimator::EntropyEstimator;
+use crate::{Config, ConfigError, Simulation};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// a grid of runs read from a file such as
+//   {"seed": 1, "steps": 5000, "repeats": 4,
+//    "parameters": {"heat": [0.25, 0.5, 1.0], "bath.coupling": [0.01, 0.1]}}
+// every combination of the values is run `repeats` times. points are numbered
+// the same way from the file alone, and each one's seed comes from its number,
+// so any partition of them gives the same runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sweep {
+    pub seed: u64,
+    pub steps: usize,
+    #[serde(default = "default_repeats")]
+    pub repeats: usize,
+    // config fields by name, with a dot into nested ones, in the order of
+    // their names; the last varies fastest
+    #[serde(default)]
+    pub parameters: Map<String, Value>,
+}
+
+fn default_repeats() -> usize {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    pub index: usize,
+    // which of the repeats of its combination this is
+    pub repeat: usize,
+    pub seed: u64,
+    pub parameters: Map<String, Value>,
+}
+
+// what a point's run ended with, one JSON line each
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SweepRow {
+    pub point: usize,
+    pub repeat: usize,
+    pub seed: u64,
+    pub parameters: Map<String, Value>,
+    pub steps: usize,
+    pub initial_entropy: f64,
+    pub final_entropy: f64,
+    pub final_energy: f64,
+}
+
+#[derive(Debug)]
+pub enum SweepError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+    NoValues(String),
+    ZeroRepeats,
+    UnknownField(String),
+    BadValue { field: String, error: String },
+    Config { point: usize, error: ConfigError },
+    BadTask { index: usize, tasks: usize },
+}
+
+impl fmt::Display for SweepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SweepError::Io(e) => write!(f, "{}", e),
+            SweepError::Parse(e) => write!(f, "{}", e),
+            SweepError::NoValues(field) => write!(f, "{} has no values to sweep", field),
+            SweepError::ZeroRepeats => write!(f, "repeats must be at least 1"),
+            SweepError::UnknownField(field) => write!(f, "no config field {:?}", field),
+            SweepError::BadValue { field, error } => write!(f, "{}: {}", field, error),
+            SweepError::Config { point, error } => write!(f, "point {}: {}", point, error),
+            SweepError::BadTask { index, tasks } => write!(
+                f,
+                "task index {} is out of range for {} tasks",
+                index, tasks
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SweepError {}
+
+impl From<io::Error> for SweepError {
+    fn from(e: io::Error) -> Self {
+        SweepError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SweepError {
+    fn from(e: serde_json::Error) -> Self {
+        SweepError::Parse(e)
+    }
+}
+
+impl Sweep {
+    pub fn read(path: &Path) -> Result<Self, SweepError> {
+        let sweep: Sweep = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        if sweep.repeats == 0 {
+            return Err(SweepError::ZeroRepeats);
+        }
+        if let Some((field, _)) = sweep
+            .parameters
+            .iter()
+            .find(|(_, values)| values.as_array().is_none_or(|v| v.is_empty()))
+        {
+            return Err(SweepError::NoValues(field.clone()));
+        }
+        Ok(sweep)
+    }
+
+    fn values(&self) -> impl Iterator<Item = (&String, &[Value])> {
+        self.parameters
+            .iter()
+            .map(|(field, values)| (field, values.as_array().map_or(&[][..], |v| &v[..])))
+    }
+
+    pub fn len(&self) -> usize {
+        self.values().map(|(_, v)| v.len()).product::<usize>() * self.repeats
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn point(&self, index: usize) -> Point {
+        let (repeat, mut rest) = (index % self.repeats, index / self.repeats);
+        let mut parameters: Vec<_> = self.values().collect();
+        // counting from the last, which varies fastest
+        for (_, values) in parameters.iter_mut().rev() {
+            let i = rest % values.len();
+            rest /= values.len();
+            *values = &values[i..=i];
+        }
+
+        Point {
+            index,
+            repeat,
+            seed: derive_seed(self.seed, index),
+            parameters: parameters
+                .into_iter()
+                .map(|(field, value)| (field.clone(), value[0].clone()))
+                .collect(),
+        }
+    }
+
+    // the points task `index` of `tasks` runs: every tasks-th one from index,
+    // so the tasks share the grid evenly whatever its shape
+    pub fn task(&self, index: usize, tasks: usize) -> Result<Vec<Point>, SweepError> {
+        if index >= tasks {
+            return Err(SweepError::BadTask { index, tasks });
+        }
+        Ok((index..self.len())
+            .step_by(tasks)
+            .map(|i| self.point(i))
+            .collect())
+    }
+
+    // `base` with the point's parameters and seed
+    pub fn config(&self, base: &Config, point: &Point) -> Result<Config, SweepError> {
+        let mut json = serde_json::to_value(base).expect("a config always serializes");
+        for (field, value) in &point.parameters {
+            set_field(&mut json, field, value.clone())?;
+        }
+        let mut config: Config =
+            serde_json::from_value(json).map_err(|e| SweepError::BadValue {
+                field: point
+                    .parameters
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                error: e.to_string(),
+            })?;
+        config.seed = Some(point.seed);
+        config.validate().map_err(|error| SweepError::Config {
+            point: point.index,
+            error,
+        })?;
+        Ok(config)
+    }
+
+    // runs `points` in parallel, in order
+    pub fn run(&self, base: &Config, points: &[Point]) -> Result<Vec<SweepRow>, SweepError> {
+        points
+            .par_iter()
+            .map(|point| {
+                let config = self.config(base, point)?;
+                let estimator = config.entropy_estimator;
+                let mut sim = Simulation::new(config).map_err(|error| SweepError::Config {
+                    point: point.index,
+                    error,
+                })?;
+                let initial_entropy = estimator.estimate(sim.board());
+                for _ in 0..self.steps {
+                    sim.step();
+                }
+                Ok(SweepRow {
+                    point: point.index,
+                    repeat: point.repeat,
+                    seed: point.seed,
+                    parameters: point.parameters.clone(),
+                    steps: self.steps,
+                    initial_entropy,
+                    final_entropy: estimator.estimate(sim.board()),
+                    final_energy: sim.board().sum(),
+                })
+            })
+            .collect()
+    }
+}
+
+// where task `index` writes its rows under `dir`
+pub fn task_path(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("task-{}.jsonl", index))
+}
+
+pub fn write_rows(path: &Path, rows: &[SweepRow]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut out = BufWriter::new(File::create(path)?);
+    for row in rows {
+        serde_json::to_writer(&mut out, row)?;
+        writeln!(out)?;
+    }
+    out.flush()
+}
+
+// splitmix64 of the sweep's seed and a point's number, so neighboring points
+// get unrelated seeds
+pub fn derive_seed(seed: u64, index: usize) -> u64 {
+    let mut z = seed.wrapping_add((index as u64 + 1).wrapping_mul(0x9e3779b97f4a7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+// sets "a.b.c" in `json`, making the objects on the way if they're null
+fn set_field(json: &mut Value, field: &str, value: Value) -> Result<(), SweepError> {
+    let mut parts = field.split('.').peekable();
+    let mut at = json;
+    let mut top = true;
+    while let Some(part) = parts.next() {
+        if at.is_null() {
+            *at = Value::Object(Map::new());
+        }
+        let object = at.as_object_mut().ok_or_else(|| SweepError::BadValue {
+            field: field.to_string(),
+            error: "isn't inside an object".to_string(),
+        })?;
+        // a misspelled top-level field would be ignored, so it's an error
+        if top && !object.contains_key(part) {
+            return Err(SweepError::UnknownField(field.to_string()));
+        }
+        top = false;
+        if parts.peek().is_none() {
+            object.insert(part.to_string(), value);
+            return Ok(());
+        }
+        at = object.entry(part).or_insert(Value::Null);
+    }
+    Ok(())
+}
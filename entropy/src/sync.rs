@@ -0,0 +1,156 @@
+use crate::session::Key;
+use crate::Config;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::time::Duration;
+
+// messages a follower may fall behind by before it's dropped
+const FOLLOWER_BACKLOG: usize = 4096;
+// how long a write to a follower may stall before it's dropped
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// what a leader tells its followers, one JSON object per line. a follower runs
+// the same seeded simulation and applies each change at the step it was made at,
+// so it never needs the board itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Message {
+    Hello { seed: u64, config: Config },
+    Key { step: usize, key: Key },
+    // parameters changed mid-run, e.g. by the randomizer
+    Config { step: usize, config: Config },
+    // how far the leader has got; followers don't step past it
+    Step { step: usize },
+}
+
+impl Message {
+    pub fn step(&self) -> usize {
+        match *self {
+            Message::Hello { .. } => 0,
+            Message::Key { step, .. } | Message::Config { step, .. } | Message::Step { step } => {
+                step
+            }
+        }
+    }
+}
+
+pub struct Leader {
+    listener: TcpListener,
+    // each follower is written to on a thread of its own, so a slow one never
+    // holds up the window
+    followers: Vec<SyncSender<Message>>,
+    // everything but the latest Step, so followers can join late and catch up
+    log: Vec<Message>,
+    step: usize,
+}
+
+impl Leader {
+    pub fn bind(addr: impl ToSocketAddrs, seed: u64, config: &Config) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            followers: Vec::new(),
+            log: vec![Message::Hello {
+                seed,
+                config: config.clone(),
+            }],
+            step: 0,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn broadcast(&mut self, message: Message) {
+        self.accept();
+        match &message {
+            Message::Step { step } => self.step = *step,
+            message => self.log.push(message.clone()),
+        }
+        // followers that went away, or fell too far behind, are dropped
+        self.followers
+            .retain(|follower| follower.try_send(message.clone()).is_ok());
+    }
+
+    pub fn followers(&self) -> usize {
+        self.followers.len()
+    }
+
+    // new followers get the log and the latest step before anything else
+    fn accept(&mut self) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            let ready = stream
+                .set_nonblocking(false)
+                .and_then(|_| stream.set_write_timeout(Some(WRITE_TIMEOUT)));
+            if ready.is_err() {
+                continue;
+            }
+            let (tx, rx) = mpsc::sync_channel(FOLLOWER_BACKLOG);
+            let mut catch_up = self.log.clone();
+            catch_up.push(Message::Step { step: self.step });
+            std::thread::spawn(move || {
+                let mut follower = BufWriter::new(stream);
+                // a write that fails or times out ends the thread, and with it
+                // the channel, which broadcast then drops
+                let _ = catch_up
+                    .into_iter()
+                    .chain(rx)
+                    .try_for_each(|message| write_message(&mut follower, &message));
+            });
+            self.followers.push(tx);
+        }
+    }
+}
+
+fn write_message(out: &mut BufWriter<TcpStream>, message: &Message) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, message)?;
+    out.write_all(b"\n")?;
+    out.flush()
+}
+
+pub struct Follower {
+    messages: Receiver<Message>,
+}
+
+impl Follower {
+    // connects and waits for the leader's seed and config
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<(u64, Config, Follower)> {
+        let mut lines = BufReader::new(TcpStream::connect(addr)?).lines();
+        let hello = lines
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "leader hung up"))??;
+        let (seed, config) = match serde_json::from_str(&hello)? {
+            Message::Hello { seed, config } => (seed, config),
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("expected hello, got {:?}", other),
+                ))
+            }
+        };
+
+        // read on a thread so the render loop never waits on the network
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in lines.map_while(Result::ok) {
+                let Ok(message) = serde_json::from_str(&line) else {
+                    break;
+                };
+                if tx.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok((seed, config, Follower { messages: rx }))
+    }
+
+    // whatever has arrived since the last call
+    pub fn poll(&self) -> Vec<Message> {
+        self.messages.try_iter().collect()
+    }
+}
@@ -0,0 +1,90 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+// how long steps or frames took over a run, kept as a histogram with
+// PER_OCTAVE buckets to each doubling so a long run doesn't grow it; smooth
+// playback depends on the slow tail more than on the mean
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    // microseconds, bucket k holding durations below 2^((k + 1) / PER_OCTAVE)
+    buckets: Vec<u64>,
+    count: u64,
+    max: Duration,
+    previous: Option<Duration>,
+    // the largest change from one duration to the next
+    jitter: Duration,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Histogram {
+    const PER_OCTAVE: f64 = 8.0;
+    // up to 2^32 us, a little over an hour
+    const BUCKETS: usize = 32 * 8;
+
+    pub fn new() -> Self {
+        Self {
+            buckets: vec![0; Self::BUCKETS],
+            count: 0,
+            max: Duration::ZERO,
+            previous: None,
+            jitter: Duration::ZERO,
+        }
+    }
+
+    pub fn record(&mut self, duration: Duration) {
+        let us = duration.as_secs_f64() * 1e6;
+        let k = (us.max(1.0).log2() * Self::PER_OCTAVE) as usize;
+        self.buckets[k.min(Self::BUCKETS - 1)] += 1;
+        self.count += 1;
+        self.max = self.max.max(duration);
+        if let Some(previous) = self.previous {
+            self.jitter = self.jitter.max(duration.abs_diff(previous));
+        }
+        self.previous = Some(duration);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    pub fn jitter(&self) -> Duration {
+        self.jitter
+    }
+
+    // the upper edge of the bucket holding the q quantile, at most the longest
+    // duration seen; zero before anything is recorded
+    pub fn quantile(&self, q: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (k, &n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                let us = ((k + 1) as f64 / Self::PER_OCTAVE).exp2();
+                return Duration::from_secs_f64(us / 1e6).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    // one line, e.g. "p50 1.2ms  p95 3.4ms  p99 5.6ms  max 9.8ms  jitter 7.1ms"
+    pub fn report(&self) -> String {
+        let mut s = String::new();
+        for (name, q) in [("p50", 0.5), ("p95", 0.95), ("p99", 0.99)] {
+            write!(s, "{} {:.1?}  ", name, self.quantile(q)).unwrap();
+        }
+        write!(s, "max {:.1?}  jitter {:.1?}", self.max, self.jitter).unwrap();
+        s
+    }
+}
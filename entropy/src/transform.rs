@@ -0,0 +1,119 @@
+use image::imageops::{self, FilterType};
+use ndarray::{s, Array2, ArrayView2};
+use serde::{Deserialize, Serialize};
+
+// applied to fields read from files: mirror first, then rotate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transform {
+    // counterclockwise, in degrees; one of 0, 90, 180 or 270
+    #[serde(default)]
+    pub rotate: u32,
+    #[serde(default)]
+    pub mirror: Option<Mirror>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mirror {
+    // left and right swap
+    Horizontal,
+    // top and bottom swap
+    Vertical,
+}
+
+impl Transform {
+    pub fn rotation(quarter_turns: u32) -> Self {
+        Self {
+            rotate: 90 * (quarter_turns % 4),
+            mirror: None,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        matches!(self.rotate, 0 | 90 | 180 | 270)
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.rotate == 0 && self.mirror.is_none()
+    }
+
+    // the shape of a (h, w) field after the transform
+    pub fn dims(&self, (h, w): (usize, usize)) -> (usize, usize) {
+        match self.rotate {
+            90 | 270 => (w, h),
+            _ => (h, w),
+        }
+    }
+
+    pub fn apply(&self, field: ArrayView2<f64>) -> Array2<f64> {
+        let mirrored = match self.mirror {
+            None => field,
+            Some(Mirror::Horizontal) => field.slice_move(s![.., ..;-1]),
+            Some(Mirror::Vertical) => field.slice_move(s![..;-1, ..]),
+        };
+
+        match self.rotate {
+            90 => mirrored.reversed_axes().slice_move(s![..;-1, ..]),
+            180 => mirrored.slice_move(s![..;-1, ..;-1]),
+            270 => mirrored.reversed_axes().slice_move(s![.., ..;-1]),
+            _ => mirrored,
+        }
+        .to_owned()
+    }
+
+    // the cell of the original (h, w) field that ends up at `cell`
+    pub fn source(&self, (i, j): (usize, usize), (h, w): (usize, usize)) -> (usize, usize) {
+        let (i, j) = match self.rotate {
+            90 => (j, w - 1 - i),
+            180 => (h - 1 - i, w - 1 - j),
+            270 => (h - 1 - j, i),
+            _ => (i, j),
+        };
+
+        match self.mirror {
+            None => (i, j),
+            Some(Mirror::Horizontal) => (i, w - 1 - j),
+            Some(Mirror::Vertical) => (h - 1 - i, j),
+        }
+    }
+}
+
+// reads a 2d .npy field and transforms it
+pub fn load_field(path: &str, transform: &Transform) -> Result<Array2<f64>, String> {
+    let field: Array2<f64> = ndarray_npy::read_npy(path).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(transform.apply(field.view()))
+}
+
+// reads a PNG or GIF, resized to come out `dims` once transformed, as a field
+// holding `energy` in all, shared out by luminance; the image's top row ends up
+// at the top of the window
+pub fn load_image(
+    path: &str,
+    dims: (usize, usize),
+    energy: f64,
+    transform: &Transform,
+) -> Result<Array2<f64>, String> {
+    let field = load_luminance(path, dims, FilterType::Triangle, transform)?;
+    let total = field.sum();
+    if total <= 0.0 {
+        return Err(format!("{}: the image is black", path));
+    }
+    Ok(field * (energy / total))
+}
+
+// a PNG or GIF's luminance, from 0 to 255, resized with `filter` to come out
+// `dims` once transformed
+pub fn load_luminance(
+    path: &str,
+    dims: (usize, usize),
+    filter: FilterType,
+    transform: &Transform,
+) -> Result<Array2<f64>, String> {
+    let image = image::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    let (h, w) = transform.dims(dims);
+    let luma = imageops::resize(&image.to_luma8(), w as u32, h as u32, filter);
+    let field = Array2::from_shape_fn((h, w), |(i, j)| {
+        luma.get_pixel(j as u32, (h - 1 - i) as u32)[0] as f64
+    });
+    Ok(transform.apply(field.view()))
+}
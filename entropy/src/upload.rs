@@ -0,0 +1,167 @@
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+// the manifest listing every file uploaded with its SHA-256, as sha256sum
+// writes them, so `sha256sum -c` checks a download
+pub const CHECKSUMS: &str = "SHA256SUMS";
+
+// where a run's files go: s3://bucket/prefix and gs://bucket/prefix through the
+// aws and gcloud command-line tools, already set up with credentials, or
+// file:///dir for a mounted bucket or a test
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    S3(String),
+    Gcs(String),
+    File(PathBuf),
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = s.trim_end_matches('/');
+        let (scheme, rest) = s
+            .split_once("://")
+            .ok_or_else(|| format!("expected s3://, gs:// or file://, got {:?}", s))?;
+        if rest.is_empty() {
+            return Err(format!("{:?} has no bucket or directory", s));
+        }
+        match scheme {
+            "s3" => Ok(Target::S3(s.to_string())),
+            "gs" => Ok(Target::Gcs(s.to_string())),
+            "file" => Ok(Target::File(PathBuf::from(rest))),
+            _ => Err(format!("unknown object store {:?}", scheme)),
+        }
+    }
+}
+
+impl Target {
+    // the URI of `key` under the target
+    pub fn uri(&self, key: &str) -> String {
+        match self {
+            Target::S3(base) | Target::Gcs(base) => format!("{}/{}", base, key),
+            Target::File(dir) => format!("file://{}", dir.join(key).display()),
+        }
+    }
+
+    fn put(&self, file: &Path, key: &str) -> io::Result<()> {
+        let mut command = match self {
+            Target::File(dir) => {
+                let to = dir.join(key);
+                if let Some(parent) = to.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                return fs::copy(file, to).map(|_| ());
+            }
+            Target::S3(_) => {
+                let mut command = Command::new("aws");
+                command.args(["s3", "cp", "--only-show-errors"]);
+                command
+            }
+            Target::Gcs(_) => {
+                let mut command = Command::new("gcloud");
+                command.args(["storage", "cp", "--quiet"]);
+                command
+            }
+        };
+        let status = command.arg(file).arg(self.uri(key)).status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!(
+                "copying {} to {} failed with {}",
+                file.display(),
+                self.uri(key),
+                status
+            )))
+        }
+    }
+}
+
+// an upload of a run's files once it's over, each retried on failure with the
+// wait doubling from `backoff`
+#[derive(Debug, Clone)]
+pub struct Upload {
+    pub target: Target,
+    pub retries: u32,
+    pub backoff: Duration,
+    // file names to upload, of those the run wrote; all of them if empty
+    pub only: Vec<String>,
+}
+
+impl Upload {
+    // puts each of `files` at <target>/<prefix>/<file name>, then the checksum
+    // manifest, so an upload with a manifest is a complete one; returns the
+    // manifest's URI. a run with nothing to upload is an error, not an empty
+    // manifest
+    pub fn run(&self, prefix: &str, files: &[PathBuf]) -> io::Result<String> {
+        let files: Vec<(String, &PathBuf)> = files
+            .iter()
+            .filter(|file| file.is_file())
+            .filter_map(|file| Some((file.file_name()?.to_str()?.to_string(), file)))
+            .filter(|(name, _)| self.only.is_empty() || self.only.contains(name))
+            .collect();
+        if files.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("no files to upload for {}", prefix),
+            ));
+        }
+
+        let mut manifest = String::new();
+        for (name, file) in &files {
+            let key = format!("{}/{}", prefix, name);
+            retry(self.retries, self.backoff, || self.target.put(file, &key))?;
+            writeln!(manifest, "{}  {}", hex(&sha256(&fs::read(file)?)), name).unwrap();
+        }
+
+        let staged = std::env::temp_dir().join(format!(
+            "entropy-upload-{}-{}",
+            std::process::id(),
+            CHECKSUMS
+        ));
+        fs::write(&staged, manifest)?;
+        let key = format!("{}/{}", prefix, CHECKSUMS);
+        let put = retry(self.retries, self.backoff, || {
+            self.target.put(&staged, &key)
+        });
+        fs::remove_file(&staged)?;
+        put.map(|()| self.target.uri(&key))
+    }
+}
+
+// runs `f` until it succeeds, at most `retries` more times, waiting `backoff`
+// and then twice as long each time; a tool that isn't installed isn't retried
+pub fn retry<T>(
+    retries: u32,
+    backoff: Duration,
+    mut f: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    let mut wait = backoff;
+    for _ in 0..retries {
+        match f() {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                eprintln!("{}; retrying in {:?}", e, wait);
+                thread::sleep(wait);
+                wait *= 2;
+            }
+            done => return done,
+        }
+    }
+    f()
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn sha256(bytes: &[u8]) -> [u8; 32] {
+    Sha256::digest(bytes).into()
+}
@@ -0,0 +1,233 @@
+use crate::images;
+use entropy::config::VideoSettings;
+use entropy::palette::{ColorScale, Palette, Scale};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::imageops::{self, FilterType};
+use image::{Delay, DynamicImage, Frame, RgbImage};
+use ndarray::Array2;
+use std::fs::File;
+use std::io::{self, BufWriter, Stdout, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+// the gif crate's default trade of color quality for time, from 1 to 30
+const GIF_SPEED: i32 = 10;
+
+enum Encoder {
+    Gif(Box<GifEncoder<BufWriter<File>>>),
+    // raw RGB frames piped to an ffmpeg that writes whatever the extension says
+    Ffmpeg { child: Child, stdin: ChildStdin },
+}
+
+impl Encoder {
+    fn start(path: &Path, (w, h): (u32, u32), fps: u32) -> io::Result<Self> {
+        let gif = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("gif"));
+        if gif {
+            // the quantizer's slowest setting takes seconds over a full window
+            let out = BufWriter::new(File::create(path)?);
+            let mut encoder = GifEncoder::new_with_speed(out, GIF_SPEED);
+            encoder
+                .set_repeat(Repeat::Infinite)
+                .map_err(io::Error::other)?;
+            return Ok(Encoder::Gif(Box::new(encoder)));
+        }
+
+        let mut child = Command::new("ffmpeg")
+            .args([
+                "-y",
+                "-loglevel",
+                "error",
+                "-f",
+                "rawvideo",
+                "-pix_fmt",
+                "rgb24",
+            ])
+            .args([
+                "-s",
+                &format!("{}x{}", w, h),
+                "-r",
+                &fps.to_string(),
+                "-i",
+                "-",
+            ])
+            // most players want yuv420p, which needs even sides
+            .args([
+                "-vf",
+                "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+                "-pix_fmt",
+                "yuv420p",
+            ])
+            .arg(path)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("couldn't start ffmpeg: {}", e)))?;
+        let stdin = child.stdin.take().expect("ffmpeg's stdin is piped");
+        Ok(Encoder::Ffmpeg { child, stdin })
+    }
+
+    fn push(&mut self, frame: &RgbImage, fps: u32) -> io::Result<()> {
+        match self {
+            Encoder::Gif(encoder) => {
+                let rgba = DynamicImage::from(frame.clone()).into_rgba8();
+                let delay = Delay::from_numer_denom_ms(1000, fps);
+                encoder
+                    .encode_frame(Frame::from_parts(rgba, 0, 0, delay))
+                    .map_err(io::Error::other)
+            }
+            Encoder::Ffmpeg { stdin, .. } => stdin.write_all(frame.as_raw()),
+        }
+    }
+
+    // the GIF's trailer is written as its encoder drops; ffmpeg finishes the
+    // file once its input closes
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Encoder::Gif(encoder) => {
+                drop(encoder);
+                Ok(())
+            }
+            Encoder::Ffmpeg { mut child, stdin } => {
+                drop(stdin);
+                let status = child.wait()?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(io::Error::other(format!("ffmpeg exited with {}", status)))
+                }
+            }
+        }
+    }
+}
+
+// the window encoded straight to an animated GIF or, through ffmpeg, a video
+pub struct Recorder {
+    settings: VideoSettings,
+    encoder: Option<Encoder>,
+    // frames seen and written since recording started
+    drawn: usize,
+    written: usize,
+}
+
+impl Recorder {
+    pub fn new(settings: VideoSettings) -> Self {
+        Self {
+            settings,
+            encoder: None,
+            drawn: 0,
+            written: 0,
+        }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        PathBuf::from(&self.settings.path)
+    }
+
+    pub fn recording(&self) -> bool {
+        self.encoder.is_some()
+    }
+
+    // starts recording frames of `dims`, and returns whether it now is
+    pub fn toggle(&mut self, dims: (u32, u32)) -> io::Result<bool> {
+        if self.recording() {
+            self.stop()?;
+            return Ok(false);
+        }
+        self.encoder = Some(Encoder::start(&self.path(), dims, self.settings.fps)?);
+        self.drawn = 0;
+        self.written = 0;
+        Ok(true)
+    }
+
+    pub fn stop(&mut self) -> io::Result<()> {
+        match self.encoder.take() {
+            Some(encoder) => encoder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    // adds every `every`-th frame, stopping once the video is `seconds` long;
+    // returns whether it's still recording
+    pub fn observe(&mut self, frame: &RgbImage) -> io::Result<bool> {
+        let Some(encoder) = &mut self.encoder else {
+            return Ok(false);
+        };
+        self.drawn += 1;
+        if (self.drawn - 1).is_multiple_of(self.settings.every) {
+            encoder.push(frame, self.settings.fps)?;
+            self.written += 1;
+        }
+
+        let limit = self
+            .settings
+            .seconds
+            .map(|s| (s * self.settings.fps as f64).ceil() as usize);
+        if limit.is_some_and(|limit| self.written >= limit) {
+            self.stop()?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let path = self.path();
+        if self.recording() {
+            match self.stop() {
+                Ok(()) => println!("wrote video to {}", path.display()),
+                Err(e) => eprintln!("Couldn't finish {}: {}", path.display(), e),
+            }
+        }
+    }
+}
+
+// boards written to stdout as bare RGB24 frames, top row first, for an ffmpeg
+// reading `-f rawvideo -pix_fmt rgb24` from a pipe
+pub struct FramePipe {
+    out: BufWriter<Stdout>,
+    palette: Option<Palette>,
+    scale: Scale,
+    color_scale: ColorScale,
+    // each cell is a square this many pixels a side
+    factor: u32,
+}
+
+impl FramePipe {
+    pub fn new(
+        palette: Option<Palette>,
+        scale: Scale,
+        color_scale: ColorScale,
+        factor: u32,
+    ) -> Self {
+        Self {
+            out: BufWriter::new(io::stdout()),
+            palette,
+            scale,
+            color_scale,
+            factor: factor.max(1),
+        }
+    }
+
+    // the frames' width and height, as ffmpeg's -s wants them
+    pub fn size(&self, (h, w): (usize, usize)) -> (u32, u32) {
+        (w as u32 * self.factor, h as u32 * self.factor)
+    }
+
+    pub fn push(&mut self, board: &Array2<f64>) -> io::Result<()> {
+        let max_energy = self.scale.update(board);
+        let mut frame = images::render(board, self.palette.as_ref(), max_energy, self.color_scale);
+        if self.factor > 1 {
+            let (w, h) = frame.dimensions();
+            frame = imageops::resize(
+                &frame,
+                w * self.factor,
+                h * self.factor,
+                FilterType::Nearest,
+            );
+        }
+        self.out.write_all(frame.as_raw())?;
+        self.out.flush()
+    }
+}
@@ -0,0 +1,94 @@
+use entropy::recording::{RecordingIndex, INDEX};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+// the whole app, built into the binary so there is nothing to install
+const PAGE: &str = include_str!("viewer.html");
+
+// serves the page and the files of one recording made by `entropy export`
+pub fn run(recording: &Path, port: u16) {
+    let index = RecordingIndex::read(recording).unwrap_or_else(|e| {
+        eprintln!("Couldn't read recording {}: {}", recording.display(), e);
+        std::process::exit(1);
+    });
+    let listener = TcpListener::bind(("127.0.0.1", port)).expect("Couldn't bind the viewer port");
+    println!(
+        "serving {} ({} keyframes) on http://{}",
+        recording.display(),
+        index.frames.len(),
+        listener
+            .local_addr()
+            .expect("Couldn't read the viewer address")
+    );
+
+    let root = Arc::new(recording.to_path_buf());
+    for stream in listener.incoming().flatten() {
+        let root = Arc::clone(&root);
+        std::thread::spawn(move || {
+            // a client hanging up halfway is its own business
+            let _ = serve(stream, &root);
+        });
+    }
+}
+
+fn serve(mut stream: TcpStream, root: &Path) -> std::io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // the headers have to be read, or closing the socket may reset it mid-reply
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    let (method, target) = (parts.next(), parts.next().unwrap_or("/"));
+    let path = target.split('?').next().unwrap_or("/");
+
+    let (status, kind, body) = match (method, path) {
+        (Some("GET"), "/") => (
+            "200 OK",
+            "text/html; charset=utf-8",
+            PAGE.as_bytes().to_vec(),
+        ),
+        (Some("GET"), _) => match recording_file(root, path).map(std::fs::read) {
+            Some(Ok(bytes)) => ("200 OK", content_type(path), bytes),
+            _ => ("404 Not Found", "text/plain", b"not found".to_vec()),
+        },
+        _ => ("405 Method Not Allowed", "text/plain", b"only GET".to_vec()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        kind,
+        body.len()
+    )?;
+    stream.write_all(&body)?;
+    stream.flush()
+}
+
+// only the index and keyframe files, and nothing outside the recording
+fn recording_file(root: &Path, path: &str) -> Option<PathBuf> {
+    let relative = Path::new(path.trim_start_matches('/'));
+    let plain = relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)));
+    let known = relative == Path::new(INDEX)
+        || matches!(
+            relative.extension().and_then(|e| e.to_str()),
+            Some("f32" | "npy")
+        );
+    (plain && known).then(|| root.join(relative))
+}
+
+fn content_type(path: &str) -> &'static str {
+    if path.ends_with(".json") {
+        "application/json"
+    } else {
+        "application/octet-stream"
+    }
+}
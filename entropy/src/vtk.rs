@@ -0,0 +1,91 @@
+use ndarray::Array2;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+pub const SERIES: &str = "series.pvd";
+
+// a board as VTK ImageData, one cell per board cell so ParaView's cell data
+// shows each at its true size; x runs along the columns and y up the rows, as
+// in the window
+pub fn write_vti(path: &Path, board: &Array2<f64>) -> io::Result<()> {
+    let (h, w) = board.dim();
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "<?xml version=\"1.0\"?>")?;
+    writeln!(
+        out,
+        "<VTKFile type=\"ImageData\" version=\"0.1\" byte_order=\"LittleEndian\">"
+    )?;
+    writeln!(
+        out,
+        "<ImageData WholeExtent=\"0 {w} 0 {h} 0 0\" Origin=\"0 0 0\" Spacing=\"1 1 1\">",
+        w = w,
+        h = h
+    )?;
+    writeln!(out, "<Piece Extent=\"0 {} 0 {} 0 0\">", w, h)?;
+    writeln!(out, "<CellData Scalars=\"energy\">")?;
+    writeln!(
+        out,
+        "<DataArray type=\"Float64\" Name=\"energy\" format=\"ascii\">"
+    )?;
+    // rows in order, so x varies fastest as VTK expects
+    for row in board.rows() {
+        let line: Vec<String> = row.iter().map(f64::to_string).collect();
+        writeln!(out, "{}", line.join(" "))?;
+    }
+    writeln!(
+        out,
+        "</DataArray>\n</CellData>\n</Piece>\n</ImageData>\n</VTKFile>"
+    )?;
+    out.flush()
+}
+
+// numbered .vti files in a directory, with the .pvd collection that lets
+// ParaView open them as one time series, the step as the time
+pub struct VtkSeries {
+    dir: PathBuf,
+    steps: Vec<usize>,
+}
+
+impl VtkSeries {
+    pub fn create(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            steps: Vec::new(),
+        })
+    }
+
+    pub fn push(&mut self, step: usize, board: &Array2<f64>) -> io::Result<()> {
+        write_vti(&self.dir.join(file_name(step)), board)?;
+        self.steps.push(step);
+        Ok(())
+    }
+
+    // writes the collection, returning where it went
+    pub fn finish(self) -> io::Result<PathBuf> {
+        let path = self.dir.join(SERIES);
+        let mut out = BufWriter::new(File::create(&path)?);
+        writeln!(out, "<?xml version=\"1.0\"?>")?;
+        writeln!(
+            out,
+            "<VTKFile type=\"Collection\" version=\"0.1\" byte_order=\"LittleEndian\">"
+        )?;
+        writeln!(out, "<Collection>")?;
+        for step in &self.steps {
+            writeln!(
+                out,
+                "<DataSet timestep=\"{}\" group=\"\" part=\"0\" file=\"{}\"/>",
+                step,
+                file_name(*step)
+            )?;
+        }
+        writeln!(out, "</Collection>\n</VTKFile>")?;
+        out.flush()?;
+        Ok(path)
+    }
+}
+
+fn file_name(step: usize) -> String {
+    format!("{:08}.vti", step)
+}
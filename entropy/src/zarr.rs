@@ -0,0 +1,230 @@
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use ndarray::Array2;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub const ENERGY: &str = "energy";
+pub const STEP: &str = "step";
+
+// how a Zarr store is chunked and compressed, parsed from e.g.
+// "chunks=16x128x128 level=5"; level=none stores the chunks raw
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZarrSpec {
+    // frames, rows and columns a chunk; cut down to the board when bigger
+    pub chunks: (usize, usize, usize),
+    // zlib's, from 0 to 9
+    pub level: Option<u32>,
+}
+
+impl Default for ZarrSpec {
+    fn default() -> Self {
+        Self {
+            chunks: (16, 256, 256),
+            level: Some(5),
+        }
+    }
+}
+
+impl std::str::FromStr for ZarrSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut spec = ZarrSpec::default();
+        for part in s.split([' ', ',']).filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, got {:?}", part))?;
+            let bad = |e: &dyn std::fmt::Display| format!("{:?}: {}", part, e);
+            match key {
+                "chunks" => {
+                    let sides = value
+                        .split(['x', 'X'])
+                        .map(|n| n.parse::<usize>().map_err(|e| bad(&e)))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    spec.chunks = match sides[..] {
+                        [t, h, w] => (t, h, w),
+                        _ => return Err(format!("{:?}: expected FRAMESxROWSxCOLS", part)),
+                    };
+                }
+                "level" if value == "none" => spec.level = None,
+                "level" => spec.level = Some(value.parse().map_err(|e| bad(&e))?),
+                _ => return Err(format!("unknown zarr setting {:?}", key)),
+            }
+        }
+        let (t, h, w) = spec.chunks;
+        if t == 0 || h == 0 || w == 0 {
+            return Err("chunks must be at least 1 a side".to_string());
+        }
+        if spec.level.is_some_and(|level| level > 9) {
+            return Err("level must be from 0 to 9, or none".to_string());
+        }
+        Ok(spec)
+    }
+}
+
+// boards written as they come into a Zarr (v2) store on disk, one float64
+// array of (step, y, x) and the steps beside it, with the dimension names and
+// consolidated metadata xarray's open_zarr looks for
+pub struct ZarrWriter {
+    root: PathBuf,
+    dims: (usize, usize),
+    chunks: (usize, usize, usize),
+    level: Option<u32>,
+    attrs: Value,
+    // frames not yet filling a chunk along time
+    pending: Vec<Array2<f64>>,
+    steps: Vec<usize>,
+}
+
+impl ZarrWriter {
+    // `attrs` go on the root group, e.g. the config and seed
+    pub fn create(
+        root: &Path,
+        dims: (usize, usize),
+        spec: ZarrSpec,
+        attrs: Value,
+    ) -> io::Result<Self> {
+        let (t, h, w) = spec.chunks;
+        fs::create_dir_all(root.join(ENERGY))?;
+        fs::create_dir_all(root.join(STEP))?;
+        let writer = Self {
+            root: root.to_path_buf(),
+            dims,
+            chunks: (t, h.min(dims.0), w.min(dims.1)),
+            level: spec.level,
+            attrs,
+            pending: Vec::new(),
+            steps: Vec::new(),
+        };
+        writer.write_metadata(0)?;
+        Ok(writer)
+    }
+
+    pub fn push(&mut self, step: usize, board: &Array2<f64>) -> io::Result<()> {
+        self.pending.push(board.clone());
+        self.steps.push(step);
+        if self.pending.len() == self.chunks.0 {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    // writes what's pending, so the store reads back with every frame pushed;
+    // a slab cut short is written again in full once it fills
+    pub fn flush(&mut self) -> io::Result<()> {
+        let (ct, ch, cw) = self.chunks;
+        let (h, w) = self.dims;
+        let frames = self.steps.len();
+        let slab = (frames - self.pending.len()) / ct;
+
+        for (ci, cj) in itertools::iproduct!(0..h.div_ceil(ch), 0..w.div_ceil(cw)) {
+            // chunks are always whole, the parts past the array's edge zero
+            let mut raw = Vec::with_capacity(ct * ch * cw * 8);
+            for k in 0..ct {
+                for i in ci * ch..(ci + 1) * ch {
+                    for j in cj * cw..(cj + 1) * cw {
+                        let e = match self.pending.get(k) {
+                            Some(board) if i < h && j < w => board[[i, j]],
+                            _ => 0.0,
+                        };
+                        raw.extend_from_slice(&e.to_le_bytes());
+                    }
+                }
+            }
+            let key = format!("{}.{}.{}", slab, ci, cj);
+            fs::write(self.root.join(ENERGY).join(key), self.compress(&raw)?)?;
+        }
+
+        if self.pending.len() == ct {
+            self.pending.clear();
+        }
+        let steps: Vec<u8> = self
+            .steps
+            .iter()
+            .flat_map(|&s| (s as u64).to_le_bytes())
+            .collect();
+        fs::write(self.root.join(STEP).join("0"), steps)?;
+        self.write_metadata(frames)
+    }
+
+    // returns the frames written
+    pub fn finish(mut self) -> io::Result<usize> {
+        if !self.pending.is_empty() {
+            self.flush()?;
+        }
+        Ok(self.steps.len())
+    }
+
+    fn compress(&self, raw: &[u8]) -> io::Result<Vec<u8>> {
+        match self.level {
+            None => Ok(raw.to_vec()),
+            Some(level) => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+                encoder.write_all(raw)?;
+                encoder.finish()
+            }
+        }
+    }
+
+    fn write_metadata(&self, frames: usize) -> io::Result<()> {
+        let (ct, ch, cw) = self.chunks;
+        let (h, w) = self.dims;
+        let compressor = match self.level {
+            Some(level) => json!({"id": "zlib", "level": level}),
+            None => Value::Null,
+        };
+        let array = |shape: Value, chunks: Value, dtype: &str, compressor: Value| {
+            json!({
+                "zarr_format": 2,
+                "shape": shape,
+                "chunks": chunks,
+                "dtype": dtype,
+                "compressor": compressor,
+                "fill_value": 0,
+                "order": "C",
+                "filters": null,
+                "dimension_separator": ".",
+            })
+        };
+        let files = [
+            (".zgroup".to_string(), json!({"zarr_format": 2})),
+            (".zattrs".to_string(), self.attrs.clone()),
+            (
+                format!("{}/.zarray", ENERGY),
+                array(
+                    json!([frames, h, w]),
+                    json!([ct, ch, cw]),
+                    "<f8",
+                    compressor,
+                ),
+            ),
+            (
+                format!("{}/.zattrs", ENERGY),
+                json!({"_ARRAY_DIMENSIONS": [STEP, "y", "x"]}),
+            ),
+            // the steps are one chunk, rewritten as frames come in
+            (
+                format!("{}/.zarray", STEP),
+                array(json!([frames]), json!([frames.max(1)]), "<u8", Value::Null),
+            ),
+            (
+                format!("{}/.zattrs", STEP),
+                json!({"_ARRAY_DIMENSIONS": [STEP]}),
+            ),
+        ];
+
+        let mut consolidated = serde_json::Map::new();
+        for (key, value) in files {
+            fs::write(self.root.join(&key), serde_json::to_vec_pretty(&value)?)?;
+            consolidated.insert(key, value);
+        }
+        let zmetadata = json!({"zarr_consolidated_format": 1, "metadata": consolidated});
+        fs::write(
+            self.root.join(".zmetadata"),
+            serde_json::to_vec_pretty(&zmetadata)?,
+        )
+    }
+}
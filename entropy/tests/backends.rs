@@ -0,0 +1,252 @@
+use entropy::{
+    board_time_step, init_board, scratch_time_step, traced_time_step, Backend, Board, Boundary,
+    Config, Drift, Kernel, Levy, ParallelScratch, QuantaBoard, SimRng, SparseBoard, Tracer,
+};
+use itertools::iproduct;
+use ndarray::Array2;
+use rand::SeedableRng;
+
+const SEED: u64 = 7;
+const STEPS: usize = 50;
+// relative to the total energy on the board
+const TOLERANCE: f64 = 1e-9;
+
+fn config(backend: Backend) -> Config {
+    let mut config: Config = serde_json::from_str(
+        r#"{
+            "dims": [24, 24],
+            "hotspots": 3,
+            "sleep_interval_ms": 0,
+            "heat": 1.0,
+            "size_factor": 1
+        }"#,
+    )
+    .unwrap();
+    config.backend = backend;
+    config
+}
+
+fn run_on<B: Board>(backend: Backend) -> Array2<f64> {
+    let config = config(backend);
+    let mut rng = SimRng::seed_from_u64(SEED);
+
+    let mut lagged_board: B = init_board(&config, &mut rng);
+    let mut board = B::zeros(config.dims);
+
+    for _ in 0..STEPS {
+        board_time_step(&mut board, &mut lagged_board, &config, &mut rng, None);
+    }
+
+    lagged_board.to_dense()
+}
+
+fn run(backend: Backend) -> Array2<f64> {
+    run_on::<Array2<f64>>(backend)
+}
+
+fn max_abs_diff(a: &Array2<f64>, b: &Array2<f64>) -> f64 {
+    (a - b).fold(0.0_f64, |m, &d| m.max(d.abs()))
+}
+
+#[test]
+fn backends_agree_with_scalar() {
+    let reference = run(Backend::Scalar);
+    let total = reference.sum();
+
+    for &backend in Backend::ALL {
+        let diff = max_abs_diff(&reference, &run(backend));
+        assert!(
+            diff <= TOLERANCE * total,
+            "{:?} differs from scalar by {}",
+            backend,
+            diff
+        );
+    }
+}
+
+#[test]
+fn parallel_agrees_with_scalar_on_every_boundary() {
+    let kernels = [
+        Kernel::default(),
+        Kernel::Gaussian {
+            radius: 2,
+            sigma: None,
+        },
+    ];
+    for (boundary, kernel) in iproduct!(
+        [Boundary::Closed, Boundary::Periodic, Boundary::Absorbing],
+        kernels
+    ) {
+        let run = |backend| {
+            let config = Config {
+                boundary,
+                kernel: kernel.clone(),
+                levy: Some(Levy {
+                    fraction: 0.1,
+                    exponent: 1.5,
+                }),
+                drift: Some(Drift::Buoyancy {
+                    coefficient: 1.0,
+                    max_speed: 0.5,
+                }),
+                active_threshold: 1e-6,
+                threads: Some(3),
+                heat: 0.5,
+                ..config(backend)
+            };
+            let mut rng = SimRng::seed_from_u64(SEED);
+            let mut lagged_board: Array2<f64> = init_board(&config, &mut rng);
+            let mut board = Array2::zeros(config.dims);
+            let mut dye = Tracer::LeftHalf.initial(config.dims);
+            let mut escaped = 0.0;
+            for _ in 0..STEPS {
+                let report = traced_time_step(
+                    &mut board,
+                    &mut lagged_board,
+                    Some(&mut dye),
+                    &config,
+                    &mut rng,
+                    None,
+                );
+                escaped += report.escaped;
+            }
+            (lagged_board, dye, escaped)
+        };
+
+        let (reference, dye, escaped) = run(Backend::Scalar);
+        let (parallel, parallel_dye, parallel_escaped) = run(Backend::Parallel);
+        let total = reference.sum() + escaped;
+        assert!(
+            max_abs_diff(&reference, &parallel) <= TOLERANCE * total,
+            "{:?} {:?}",
+            boundary,
+            kernel
+        );
+        assert!((escaped - parallel_escaped).abs() <= TOLERANCE * total);
+        assert!(max_abs_diff(&dye, &parallel_dye) <= TOLERANCE * dye.len() as f64);
+    }
+}
+
+#[test]
+fn parallel_scratch_carries_over_threads_and_dims() {
+    let reference = run(Backend::Parallel);
+    let mut parallel = ParallelScratch::default();
+
+    for (threads, dims) in [(Some(1), (24, 24)), (Some(3), (9, 13)), (None, (24, 24))] {
+        let mut config = config(Backend::Parallel);
+        config.dims = dims;
+        config.threads = threads;
+        let mut rng = SimRng::seed_from_u64(SEED);
+        let mut lagged_board: Array2<f64> = init_board(&config, &mut rng);
+        let mut board = Array2::zeros(config.dims);
+        let mut fresh_rng = rng.clone();
+        let mut fresh = lagged_board.clone();
+        let mut fresh_board = board.clone();
+
+        for _ in 0..STEPS {
+            scratch_time_step(
+                &mut board,
+                &mut lagged_board,
+                None,
+                &config,
+                &mut rng,
+                None,
+                &mut parallel,
+            );
+            traced_time_step(
+                &mut fresh_board,
+                &mut fresh,
+                None,
+                &config,
+                &mut fresh_rng,
+                None,
+            );
+        }
+        assert_eq!(lagged_board, fresh, "{:?} threads on {:?}", threads, dims);
+        if dims == (24, 24) {
+            assert_eq!(lagged_board, reference);
+        }
+    }
+}
+
+#[test]
+fn backends_are_deterministic_for_a_seed() {
+    for &backend in Backend::ALL {
+        assert_eq!(run(backend), run(backend), "{:?}", backend);
+    }
+}
+
+#[test]
+fn backends_conserve_energy() {
+    let initial =
+        init_board::<Array2<f64>>(&config(Backend::Scalar), &mut SimRng::seed_from_u64(SEED)).sum();
+
+    for &backend in Backend::ALL {
+        let total = run(backend).sum();
+        assert!(
+            (total - initial).abs() <= TOLERANCE * initial,
+            "{:?} went from {} to {}",
+            backend,
+            initial,
+            total
+        );
+    }
+}
+
+#[test]
+fn board_storages_agree_with_dense_f64() {
+    let reference = run(Backend::Scalar);
+    let total = reference.sum();
+
+    let sparse = max_abs_diff(&reference, &run_on::<SparseBoard>(Backend::Scalar));
+    assert!(sparse <= TOLERANCE * total, "sparse differs by {}", sparse);
+
+    // single precision only keeps about 7 significant digits
+    let single = max_abs_diff(&reference, &run_on::<Array2<f32>>(Backend::Scalar));
+    assert!(single <= 1e-5 * total, "f32 differs by {}", single);
+
+    // a millionth a quantum, rounded on every write
+    let quanta = max_abs_diff(
+        &reference,
+        &run_on::<QuantaBoard<1_000_000>>(Backend::Scalar),
+    );
+    assert!(quanta <= 1e-5 * total, "quanta differ by {}", quanta);
+}
+
+#[test]
+fn sparse_and_quanta_totals_are_exact_sums() {
+    let cells = [
+        ((0, 0), 0.1),
+        ((3, 5), 1e-17),
+        ((7, 2), 2.5),
+        ((1, 6), -0.3),
+    ];
+    let mut sparse = SparseBoard::zeros((8, 8));
+    let mut quanta = QuantaBoard::<1000>::zeros((8, 8));
+    for (cell, energy) in cells {
+        sparse.add(cell, energy);
+        quanta.add(cell, energy);
+    }
+    // the same however the cells were put in
+    let mut reversed = SparseBoard::zeros((8, 8));
+    for (cell, energy) in cells.into_iter().rev() {
+        reversed.add(cell, energy);
+    }
+    assert_eq!(sparse.total().to_bits(), reversed.total().to_bits());
+    assert!((sparse.total() - 2.3).abs() < 1e-12);
+
+    assert_eq!(quanta.count((0, 0)), 100);
+    assert_eq!(quanta.count((3, 5)), 0);
+    assert_eq!(quanta.total(), 2.3);
+}
+
+#[test]
+#[should_panic(expected = "lattice_time_step")]
+fn lbm_steps_need_their_lattice() {
+    let mut config = config(Backend::Scalar);
+    config.scheme = entropy::Scheme::Lbm;
+    let mut rng = SimRng::seed_from_u64(SEED);
+    let mut lagged_board: Array2<f64> = init_board(&config, &mut rng);
+    let mut board = Array2::zeros(config.dims);
+    board_time_step(&mut board, &mut lagged_board, &config, &mut rng, None);
+}
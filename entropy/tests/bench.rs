@@ -0,0 +1,41 @@
+use entropy::bench::{self, BenchReport, BenchResult, CASES, REPORT_VERSION};
+
+fn report(medians: &[(&str, f64)]) -> BenchReport {
+    BenchReport {
+        version: REPORT_VERSION,
+        results: medians
+            .iter()
+            .map(|&(name, median_ns)| BenchResult {
+                name: name.to_string(),
+                steps: 10,
+                median_ns,
+                p95_ns: median_ns,
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn slower_medians_past_the_threshold_are_regressions() {
+    let baseline = report(&[("a", 100.0), ("b", 100.0), ("gone", 5.0)]);
+    let current = report(&[("a", 105.0), ("b", 125.0), ("new", 1.0)]);
+    let comparisons = bench::compare(&baseline, &current, 0.1);
+    let names: Vec<_> = comparisons.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["a", "b"]);
+    assert!(!comparisons[0].regressed);
+    assert!(comparisons[1].regressed);
+    assert!((comparisons[1].change - 0.25).abs() < 1e-12);
+}
+
+#[test]
+fn every_case_runs_and_the_report_survives_a_save() {
+    assert!(CASES.iter().all(|case| case.config().validate().is_ok()));
+
+    let report = bench::run(2, Some("gather"));
+    assert_eq!(report.results.len(), 1);
+    assert_eq!(report.results[0].steps, 2);
+    let path = std::env::temp_dir().join(format!("entropy-bench-{}.json", std::process::id()));
+    report.write(&path).unwrap();
+    assert_eq!(BenchReport::read(&path).unwrap(), report);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,46 @@
+use entropy::debug::{read_dump, rng_at, write_dump, StepDump};
+use entropy::{board_time_step, init_board, Config, SimRng};
+use ndarray::Array2;
+use rand::SeedableRng;
+
+#[test]
+fn dumps_replay_their_step_bit_for_bit() {
+    let seed = 12;
+    let config = Config {
+        dims: (16, 16),
+        seed: Some(seed),
+        ..Config::default()
+    };
+    let mut rng = SimRng::seed_from_u64(seed);
+    let mut lagged_board: Array2<f64> = init_board(&config, &mut rng);
+    let mut board = Array2::zeros(config.dims);
+    for _ in 0..9 {
+        board_time_step(&mut board, &mut lagged_board, &config, &mut rng, None);
+    }
+
+    // the tenth step, dumped as checked_time_step would before taking it
+    let dump = StepDump {
+        step: 10,
+        seed,
+        word_pos: rng.get_word_pos(),
+        cell: (0, 0),
+        value: 0.0,
+        weights: None,
+        lagged_board: lagged_board.clone(),
+        config: config.clone(),
+    };
+    board_time_step(&mut board, &mut lagged_board, &config, &mut rng, None);
+
+    let path = std::env::temp_dir().join(format!("entropy-dump-{}.json", std::process::id()));
+    write_dump(&path, &dump);
+    let read = read_dump(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(rng_at(seed, read.word_pos).get_word_pos(), dump.word_pos);
+
+    let replayed = read.replay();
+    assert_eq!(replayed.dim(), lagged_board.dim());
+    assert!(replayed
+        .iter()
+        .zip(&lagged_board)
+        .all(|(a, b)| a.to_bits() == b.to_bits()));
+}
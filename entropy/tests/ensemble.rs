@@ -0,0 +1,56 @@
+use entropy::ensemble::{bootstrap, Ensemble, EnsembleExperiment};
+use entropy::{Config, SimRng};
+use rand::SeedableRng;
+
+#[test]
+fn bootstrap_bands_hold_the_mean_and_narrow_with_more_runs() {
+    let mut rng = SimRng::seed_from_u64(3);
+    let few: Vec<f64> = (0..8).map(|i| i as f64).collect();
+    let many: Vec<f64> = (0..800).map(|i| (i % 8) as f64).collect();
+    let narrow = bootstrap(&few, 2000, 0.5, &mut rng);
+    let wide = bootstrap(&few, 2000, 0.99, &mut rng);
+    let tight = bootstrap(&many, 2000, 0.99, &mut rng);
+    for band in [narrow, wide, tight] {
+        assert_eq!(band.mean, 3.5);
+        assert!(band.lo < band.mean && band.mean < band.hi);
+    }
+    assert!(wide.hi - wide.lo > narrow.hi - narrow.lo);
+    assert!(tight.hi - tight.lo < (wide.hi - wide.lo) / 5.0);
+
+    // identical runs leave nothing to resample
+    let same = bootstrap(&[2.0; 10], 100, 0.95, &mut rng);
+    assert_eq!((same.lo, same.hi), (2.0, 2.0));
+}
+
+#[test]
+fn ensembles_sample_every_few_steps_and_repeat_with_a_seed() {
+    let config = Config {
+        dims: (12, 12),
+        hotspots: 3,
+        seed: Some(5),
+        ..Config::default()
+    };
+    let experiment = EnsembleExperiment {
+        runs: 6,
+        steps: 20,
+        every: 5,
+        resamples: 200,
+        confidence: 0.9,
+    };
+    let ensemble = experiment.run(&config).unwrap();
+    assert_eq!(ensemble.runs, 6);
+    let steps: Vec<_> = ensemble.rows.iter().map(|r| r.step).collect();
+    assert_eq!(steps, [0, 5, 10, 15, 20]);
+    // the boards spread out, so the entropy rises
+    assert!(ensemble.rows[4].entropy.mean > ensemble.rows[0].entropy.mean);
+    assert_eq!(experiment.run(&config).unwrap().rows, ensemble.rows);
+
+    let path = std::env::temp_dir().join(format!("entropy-ensemble-{}.csv", std::process::id()));
+    ensemble.write(&path).unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<_> = text.lines().collect();
+    assert_eq!(lines[0], Ensemble::HEADER);
+    assert_eq!(lines.len(), 6);
+    assert_eq!(lines[1].split(',').count(), 7);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,57 @@
+use entropy::estimator::{Binned, EntropyEstimator, Estimator, NearestNeighbor, Renyi, Shannon};
+use entropy::stats::RunMetrics;
+use entropy::{Config, ConfigError};
+use ndarray::Array2;
+
+#[test]
+fn estimators_agree_where_they_should_and_differ_where_they_should() {
+    let uniform = Array2::from_elem((8, 8), 1.0);
+    let ln_n = 64f64.ln();
+    assert!((Shannon.estimate(&uniform) - ln_n).abs() < 1e-12);
+    assert!((Renyi { alpha: 2.0 }.estimate(&uniform) - ln_n).abs() < 1e-12);
+
+    let peaked = Array2::from_shape_fn((8, 8), |(i, j)| if (i, j) == (0, 0) { 20.0 } else { 1.0 });
+    let shannon = Shannon.estimate(&peaked);
+    assert_eq!(Renyi { alpha: 1.0 }.estimate(&peaked), shannon);
+    // rényi entropy falls as alpha rises
+    assert!(Renyi { alpha: 0.5 }.estimate(&peaked) > shannon);
+    assert!(Renyi { alpha: 2.0 }.estimate(&peaked) < shannon);
+
+    // a flat board uses one energy level; two equally common levels make ln 2
+    assert_eq!(Binned { bins: 16 }.estimate(&uniform), 0.0);
+    let halves = Array2::from_shape_fn((8, 8), |(i, _)| if i < 4 { 1.0 } else { 3.0 });
+    assert!((Binned { bins: 16 }.estimate(&halves) - 2f64.ln()).abs() < 1e-12);
+
+    // energies exactly one gap 1/N apart land every distance on the gap, which
+    // leaves the estimator's own constants, Euler's gamma plus ln 2
+    let spread = Array2::from_shape_fn((50, 50), |(i, j)| (50 * i + j) as f64 / 2500.0);
+    let gamma = 0.577_215_664_9;
+    assert!((NearestNeighbor { k: 1 }.estimate(&spread) - gamma - 2f64.ln()).abs() < 1e-3);
+    let wide = spread.mapv(|e| 4.0 * e);
+    let gain =
+        NearestNeighbor { k: 3 }.estimate(&wide) - NearestNeighbor { k: 3 }.estimate(&spread);
+    // stretching a sample by 4 adds ln 4
+    assert!((gain - 4f64.ln()).abs() < 1e-9);
+    assert_eq!(NearestNeighbor { k: 1 }.max_entropy(64), None);
+}
+
+#[test]
+fn the_config_picks_the_estimator_the_metrics_report() {
+    let config: Config = serde_json::from_str(
+        r#"{"dims": [8, 8], "hotspots": 1, "sleep_interval_ms": 0, "heat": 1.0, "size_factor": 1, "entropy_estimator": {"renyi": {"alpha": 2}}}"#,
+    )
+    .unwrap();
+    assert_eq!(config.entropy_estimator, Estimator::Renyi { alpha: 2.0 });
+    let board = Array2::from_shape_fn((8, 8), |(i, j)| (i + j) as f64);
+    let metrics = RunMetrics::new(&board, &config);
+    assert_eq!(
+        metrics.initial().entropy,
+        Renyi { alpha: 2.0 }.estimate(&board)
+    );
+
+    let bad = Config {
+        entropy_estimator: Estimator::Binned { bins: 0 },
+        ..Config::default()
+    };
+    assert!(matches!(bad.validate(), Err(ConfigError::InvalidEstimator)));
+}
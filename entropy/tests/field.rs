@@ -0,0 +1,62 @@
+use entropy::field::{gaussian_field, Spectrum};
+use entropy::{Config, ConfigError, InitialCondition, SimRng, Simulation};
+use ndarray::Array2;
+use rand::SeedableRng;
+
+// how alike horizontally adjacent cells are, from -1 to 1
+fn neighbor_correlation(field: &Array2<f64>) -> f64 {
+    let (h, w) = field.dim();
+    let mut sum = 0.0;
+    for i in 0..h {
+        for j in 0..w - 1 {
+            sum += field[[i, j]] * field[[i, j + 1]];
+        }
+    }
+    sum / (h * (w - 1)) as f64
+}
+
+#[test]
+fn longer_correlations_make_smoother_fields() {
+    let mut rng = SimRng::seed_from_u64(3);
+    let white = gaussian_field((40, 60), &Spectrum::PowerLaw { exponent: 0.0 }, &mut rng);
+    let smooth = gaussian_field((40, 60), &Spectrum::Gaussian { length: 4.0 }, &mut rng);
+
+    for field in [&white, &smooth] {
+        assert_eq!(field.dim(), (40, 60));
+        assert!(field.mean().unwrap().abs() < 1e-9);
+        assert!((field.std(0.0) - 1.0).abs() < 1e-9);
+    }
+    assert!(neighbor_correlation(&white).abs() < 0.1);
+    assert!(neighbor_correlation(&smooth) > 0.9);
+}
+
+#[test]
+fn a_field_initial_condition_starts_near_one_unit_a_cell() {
+    let config: Config = serde_json::from_str(
+        r#"{
+            "dims": [32, 32],
+            "hotspots": 1,
+            "sleep_interval_ms": 0,
+            "heat": 1.0,
+            "size_factor": 1,
+            "seed": 5,
+            "initial": {"field": {"spectrum": {"power_law": {"exponent": 3.0}}, "contrast": 0.2}}
+        }"#,
+    )
+    .unwrap();
+    let sim = Simulation::new(config.clone()).unwrap();
+    let board = sim.board();
+
+    assert!(board.iter().all(|&e| e >= 0.0));
+    assert!((board.mean().unwrap() - 1.0).abs() < 0.05);
+    assert!(board.std(0.0) > 0.1);
+
+    let bad = Config {
+        initial: InitialCondition::Field {
+            spectrum: Spectrum::Gaussian { length: 0.0 },
+            contrast: 0.5,
+        },
+        ..config
+    };
+    assert!(matches!(bad.validate(), Err(ConfigError::InvalidSpectrum)));
+}
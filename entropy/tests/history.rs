@@ -0,0 +1,25 @@
+use entropy::history::History;
+use ndarray::Array2;
+
+#[test]
+fn histories_keep_what_fits_and_drop_the_oldest() {
+    // each frame's header counts, so a tiny board still gets a bounded number
+    let tiny = History::with_memory_cap((2, 2), 1);
+    let header = std::mem::size_of::<Array2<f64>>();
+    assert_eq!(tiny.capacity(), 1024 * 1024 / (4 * 8 + header));
+
+    // frames of 1024 cells would fit 128 times in 1 MB without their headers
+    let mut history = History::with_memory_cap((32, 32), 1);
+    assert!(history.capacity() < 128);
+    for k in 0..history.capacity() + 5 {
+        history.push(&Array2::from_elem((32, 32), k as f64));
+    }
+    assert_eq!(history.len(), history.capacity());
+    assert_eq!(
+        history.get(0).unwrap()[[0, 0]],
+        (history.capacity() + 4) as f64
+    );
+    // the 5 oldest are gone
+    let oldest = history.get(history.len() - 1).unwrap();
+    assert_eq!(oldest[[0, 0]], 5.0);
+}
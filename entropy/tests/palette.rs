@@ -0,0 +1,228 @@
+use entropy::palette::{self, Blend, Palette, PaletteError, Stop};
+
+#[test]
+fn palettes_blend_between_stops_and_survive_a_save() {
+    let dir = std::env::temp_dir().join(format!("entropy-palettes-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let stops = ["1=#ffffff", "0=#000000", "0.5=#ff0000"]
+        .iter()
+        .map(|s| Stop::parse(s))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let palette = Palette::new(stops).unwrap();
+    assert_eq!(palette.sample(0.0), [0, 0, 0]);
+    assert_eq!(palette.sample(0.25), [128, 0, 0]);
+    assert_eq!(palette.sample(0.75), [255, 128, 128]);
+    assert_eq!(palette.sample(7.0), [255, 255, 255]);
+
+    palette.save(&dir, "embers").unwrap();
+    assert_eq!(Palette::load(&dir, "embers").unwrap(), palette);
+    assert_eq!(palette::list(&dir).unwrap(), vec!["embers".to_string()]);
+    assert!(matches!(
+        Palette::load(&dir, "../embers"),
+        Err(PaletteError::BadName(_))
+    ));
+}
+
+#[test]
+fn bad_stops_are_rejected() {
+    assert!(matches!(
+        Stop::parse("0.5=#ff00"),
+        Err(PaletteError::BadColor(_))
+    ));
+    assert!(matches!(Stop::parse("half"), Err(PaletteError::BadStop(_))));
+    let one = vec![Stop::parse("0=#000000").unwrap()];
+    assert!(matches!(Palette::new(one), Err(PaletteError::TooFewStops)));
+    let off = vec![
+        Stop::parse("0=#000000").unwrap(),
+        Stop::parse("1.5=#000000").unwrap(),
+    ];
+    assert!(matches!(
+        Palette::new(off),
+        Err(PaletteError::StopOffRamp(_))
+    ));
+}
+
+#[test]
+fn hues_wrap_round_the_circle() {
+    let close = |a: [f64; 3], b: [f64; 3]| a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-12);
+    assert!(close(palette::hsv_to_rgb(0.0, 1.0, 1.0), [1.0, 0.0, 0.0]));
+    assert!(close(palette::hsv_to_rgb(360.0, 1.0, 1.0), [1.0, 0.0, 0.0]));
+    assert!(close(
+        palette::hsv_to_rgb(-120.0, 1.0, 1.0),
+        [0.0, 0.0, 1.0]
+    ));
+    assert!(close(palette::hsv_to_rgb(120.0, 0.0, 0.5), [0.5, 0.5, 0.5]));
+    for k in -720..=720 {
+        let rgb = palette::hsv_to_rgb(k as f64 * 0.999, 1.0, 1.0);
+        assert!(
+            rgb.iter().all(|c| (0.0..=1.0).contains(c)),
+            "{} {:?}",
+            k,
+            rgb
+        );
+    }
+
+    // the built-in ramp saturates past its ends instead of wrapping to magenta
+    assert_eq!(palette::hue_ramp(0.0), [0, 0, 255]);
+    assert_eq!(palette::hue_ramp(1.0), [255, 0, 0]);
+    assert_eq!(palette::hue_ramp(1.5), [255, 0, 0]);
+    assert_eq!(palette::hue_ramp(-0.5), [0, 0, 255]);
+    assert_eq!(palette::hue_ramp(f64::NAN), [0, 0, 255]);
+}
+
+#[test]
+fn oklab_blends_keep_lightness_even() {
+    for rgb in [[0, 0, 0], [255, 255, 255], [255, 136, 0], [12, 200, 99]] {
+        assert_eq!(palette::from_oklab(palette::to_oklab(rgb)), rgb);
+    }
+
+    let stops = vec![
+        Stop::parse("0=#0000ff").unwrap(),
+        Stop::parse("1=#ffff00").unwrap(),
+    ];
+    let rgb = Palette::new(stops.clone()).unwrap();
+    let oklab = Palette::new(stops).unwrap().with_blend(Blend::Oklab);
+    assert_eq!(oklab.sample(0.0), [0, 0, 255]);
+    assert_eq!(oklab.sample(1.0), [255, 255, 0]);
+    // blended in sRGB, the middle of the ramp is darker than halfway between its ends
+    let lightness = |c: [u8; 3]| palette::to_oklab(c)[0];
+    let middle = (lightness([0, 0, 255]) + lightness([255, 255, 0])) / 2.0;
+    assert!((lightness(oklab.sample(0.5)) - middle).abs() < 0.01);
+    assert!((lightness(rgb.sample(0.5)) - middle).abs() > 0.05);
+
+    let json = serde_json::to_string(&oklab).unwrap();
+    assert_eq!(serde_json::from_str::<Palette>(&json).unwrap(), oklab);
+    // sRGB palettes are still saved as a bare list of stops
+    assert!(serde_json::to_string(&rgb).unwrap().starts_with('['));
+}
+
+#[test]
+fn linear_light_blends_are_brighter_between_stops() {
+    for k in 0..=40 {
+        let t = k as f64 / 40.0;
+        // the stops are rounded to whole channels
+        let (a, b) = (Palette::hue().sample(t), palette::hue_ramp(t));
+        assert!((0..3).all(|k| a[k].abs_diff(b[k]) <= 1), "{}", t);
+    }
+
+    let gray = Palette::new(vec![
+        Stop::parse("0=#000000").unwrap(),
+        Stop::parse("1=#ffffff").unwrap(),
+    ])
+    .unwrap();
+    assert_eq!(gray.sample(0.5), [128, 128, 128]);
+    let linear = gray.in_linear_light();
+    assert_eq!(linear.blend(), Blend::Linear);
+    // half the light of white, encoded as sRGB
+    assert_eq!(linear.sample(0.5), [188, 188, 188]);
+    assert_eq!(linear.sample(1.0), [255, 255, 255]);
+    // between green and yellow the sRGB blend is dimmer than either
+    let hue = Palette::hue().in_linear_light();
+    assert!(hue.sample(0.625)[0] > palette::hue_ramp(0.625)[0]);
+}
+
+#[test]
+fn colormaps_are_picked_by_name_or_given_as_stops() {
+    let colormap = |json: &str| serde_json::from_str::<palette::Colormap>(json).unwrap();
+    assert_eq!(colormap(r#""hue""#).palette(), None);
+    let viridis = colormap(r#""viridis""#).palette().unwrap();
+    assert_eq!(viridis.sample(0.0), [0x44, 0x01, 0x54]);
+    assert_eq!(viridis.sample(1.0), [0xfd, 0xe7, 0x25]);
+    let gray = colormap(r#""grayscale""#).palette().unwrap();
+    assert_eq!(gray.sample(0.5), [128, 128, 128]);
+    // inferno gets lighter all the way along
+    let inferno = colormap(r#""inferno""#).palette().unwrap();
+    let lightness = |t: f64| palette::to_oklab(inferno.sample(t))[0];
+    assert!((1..=20).all(|k| lightness(k as f64 / 20.0) > lightness((k - 1) as f64 / 20.0)));
+
+    let custom =
+        colormap(r##"{"custom": [{"at": 0, "color": "#000000"}, {"at": 1, "color": "#ff0000"}]}"##);
+    assert_eq!(custom.palette().unwrap().sample(0.5), [128, 0, 0]);
+    assert!(serde_json::from_str::<palette::Colormap>(
+        r##"{"custom": [{"at": 0, "color": "#000000"}]}"##
+    )
+    .is_err());
+
+    let config = entropy::Config {
+        palette: Some("embers".to_string()),
+        colormap: palette::Colormap::Inferno,
+        ..Default::default()
+    };
+    assert!(matches!(
+        config.validate(),
+        Err(entropy::ConfigError::PaletteAndColormap)
+    ));
+}
+
+#[test]
+fn the_ramp_is_scaled_to_the_initial_board() {
+    use ndarray::Array2;
+
+    // one hotspot on a square board is drawn up to twice the mean, as it always was
+    let mut hotspot = Array2::zeros((10, 10));
+    hotspot[(3, 4)] = 100.0;
+    assert_eq!(palette::auto_max_energy(&hotspot), 2.0);
+    // a smooth start below twice its mean is drawn up to its peak
+    let smooth = Array2::from_shape_fn((4, 4), |(i, _)| 1.0 + 0.1 * i as f64);
+    assert!((palette::auto_max_energy(&smooth) - 1.3).abs() < 1e-12);
+    assert_eq!(palette::auto_max_energy(&Array2::zeros((4, 4))), 1.0);
+
+    let mut config = entropy::Config::default();
+    assert_eq!(config.max_energy_for(&hotspot.mapv(|e| e * 3.0)), 6.0);
+    config.max_energy = Some(0.5);
+    assert_eq!(config.max_energy_for(&hotspot), 0.5);
+    config.max_energy = Some(0.0);
+    assert!(matches!(
+        config.validate(),
+        Err(entropy::ConfigError::InvalidMaxEnergy(_))
+    ));
+}
+
+#[test]
+fn the_scale_follows_the_normalization() {
+    use ndarray::Array2;
+    use palette::{Normalization, Scale};
+
+    let board = Array2::from_shape_fn((10, 10), |(i, j)| (10 * i + j + 1) as f64);
+    assert_eq!(palette::percentile(&board, 99.0), 99.0);
+    assert_eq!(palette::percentile(&board, 0.0), 1.0);
+
+    let mut fixed = Scale::new(Normalization::Fixed, 2.0);
+    assert_eq!(fixed.update(&board), 2.0);
+    let mut percentile = Scale::new(Normalization::Percentile(50.0), 2.0);
+    assert_eq!(percentile.update(&board), 50.0);
+    // mostly empty boards fall back to the fixed max
+    assert_eq!(percentile.update(&Array2::zeros((4, 4))), 2.0);
+
+    let mut running = Scale::new(Normalization::RunningMax, 2.0);
+    assert_eq!(running.update(&board), 100.0);
+    assert_eq!(running.update(&(&board / 10.0)), 100.0);
+
+    let config: entropy::Config =
+        serde_json::from_str(r#"{"dims": [10, 10], "hotspots": 1, "sleep_interval_ms": 0, "heat": 1.0, "size_factor": 1, "normalization": {"percentile": 120}}"#).unwrap();
+    assert!(matches!(
+        config.validate(),
+        Err(entropy::ConfigError::InvalidNormalization)
+    ));
+}
+
+#[test]
+fn a_log_scale_lifts_the_background_off_the_bottom_of_the_ramp() {
+    use palette::ColorScale;
+
+    let max = 10_000.0;
+    assert_eq!(ColorScale::Linear.position(100.0, max), 0.01);
+    assert_eq!(ColorScale::Log.position(0.0, max), 0.0);
+    assert_eq!(ColorScale::Log.position(max, max), 1.0);
+    assert!(ColorScale::Log.position(-1.0, max) == 0.0);
+    // a cell at one hundredth of the peak gets half the ramp
+    assert!((ColorScale::Log.position(100.0, max) - 0.5).abs() < 0.01);
+
+    let config: entropy::Config = serde_json::from_str(
+        r#"{"dims": [10, 10], "hotspots": 1, "sleep_interval_ms": 0, "heat": 1.0, "size_factor": 1, "scale": "log"}"#,
+    )
+    .unwrap();
+    assert_eq!(config.scale, ColorScale::Log);
+}
@@ -0,0 +1,39 @@
+use entropy::recording::{default_levels, downsample, RecordingIndex, RecordingWriter};
+use entropy::SimulationBuilder;
+use ndarray::array;
+
+#[test]
+fn downsampling_averages_blocks_and_the_odd_edge() {
+    let board = array![[1.0, 3.0, 5.0], [1.0, 3.0, 7.0], [2.0, 2.0, 9.0]];
+    assert_eq!(downsample(&board), array![[2.0, 6.0], [2.0, 9.0]]);
+    assert_eq!(default_levels((100, 70), 16), 3);
+}
+
+#[test]
+fn pyramid_levels_are_written_and_indexed() {
+    let dir = std::env::temp_dir().join(format!("entropy-recording-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let sim = SimulationBuilder::new()
+        .dims(10, 6)
+        .seed(4)
+        .build()
+        .unwrap();
+    let mut writer = RecordingWriter::create(&dir, (10, 6), 2, 3).unwrap();
+    for frame in sim.take(6).filter(|f| f.step % 2 == 0) {
+        writer.push(&frame).unwrap();
+    }
+    writer.finish().unwrap();
+
+    let index = RecordingIndex::read(&dir).unwrap();
+    let steps: Vec<_> = index.frames.iter().map(|f| f.step).collect();
+    assert_eq!(steps, [2, 4, 6]);
+    let dims: Vec<_> = index.levels.iter().map(|l| l.dims).collect();
+    assert_eq!(dims, [(10, 6), (5, 3), (3, 2)]);
+    for (level, &(h, w)) in dims.iter().enumerate() {
+        let bytes = std::fs::read(index.frame_path(&dir, level, 4)).unwrap();
+        assert_eq!(bytes.len(), h * w * 4);
+    }
+    assert!(dir.join("frames/00000004.npy").is_file());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,50 @@
+use entropy::report::{base64, line_chart, Report};
+use entropy::stats::EnergyBudget;
+use entropy::Config;
+use image::{Rgb, RgbImage};
+use ndarray::array;
+
+#[test]
+fn reports_inline_the_curve_frame_budget_and_config() {
+    assert_eq!(base64(b""), "");
+    assert_eq!(base64(b"f"), "Zg==");
+    assert_eq!(base64(b"fo"), "Zm8=");
+    assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+
+    let chart = line_chart(&[(0.0, 1.0), (10.0, 2.0)], "step", "a < b");
+    assert!(chart.starts_with("<svg"));
+    assert!(chart.contains("a &lt; b"));
+    // the curve spans the plot, from the left margin to the right one
+    assert!(chart.contains("points=\"64.0,280.0 624.0,12.0\""));
+
+    let budget = EnergyBudget::new(&array![[1.0, 2.0]]);
+    let report = Report {
+        title: "entropy run".to_string(),
+        seed: 9,
+        config: Config {
+            hotspots: 7,
+            ..Config::default()
+        },
+        entropy: (0..=5000)
+            .map(|step| (step, (step as f64).ln_1p()))
+            .collect(),
+        budget: budget.rows().to_vec(),
+        drift: budget.drift(),
+        final_frame: RgbImage::from_pixel(4, 2, Rgb([200, 10, 10])),
+    };
+    let html = report.html().unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("5000 steps, seed 9"));
+    assert!(html.contains("src=\"data:image/png;base64,iVBORw0KGgo"));
+    assert!(html.contains("<tr><td>initial board</td><td>+3.000000000</td></tr>"));
+    assert!(html.contains("<tr><td>hotspots</td><td><code>7</code></td></tr>"));
+    // long runs are thinned before they're drawn
+    let points = html
+        .split("points=\"")
+        .nth(1)
+        .unwrap()
+        .split('"')
+        .next()
+        .unwrap();
+    assert!(points.split(' ').count() <= entropy::report::MAX_POINTS + 1);
+}
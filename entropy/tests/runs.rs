@@ -0,0 +1,55 @@
+use entropy::runs::{self, Manifest};
+use entropy::SimulationBuilder;
+use std::path::PathBuf;
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("entropy-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn archived_runs_are_listed_oldest_first_and_names_stay_unique() {
+    let root = scratch_dir("runs");
+    let mut sim = SimulationBuilder::new().dims(8, 8).seed(3).build().unwrap();
+    sim.step();
+
+    for started in [200, 100, 100] {
+        let mut manifest = Manifest {
+            name: runs::run_name(started),
+            started,
+            duration_secs: 1.0,
+            steps: sim.steps(),
+            seed: sim.seed(),
+            initial_entropy: 0.0,
+            final_entropy: 1.0,
+            config: sim.config().clone(),
+        };
+        runs::archive(&root, &mut manifest, &sim, None).unwrap();
+    }
+
+    let names: Vec<_> = runs::list(&root)
+        .unwrap()
+        .into_iter()
+        .map(|m| m.name)
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "1970-01-01_00-01-40",
+            "1970-01-01_00-01-40-2",
+            "1970-01-01_00-03-20"
+        ]
+    );
+
+    let dest = root.join("exported");
+    let copied = runs::export(&root, &names[1], &dest).unwrap();
+    assert_eq!(copied.len(), 2);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn utc_time_handles_leap_days() {
+    assert_eq!(runs::utc_time(951_782_400), "2000-02-29 00:00:00");
+    assert_eq!(runs::utc_time(1_790_000_000), "2026-09-21 14:13:20");
+}
@@ -0,0 +1,23 @@
+use entropy::script::resample;
+use ndarray::{s, Array2};
+
+#[test]
+fn zooming_out_conserves_energy_and_leaves_a_cold_border() {
+    let board = Array2::from_shape_fn((8, 8), |(i, j)| (i * 8 + j) as f64);
+    let zoomed = resample(&board, 0.5, (0.0, 0.0));
+
+    assert!((zoomed.sum() - board.sum()).abs() <= 1e-9 * board.sum());
+    let inner = zoomed.slice(s![2..6, 2..6]).sum();
+    assert!((inner - board.sum()).abs() <= 1e-9 * board.sum());
+}
+
+#[test]
+fn shifts_past_the_edge_pile_up_on_it() {
+    let mut board = Array2::zeros((4, 4));
+    board[[1, 1]] = 1.0;
+    let shifted = resample(&board, 1.0, (0.5, -5.0));
+
+    assert_eq!(shifted.sum(), 1.0);
+    assert_eq!(shifted[[1, 0]], 0.5);
+    assert_eq!(shifted[[2, 0]], 0.5);
+}
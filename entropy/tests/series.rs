@@ -0,0 +1,43 @@
+use entropy::series::{SeriesRow, SeriesWriter};
+use ndarray::array;
+
+#[test]
+fn series_rows_are_written_as_csv_or_json_lines() {
+    let board = array![[1.0, 3.0], [0.0, 4.0]];
+    let row = SeriesRow::of(7, &board);
+    assert_eq!(row.total_energy, 8.0);
+    assert_eq!(row.max_energy, 4.0);
+    assert_eq!(row.variance, 2.5);
+    assert_eq!(row.entropy, entropy::stats::shannon_entropy(&board));
+
+    let dir = std::env::temp_dir().join(format!("entropy-series-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for name in ["stats.csv", "stats.jsonl"] {
+        let path = dir.join(name);
+        let mut writer = SeriesWriter::create(&path).unwrap();
+        writer.push(&row).unwrap();
+        writer.push(&SeriesRow { step: 8, ..row }).unwrap();
+        // rows only reach the file a batch at a time
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap().lines().count(),
+            usize::from(name.ends_with(".csv"))
+        );
+        writer.flush().unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        if name.ends_with(".csv") {
+            assert_eq!(lines[0], SeriesRow::HEADER);
+            assert_eq!(lines[1], format!("7,8,4,2.5,{}", row.entropy));
+            assert_eq!(lines.len(), 3);
+        } else {
+            let rows: Vec<SeriesRow> = lines
+                .iter()
+                .map(|l| serde_json::from_str(l).unwrap())
+                .collect();
+            assert_eq!(rows, [row, SeriesRow { step: 8, ..row }]);
+        }
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
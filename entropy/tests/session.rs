@@ -0,0 +1,30 @@
+use entropy::session::{Key, Session};
+use entropy::Config;
+
+#[test]
+fn sessions_only_replay_under_the_config_they_were_recorded_with() {
+    let config = Config {
+        dims: (8, 8),
+        ..Config::default()
+    };
+    let mut session = Session::new(3, config.clone());
+    session.record(2, Key::Deposit { row: 7, col: 7 });
+
+    let shown_differently = Config {
+        size_factor: 3,
+        seed: Some(3),
+        ..config.clone()
+    };
+    assert!(session.mismatches(&shown_differently).is_empty());
+
+    let bigger = Config {
+        dims: (16, 8),
+        heat: 0.5,
+        ..config.clone()
+    };
+    assert_eq!(session.mismatches(&bigger), ["dims", "heat"]);
+
+    // an old session, written before the config was kept
+    let old: Session = serde_json::from_str(r#"{"seed": 3, "events": []}"#).unwrap();
+    assert!(old.mismatches(&bigger).is_empty());
+}
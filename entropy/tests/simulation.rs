@@ -0,0 +1,1363 @@
+use entropy::config::MAX_WINDOW_SIDE;
+use entropy::script::{Action, ScriptedEvent};
+use entropy::speed::{AutoSpeed, Governor, Idler, PowerSave};
+use entropy::stats::{EnergyBudget, RunMetrics, StepMetrics};
+use entropy::{
+    format, par_runs, presets, Assertions, Boundary, Cells, ClampPolicy, Config, ConfigError,
+    ConfigWarning, Drift, FixedSource, Focus, HeatCapacity, Hotspot, InitialCondition, KernelFlags,
+    Levy, Mode, PhaseChange, Region, ResetScope, Scheme, Simulation, SimulationBuilder, Tracer,
+    Traps, Waiting, Walls,
+};
+use ndarray::Array2;
+use rayon::prelude::*;
+
+#[test]
+fn builder_fills_in_defaults() {
+    let sim = SimulationBuilder::new()
+        .dims(32, 16)
+        .hotspots(5)
+        .seed(1)
+        .boundary(Boundary::Closed)
+        .build()
+        .unwrap();
+
+    assert_eq!(sim.board().dim(), (32, 16));
+    assert_eq!(sim.seed(), 1);
+    assert_eq!(sim.config().heat, 1.0);
+    assert_eq!(sim.board().iter().filter(|&&e| e > 0.0).count(), 5);
+}
+
+#[test]
+fn builder_rejects_impossible_configs() {
+    let err = SimulationBuilder::new()
+        .dims(2, 2)
+        .hotspots(5)
+        .build()
+        .err();
+    assert_eq!(
+        err,
+        Some(ConfigError::TooManyHotspots {
+            hotspots: 5,
+            cells: 4
+        })
+    );
+}
+
+#[test]
+fn simulation_iterates_over_frames() {
+    let sim = SimulationBuilder::new()
+        .dims(16, 16)
+        .seed(3)
+        .build()
+        .unwrap();
+    let entropies: Vec<f64> = sim.take(20).map(|frame| frame.entropy()).collect();
+
+    assert_eq!(entropies.len(), 20);
+    // a single hotspot can only spread out
+    assert!(entropies[19] > entropies[0]);
+}
+
+#[test]
+fn parallel_runs_are_seeded_independently() {
+    let config = Config {
+        dims: (16, 16),
+        seed: Some(10),
+        ..Config::default()
+    };
+
+    let run = || -> Vec<_> {
+        par_runs(&config, 4)
+            .unwrap()
+            .map(|mut sim| sim.nth(9).unwrap().board)
+            .collect()
+    };
+    let finals = run();
+
+    assert_eq!(finals.len(), 4);
+    assert_ne!(finals[0], finals[1]);
+    assert_eq!(finals, run());
+}
+
+#[test]
+fn serialized_simulation_continues_identically() {
+    let mut sim = SimulationBuilder::new()
+        .dims(12, 12)
+        .seed(5)
+        .build()
+        .unwrap();
+    sim.nth(4);
+
+    let json = serde_json::to_string(&sim).unwrap();
+    let mut restored: Simulation = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.steps(), 5);
+
+    assert_eq!(sim.nth(9).unwrap().board, restored.nth(9).unwrap().board);
+}
+
+#[test]
+fn checkpoints_replace_the_last_one_and_resume_identically() {
+    let dir = std::env::temp_dir().join(format!("entropy-checkpoint-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("checkpoint.bin");
+
+    let mut sim = SimulationBuilder::new()
+        .dims(12, 12)
+        .seed(8)
+        .build()
+        .unwrap();
+    sim.nth(2);
+    format::write_checkpoint(&path, &sim).unwrap();
+    sim.nth(3);
+    format::write_checkpoint(&path, &sim).unwrap();
+    let names: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(names, ["checkpoint.bin"]);
+
+    let mut resumed = format::read_state(&path).unwrap();
+    assert_eq!(resumed.steps(), 7);
+    assert_eq!(sim.nth(9).unwrap().board, resumed.nth(9).unwrap().board);
+
+    let config = Config {
+        checkpoint_every: Some(0),
+        ..Config::default()
+    };
+    assert!(matches!(
+        config.validate(),
+        Err(ConfigError::ZeroCheckpointEvery)
+    ));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn placed_hotspots_set_the_initial_board_whatever_the_seed() {
+    let spot = |x, y, energy| Hotspot { x, y, energy };
+    let config = |seed| Config {
+        dims: (6, 8),
+        seed: Some(seed),
+        initial: InitialCondition::Placed(vec![
+            spot(0, 0, 50.0),
+            spot(7, 5, 50.0),
+            spot(7, 5, 2.5),
+        ]),
+        ..Config::default()
+    };
+    let board = Simulation::new(config(1)).unwrap().board().clone();
+    assert_eq!(board, *Simulation::new(config(2)).unwrap().board());
+    assert_eq!(board[[0, 0]], 50.0);
+    assert_eq!(board[[5, 7]], 52.5);
+    assert_eq!(board.sum(), 102.5);
+
+    let off = Config {
+        initial: InitialCondition::Placed(vec![spot(8, 0, 1.0)]),
+        ..config(1)
+    };
+    assert!(matches!(
+        off.validate(),
+        Err(ConfigError::HotspotOffBoard((8, 0)))
+    ));
+    let negative = Config {
+        initial: InitialCondition::Placed(vec![spot(1, 1, -1.0)]),
+        ..config(1)
+    };
+    assert!(matches!(
+        negative.validate(),
+        Err(ConfigError::InvalidHotspotEnergy(_))
+    ));
+}
+
+#[test]
+fn fixed_sources_and_sinks_settle_into_a_steady_current() {
+    let config = Config {
+        dims: (10, 10),
+        seed: Some(6),
+        mode: Mode::Deterministic,
+        fixed_sources: vec![FixedSource {
+            cells: Cells::Rect {
+                rows: (0, 2),
+                cols: (0, 2),
+            },
+            power: 1.0,
+        }],
+        sinks: vec![Cells::List(vec![(9, 9), (9, 8)])],
+        ..Config::default()
+    };
+    let mut sim = Simulation::new(config.clone()).unwrap();
+    let mut budget = EnergyBudget::new(sim.board());
+    for _ in 0..3000 {
+        sim.step();
+        budget.update(sim.board(), sim.last_report());
+    }
+    // the sinks come to take out what the four source cells put in
+    assert_eq!(sim.last_report().source_in, 4.0);
+    assert!((sim.last_report().sink_out - 4.0).abs() < 1e-2);
+    assert_eq!(sim.board()[[9, 9]], 0.0);
+    assert!(budget.drift().abs() < 1e-6 * budget.expected());
+
+    let off = Config {
+        sinks: vec![Cells::Rect {
+            rows: (8, 11),
+            cols: (0, 1),
+        }],
+        ..config
+    };
+    assert!(matches!(off.validate(), Err(ConfigError::CellsOffBoard)));
+}
+
+#[test]
+fn certain_field_resets_keep_the_initial_board() {
+    let config = Config {
+        dims: (10, 10),
+        seed: Some(4),
+        reset_rate: 1.0,
+        reset_scope: ResetScope::Field,
+        ..Config::default()
+    };
+    let mut sim = Simulation::new(config).unwrap();
+    let initial = sim.board().clone();
+
+    assert_eq!(sim.nth(4).unwrap().board, initial);
+}
+
+#[test]
+fn heat_sets_how_fast_energy_spreads() {
+    for scheme in [Scheme::Scatter, Scheme::Gather] {
+        let center_after = |heat| {
+            let mut board = Array2::zeros((9, 9));
+            board[[4, 4]] = 1.0;
+            let config = Config {
+                heat,
+                scheme,
+                seed: Some(8),
+                ..Config::default()
+            };
+            let mut sim = Simulation::from_board(config, board).unwrap();
+            let board = sim.nth(4).unwrap().board;
+            assert!((board.sum() - 1.0).abs() < 1e-12);
+            board[[4, 4]]
+        };
+
+        assert_eq!(center_after(0.0), 1.0);
+        assert!(center_after(0.25) > center_after(1.0), "{:?}", scheme);
+    }
+
+    let config = Config {
+        heat: 1.5,
+        ..Config::default()
+    };
+    assert!(matches!(
+        config.validate(),
+        Err(ConfigError::InvalidHeat(_))
+    ));
+}
+
+#[test]
+fn thermal_noise_keeps_a_uniform_board_fluctuating() {
+    let config = Config {
+        dims: (10, 10),
+        seed: Some(6),
+        thermal_noise: 0.01,
+        ..Config::default()
+    };
+    let mut sim = Simulation::from_board(config.clone(), Array2::from_elem((10, 10), 0.5)).unwrap();
+    let mut metrics = RunMetrics::new(sim.board(), &config);
+    for _ in 0..50 {
+        sim.step();
+        metrics.update(sim.steps(), sim.board(), sim.last_report());
+    }
+
+    let spread = sim.board().std(0.0);
+    assert!(spread > 1e-3, "{}", spread);
+    assert!(sim.board().iter().all(|&e| e >= 0.0));
+    let budget = metrics.budget();
+    assert!(budget.drift().abs() <= 1e-9 * budget.expected());
+
+    let negative = Config {
+        thermal_noise: -1.0,
+        ..Config::default()
+    };
+    assert!(matches!(
+        negative.validate(),
+        Err(ConfigError::InvalidThermalNoise(_))
+    ));
+}
+
+#[test]
+fn levy_flights_reach_past_the_stencil_and_conserve_energy() {
+    let config = Config {
+        dims: (40, 40),
+        seed: Some(6),
+        levy: Some(Levy {
+            fraction: 0.3,
+            exponent: 1.0,
+        }),
+        ..Config::default()
+    };
+    let mut sim = Simulation::new(config).unwrap();
+    let initial = sim.board().sum();
+    let board = sim.nth(2).unwrap().board;
+
+    // nearest-neighbor spreading lights at most 7x7 cells in three steps
+    assert!(board.iter().filter(|&&e| e > 0.0).count() > 49);
+    assert!((board.sum() - initial).abs() <= 1e-9 * initial);
+}
+
+#[test]
+fn heavy_tailed_waiting_slows_spreading() {
+    let config = Config {
+        dims: (20, 20),
+        seed: Some(8),
+        ..Config::default()
+    };
+    let waiting = Config {
+        waiting: Some(Waiting { exponent: 0.5 }),
+        ..config.clone()
+    };
+
+    let free = Simulation::new(config).unwrap().nth(29).unwrap();
+    let trapped = Simulation::new(waiting).unwrap().nth(29).unwrap();
+
+    assert!(trapped.entropy() < free.entropy());
+    assert!((trapped.total_energy() - free.total_energy()).abs() <= 1e-9 * free.total_energy());
+}
+
+#[test]
+fn traps_hold_energy_back_and_return_it() {
+    let config = Config {
+        dims: (16, 16),
+        seed: Some(9),
+        traps: Some(Traps {
+            fraction: 0.5,
+            hold_steps: 3,
+            density: 0.2,
+            mask: None,
+        }),
+        ..Config::default()
+    };
+    let mut sim = Simulation::new(config).unwrap();
+    let initial = sim.board().sum();
+    assert!(!sim.traps().unwrap().cells().is_empty());
+
+    for _ in 0..20 {
+        sim.step();
+        let held = sim.last_report().trapped;
+        assert!((sim.board().sum() + held - initial).abs() <= 1e-9 * initial);
+    }
+}
+
+#[test]
+fn moving_sources_add_their_power_every_step() {
+    let config: Config = serde_json::from_str(
+        r#"{
+            "dims": [20, 20],
+            "hotspots": 1,
+            "sleep_interval_ms": 0,
+            "heat": 1.0,
+            "size_factor": 1,
+            "seed": 11,
+            "sources": [
+                {"power": 0.5, "path": {"circle": {"center": [10, 10], "radius": 6, "period": 40}}},
+                {"power": 0.25, "path": {"random_walk": {"start": [2, 2], "step_size": 1.5}}}
+            ]
+        }"#,
+    )
+    .unwrap();
+    let mut sim = Simulation::new(config).unwrap();
+    let initial = sim.board().sum();
+
+    let board = sim.nth(9).unwrap().board;
+    assert!((board.sum() - initial - 10.0 * 0.75).abs() <= 1e-9 * initial);
+}
+
+#[test]
+fn collision_preset_pushes_the_fronts_together() {
+    let mut config = presets::collision();
+    config.seed = Some(12);
+    let mut sim = Simulation::new(config).unwrap();
+    let (_, w) = sim.board().dim();
+
+    // energy-weighted mean column of the left half
+    let left_center = |board: &ndarray::Array2<f64>| {
+        let left = board.slice(ndarray::s![.., ..w / 2]);
+        let weighted: f64 = left.indexed_iter().map(|((_, j), &e)| j as f64 * e).sum();
+        weighted / left.sum()
+    };
+    let before = left_center(sim.board());
+    let after = left_center(&sim.nth(49).unwrap().board);
+
+    assert!(after > before + 5.0, "{} -> {}", before, after);
+}
+
+#[test]
+fn energy_settles_in_proportion_to_heat_capacity() {
+    let config = Config {
+        seed: Some(13),
+        capacity: HeatCapacity::Halves {
+            left: 1.0,
+            right: 3.0,
+        },
+        ..Config::default()
+    };
+    let mut sim = Simulation::from_board(config, ndarray::Array2::ones((10, 10))).unwrap();
+    let board = sim.nth(299).unwrap().board;
+
+    let left = board.slice(ndarray::s![.., ..5]).sum();
+    let right = board.slice(ndarray::s![.., 5..]).sum();
+    assert!((left + right - 100.0).abs() <= 1e-9);
+    assert!(right / left > 2.5, "right/left = {}", right / left);
+}
+
+#[test]
+fn melting_keeps_enthalpy_and_pins_melting_cells() {
+    let phase = PhaseChange {
+        melting_point: 0.5,
+        latent_heat: 2.0,
+    };
+    let config = Config {
+        dims: (12, 12),
+        seed: Some(14),
+        hotspots: 4,
+        phase_change: Some(phase),
+        ..Config::default()
+    };
+    let mut sim = Simulation::new(config).unwrap();
+    let initial = sim.board().sum();
+
+    for _ in 0..30 {
+        sim.step();
+        let latent = sim.last_report().latent;
+        assert!((sim.board().sum() + latent - initial).abs() <= 1e-9 * initial);
+    }
+    assert!(sim.last_report().latent > 0.0);
+    assert!(sim.board().iter().any(|&e| e == phase.melting_point));
+}
+
+#[test]
+fn buoyant_heat_rises() {
+    let mut board = ndarray::Array2::zeros((30, 10));
+    board[[5, 5]] = 50.0;
+    let config = Config {
+        seed: Some(15),
+        drift: Some(entropy::Drift::Buoyancy {
+            coefficient: 1.0,
+            max_speed: 0.5,
+        }),
+        ..Config::default()
+    };
+    let mut sim = Simulation::from_board(config, board).unwrap();
+    let board = sim.nth(29).unwrap().board;
+
+    let mean_row = board
+        .indexed_iter()
+        .map(|((i, _), &e)| i as f64 * e)
+        .sum::<f64>()
+        / board.sum();
+    assert!(mean_row > 8.0, "mean row {}", mean_row);
+}
+
+#[test]
+fn shadow_field_is_the_same_whatever_the_noise() {
+    let mut board = ndarray::Array2::zeros((16, 16));
+    board[[4, 9]] = 100.0;
+    let run = |seed| {
+        let config = Config {
+            seed: Some(seed),
+            shadow: true,
+            ..Config::default()
+        };
+        let mut sim = Simulation::from_board(config, board.clone()).unwrap();
+        let board = sim.nth(19).unwrap().board;
+        (board, sim.shadow().unwrap().clone())
+    };
+    let (board_a, shadow_a) = run(16);
+    let (board_b, shadow_b) = run(17);
+
+    assert_ne!(board_a, board_b);
+    assert_eq!(shadow_a, shadow_b);
+    assert!((shadow_a.sum() - 100.0).abs() <= 1e-9);
+}
+
+#[test]
+fn local_entropy_windows_span_more_than_one_cell() {
+    for (window, ok) in [(0, false), (1, false), (2, true)] {
+        let config = Config {
+            local_entropy_window: window,
+            ..Config::default()
+        };
+        assert_eq!(
+            !matches!(config.validate(), Err(ConfigError::ZeroWindow)),
+            ok,
+            "{}",
+            window
+        );
+    }
+}
+
+#[test]
+fn lbm_runs_have_no_shadow_to_compare_with() {
+    let config = Config {
+        dims: (12, 12),
+        scheme: Scheme::Lbm,
+        shadow: true,
+        ..Config::default()
+    };
+    assert!(matches!(
+        config.validate(),
+        Err(ConfigError::SchemeUnsupported(Scheme::Lbm, "shadow"))
+    ));
+}
+
+#[test]
+fn shadows_refuse_what_they_dont_model() {
+    let noisy = Config {
+        dims: (12, 12),
+        shadow: true,
+        thermal_noise: 0.1,
+        ..Config::default()
+    };
+    assert!(matches!(
+        noisy.validate(),
+        Err(ConfigError::ShadowUnsupported("thermal_noise"))
+    ));
+    let sinks = Config {
+        dims: (12, 12),
+        shadow: true,
+        sinks: vec![Cells::List(vec![(0, 0)])],
+        ..Config::default()
+    };
+    assert!(matches!(
+        sinks.validate(),
+        Err(ConfigError::ShadowUnsupported("sinks"))
+    ));
+    assert!(Config {
+        shadow: false,
+        ..sinks
+    }
+    .validate()
+    .is_ok());
+}
+
+#[test]
+fn deterministic_mode_steps_like_the_shadow_field() {
+    let mut board = ndarray::Array2::zeros((16, 16));
+    board[[4, 9]] = 100.0;
+    let run = |seed| {
+        let config = Config {
+            seed: Some(seed),
+            mode: Mode::Deterministic,
+            shadow: true,
+            ..Config::default()
+        };
+        let mut sim = Simulation::from_board(config, board.clone()).unwrap();
+        let board = sim.nth(19).unwrap().board;
+        (board, sim.shadow().unwrap().clone())
+    };
+    let (board_a, shadow) = run(16);
+    let (board_b, _) = run(17);
+
+    assert_eq!(board_a, board_b);
+    let diff = (&board_a - &shadow).fold(0.0_f64, |m, &d| m.max(d.abs()));
+    assert!(diff <= 1e-12, "{}", diff);
+}
+
+#[test]
+fn the_kernel_sets_which_neighbors_a_cell_reaches() {
+    let step = |kernel: &str| {
+        let mut board = Array2::zeros((9, 9));
+        board[[4, 4]] = 1.0;
+        let config = Config {
+            mode: Mode::Deterministic,
+            kernel: serde_json::from_str(kernel).unwrap(),
+            ..Config::default()
+        };
+        let mut sim = Simulation::from_board(config, board).unwrap();
+        sim.step();
+        sim.board().clone()
+    };
+
+    let cross = step(r#"{"vonNeumann": {"radius": 1}}"#);
+    assert_eq!(cross[[3, 3]], 0.0);
+    assert!((cross[[3, 4]] - 0.2).abs() < 1e-12);
+
+    let wide = step(r#"{"gaussian": {"radius": 2}}"#);
+    assert!(wide[[2, 2]] > 0.0 && wide[[2, 2]] < wide[[3, 3]]);
+    assert_eq!(wide[[1, 4]], 0.0);
+    assert!((wide.sum() - 1.0).abs() < 1e-12);
+
+    let lopsided = Config {
+        kernel: serde_json::from_str(r#"{"matrix": [[1, 1], [1, 1]]}"#).unwrap(),
+        ..Config::default()
+    };
+    assert_eq!(lopsided.validate(), Err(ConfigError::InvalidKernel));
+}
+
+#[test]
+fn a_tracer_mixes_without_moving_the_energy() {
+    let run = |tracer| {
+        let config = Config {
+            dims: (12, 12),
+            seed: Some(5),
+            tracer,
+            ..Config::default()
+        };
+        let mut sim = Simulation::new(config).unwrap();
+        let board = sim.nth(29).unwrap().board;
+        (board, sim.tracer().cloned())
+    };
+    let (plain, none) = run(None);
+    let (traced, dye) = run(Some(Tracer::LeftHalf));
+    let dye = dye.unwrap();
+
+    assert!(none.is_none());
+    assert_eq!(plain, traced);
+    // a closed board keeps its dye, and some of it has crossed into the right half
+    assert!((dye.sum() - 72.0).abs() < 1e-9);
+    assert!(dye[[6, 11]] > 0.0 && dye[[6, 0]] < 1.0);
+}
+
+#[test]
+fn a_wind_vector_carries_a_plume_downwind() {
+    let config: Config = serde_json::from_str(
+        r#"{
+            "dims": [16, 32],
+            "hotspots": 0,
+            "sleep_interval_ms": 0,
+            "heat": 1.0,
+            "size_factor": 1,
+            "mode": "deterministic",
+            "drift": [0.5, 0.0]
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        config.drift,
+        Some(Drift::Uniform {
+            velocity: (0.0, 0.5)
+        })
+    );
+    // and it is written back out in full
+    let reread: Config = serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+    assert_eq!(reread.drift, config.drift);
+
+    let mut board = Array2::zeros((16, 32));
+    board[[8, 12]] = 1.0;
+    let mut sim = Simulation::from_board(config, board).unwrap();
+    let board = sim.nth(9).unwrap().board;
+    let column_mean = |board: &Array2<f64>| {
+        board
+            .indexed_iter()
+            .map(|((_, j), e)| j as f64 * e)
+            .sum::<f64>()
+    };
+    // a third of a cell a step to the right, against none without the wind
+    assert!((column_mean(&board) - 12.0 - 10.0 / 3.0).abs() < 1e-9);
+}
+
+#[test]
+fn the_lattice_boltzmann_scheme_diffuses_and_drifts_like_scatter() {
+    let run = |scheme, boundary, drift| {
+        let mut board = Array2::zeros((33, 33));
+        board[[16, 16]] = 1.0;
+        let config = Config {
+            scheme,
+            boundary,
+            drift,
+            mode: Mode::Deterministic,
+            ..Config::default()
+        };
+        let mut sim = Simulation::from_board(config, board).unwrap();
+        sim.nth(39).unwrap().board
+    };
+    // the mean column and the variance down the rows
+    let moments = |board: &Array2<f64>| {
+        let (mut mean, mut variance) = (0.0, 0.0);
+        for ((i, j), e) in board.indexed_iter() {
+            mean += j as f64 * e;
+            variance += (i as f64 - 16.0).powi(2) * e;
+        }
+        (mean, variance)
+    };
+
+    let drift = Some(Drift::Uniform {
+        velocity: (0.0, 0.3),
+    });
+    for drift in [None, drift] {
+        let lbm = moments(&run(Scheme::Lbm, Boundary::Periodic, drift));
+        let scatter = moments(&run(Scheme::Scatter, Boundary::Periodic, drift));
+        // the lattice starts at rest, so it lags a little at first
+        assert!((lbm.0 - scatter.0).abs() < 0.05 * scatter.0, "{:?}", drift);
+        assert!((lbm.1 - scatter.1).abs() < 0.05 * scatter.1, "{:?}", drift);
+    }
+
+    let closed = run(Scheme::Lbm, Boundary::Closed, None);
+    assert!((closed.sum() - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn step_metrics_track_entropy_and_total_energy() {
+    let config = Config {
+        dims: (16, 16),
+        hotspots: 2,
+        seed: Some(8),
+        ..Config::default()
+    };
+    let mut sim = Simulation::new(config.clone()).unwrap();
+    let mut metrics = RunMetrics::new(sim.board(), &config);
+    let start = *metrics.initial();
+    for _ in 0..100 {
+        sim.step();
+        metrics.update(sim.steps(), sim.board(), sim.last_report());
+    }
+    let last = metrics.last();
+
+    assert!(start.entropy < last.entropy && last.entropy <= (256.0_f64).ln());
+    assert!((last.total_energy - start.total_energy).abs() < 1e-9 * start.total_energy);
+    let columns = |line: &str| line.split('\t').count();
+    assert_eq!(columns(&last.row()), columns(StepMetrics::HEADER));
+}
+
+#[test]
+fn headerless_state_files_migrate_to_the_current_version() {
+    let dir = std::env::temp_dir().join(format!("entropy-migrate-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (old, new) = (dir.join("old.json"), dir.join("new.state"));
+
+    let sim = SimulationBuilder::new().dims(8, 8).seed(2).build().unwrap();
+    std::fs::write(&old, serde_json::to_string(&sim).unwrap()).unwrap();
+
+    assert!(matches!(
+        format::read_state(&old),
+        Err(format::FormatError::NeedsMigration(_))
+    ));
+    assert_eq!(
+        format::migrate(&old, &new).unwrap(),
+        format::Version(0, 0, 0)
+    );
+    assert_eq!(format::read_state(&new).unwrap().board(), sim.board());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn verify_passes_on_this_build() {
+    for check in entropy::verify::run() {
+        assert!(check.passed, "{}: {}", check.name, check.detail);
+    }
+}
+
+#[test]
+fn equilibrium_fit_recovers_an_exponential_approach() {
+    let mut fit = entropy::stats::EquilibriumFit::default();
+    assert_eq!(fit.remaining(0, 1e-3), None);
+
+    // KL = exp(-t / 100) reaches 1e-3 at t = 100 ln 1000 ~ 690.8
+    for t in 0..300 {
+        fit.update(t, (-(t as f64) / 100.0).exp());
+    }
+    let remaining = fit.remaining(299, 1e-3).unwrap();
+    assert!((remaining - (690.8 - 299.0)).abs() < 1.0, "{}", remaining);
+}
+
+#[test]
+fn oversized_windows_fit_and_pixels_past_the_board_are_padding() {
+    let config = Config {
+        dims: (3000, 1000),
+        size_factor: 10,
+        marginals: true,
+        ..Config::default()
+    };
+    let (layout, warnings) = config.window_layout();
+    assert_eq!(layout.size_factor, 5);
+    assert_eq!(
+        warnings,
+        [ConfigWarning::SizeFactorReduced {
+            size_factor: 10,
+            fitted: 5
+        }]
+    );
+    assert!(layout.width <= MAX_WINDOW_SIDE && layout.height <= MAX_WINDOW_SIDE);
+
+    let dims = (3000, 1000);
+    assert_eq!(
+        layout.region(40 + 7, 11, dims),
+        Region::Board { row: 2, col: 1 }
+    );
+    assert_eq!(
+        layout.region(39, 11, dims),
+        Region::RowMarginal { row: 2, offset: 0 }
+    );
+    assert_eq!(layout.region(40 + 5000, 0, dims), Region::Blank);
+    assert_eq!(layout.region(0, 15000 + 40, dims), Region::Blank);
+}
+
+// mean corner energy over mean interior energy, averaged over a settled run
+fn corner_ratio(scheme: Scheme, boundary: Boundary) -> f64 {
+    let config = Config {
+        dims: (8, 8),
+        hotspots: 64,
+        scheme,
+        boundary,
+        seed: Some(5),
+        ..Config::default()
+    };
+    let mut sim = Simulation::new(config).unwrap();
+    let (mut corners, mut interior) = (0.0, 0.0);
+    for step in 0..4000 {
+        sim.step();
+        if step >= 1000 {
+            let board = sim.board();
+            corners += board[[0, 0]] + board[[0, 7]] + board[[7, 0]] + board[[7, 7]];
+            interior += board.slice(ndarray::s![1..7, 1..7]).sum() / 9.0;
+        }
+    }
+    corners / interior
+}
+
+#[test]
+fn gather_settles_to_uniform_where_scatter_favors_corners() {
+    // a scatter stencil is 4 cells at a corner and 9 inside, so corners hold
+    // about 4/9 of the interior's energy
+    assert!((corner_ratio(Scheme::Scatter, Boundary::Closed) - 4.0 / 9.0).abs() < 0.05);
+    assert!((corner_ratio(Scheme::Gather, Boundary::Closed) - 1.0).abs() < 0.05);
+}
+
+#[test]
+fn a_periodic_board_has_no_edges_to_favor() {
+    for scheme in [Scheme::Scatter, Scheme::Gather] {
+        assert!((corner_ratio(scheme, Boundary::Periodic) - 1.0).abs() < 0.05);
+    }
+
+    // energy leaving the last column lands in the first
+    let mut board = Array2::zeros((5, 5));
+    board[[2, 4]] = 1.0;
+    let config = Config {
+        boundary: Boundary::Periodic,
+        seed: Some(2),
+        ..Config::default()
+    };
+    let mut sim = Simulation::from_board(config, board).unwrap();
+    sim.step();
+    assert!(sim.board()[[2, 0]] > 0.0);
+    assert!((sim.board().sum() - 1.0).abs() < 1e-12);
+
+    let narrow = Config {
+        dims: (2, 8),
+        boundary: Boundary::Periodic,
+        ..Config::default()
+    };
+    assert!(matches!(
+        narrow.validate(),
+        Err(ConfigError::PeriodicTooSmall(_))
+    ));
+}
+
+#[test]
+fn an_absorbing_board_loses_what_crosses_its_edges() {
+    for scheme in [Scheme::Scatter, Scheme::Gather] {
+        let config = Config {
+            dims: (12, 12),
+            hotspots: 20,
+            scheme,
+            boundary: Boundary::Absorbing,
+            seed: Some(4),
+            ..Config::default()
+        };
+        let mut sim = Simulation::new(config.clone()).unwrap();
+        let start = sim.board().sum();
+        let mut metrics = RunMetrics::new(sim.board(), &config);
+        for _ in 0..200 {
+            sim.step();
+            metrics.update(sim.steps(), sim.board(), sim.last_report());
+        }
+
+        let budget = metrics.budget();
+        assert!(sim.board().sum() < 0.9 * start);
+        assert!(budget.escaped() > 0.1 * start);
+        assert!(budget.drift().abs() <= 1e-9 * start);
+        assert!(sim.board().iter().all(|&e| e >= 0.0));
+    }
+}
+
+#[test]
+fn gather_conserves_energy() {
+    let mut sim = SimulationBuilder::new()
+        .dims(12, 9)
+        .hotspots(4)
+        .seed(2)
+        .build()
+        .unwrap();
+    let mut config = sim.config().clone();
+    config.scheme = Scheme::Gather;
+    sim.set_config(config).unwrap();
+    let total = sim.board().sum();
+    for _ in 0..50 {
+        sim.step();
+    }
+    assert!((sim.board().sum() - total).abs() < 1e-9 * total);
+    assert!(sim.board().iter().all(|&e| e >= 0.0));
+}
+
+#[test]
+fn cells_below_the_active_threshold_sit_out_and_energy_is_kept() {
+    for scheme in [Scheme::Scatter, Scheme::Gather] {
+        let config = Config {
+            dims: (16, 16),
+            hotspots: 2,
+            // above the mean energy of 1, so some cells never take part
+            active_threshold: 2.0,
+            scheme,
+            seed: Some(9),
+            ..Config::default()
+        };
+        let mut sim = Simulation::new(config).unwrap();
+        let total = sim.board().sum();
+        sim.step();
+        // everything but the two hotspots starts at zero
+        assert!(sim.last_report().skipped >= 16 * 16 - 2);
+        for _ in 0..100 {
+            sim.step();
+        }
+        assert!((sim.board().sum() - total).abs() < 1e-9 * total);
+        assert!(sim.last_report().skipped > 0);
+    }
+}
+
+#[test]
+fn governor_speeds_up_as_entropy_settles_and_slows_for_events() {
+    let speed = AutoSpeed {
+        target_change: 1e-2,
+        max_steps_per_frame: 50,
+        event_lead: 10,
+    };
+    let mut governor = Governor::new(speed, 2);
+    assert_eq!(governor.steps_per_frame(0, &[]), 2);
+
+    governor.observe(0.1);
+    assert_eq!(governor.steps_per_frame(0, &[]), 2);
+    for _ in 0..200 {
+        governor.observe(1e-3);
+    }
+    let fast = governor.steps_per_frame(100, &[]);
+    assert!((9..=10).contains(&fast), "{}", fast);
+
+    let event = ScriptedEvent {
+        step: 105,
+        action: Action::Resample {
+            scale: 1.0,
+            shift: (0.0, 0.0),
+        },
+    };
+    assert_eq!(governor.steps_per_frame(100, &[event]), 2);
+    assert_eq!(governor.steps_per_frame(105, &[event]), fast);
+}
+
+#[test]
+fn a_settled_board_idles_until_something_happens() {
+    let save = PowerSave {
+        settled_change: 1e-4,
+        settle_frames: 3,
+        idle_fps: 2.0,
+    };
+    let mut idler = Idler::new(save);
+    idler.observe(1e-2);
+    for _ in 0..2 {
+        idler.observe(1e-5);
+    }
+    assert!(!idler.idle());
+    idler.observe(-1e-5);
+    assert!(idler.idle());
+    assert_eq!(idler.frame_time(), std::time::Duration::from_millis(500));
+
+    let event = ScriptedEvent {
+        step: 50,
+        action: Action::Resample {
+            scale: 1.0,
+            shift: (0.0, 0.0),
+        },
+    };
+    idler.expect(10, 20, &[event]);
+    assert!(idler.idle());
+    idler.expect(40, 20, &[event]);
+    assert!(!idler.idle());
+    assert_eq!(idler.frame_time(), std::time::Duration::ZERO);
+}
+
+#[test]
+fn energy_budget_reconciles_sources_baths_and_traps() {
+    let config: Config = serde_json::from_str(
+        r#"{
+            "dims": [16, 16],
+            "hotspots": 2,
+            "sleep_interval_ms": 0,
+            "heat": 1.0,
+            "size_factor": 1,
+            "seed": 21,
+            "bath": {"temperature": 0.5, "coupling": 0.1, "region": "boundary"},
+            "sources": [{"power": 0.5, "path": {"fixed": {"at": [8, 8]}}}],
+            "traps": {"fraction": 0.5, "hold_steps": 3, "density": 0.2},
+            "reset_rate": 0.01
+        }"#,
+    )
+    .unwrap();
+    let mut sim = Simulation::new(config.clone()).unwrap();
+    let mut metrics = RunMetrics::new(sim.board(), &config);
+    for _ in 0..100 {
+        sim.step();
+        metrics.update(sim.steps(), sim.board(), sim.last_report());
+    }
+
+    let budget = metrics.budget();
+    assert!(budget.drift().abs() <= 1e-9 * budget.expected());
+    assert!(metrics.summary().contains("numerical drift"));
+}
+
+#[test]
+fn kernel_flags_mark_wall_cells_and_nothing_unnormalized() {
+    let config = Config {
+        dims: (10, 7),
+        seed: Some(3),
+        assertions: Assertions::Panic,
+        ..Config::default()
+    };
+    let mut sim = Simulation::new(config).unwrap();
+    sim.step();
+
+    let flags = sim.last_report().flags.as_ref().unwrap();
+    assert_eq!(flags.count(KernelFlags::UNNORMALIZED), 0);
+    assert_eq!(flags.count(KernelFlags::CLAMPED), 2 * 10 + 2 * 7 - 4);
+    assert_eq!(flags.0[[4, 3]], 0);
+}
+
+#[test]
+fn clamp_policy_zeroes_rounding_residue_and_redistribute_keeps_the_total() {
+    let mut board = Array2::from_elem((2, 2), 1.0);
+    board[[0, 0]] = -1e-15;
+    board[[1, 1]] = f64::MIN_POSITIVE / 2.0;
+    let total = board.sum();
+
+    let mut zeroed = board.clone();
+    let added = ClampPolicy::Zero.apply(&mut zeroed).unwrap();
+    assert_eq!(zeroed[[0, 0]], 0.0);
+    assert_eq!(zeroed[[1, 1]], 0.0);
+    assert!((added - 1e-15).abs() < 1e-18);
+
+    let mut redistributed = board.clone();
+    ClampPolicy::Redistribute.apply(&mut redistributed).unwrap();
+    assert!(redistributed.iter().all(|&e| e >= 0.0));
+    assert!((redistributed.sum() - total).abs() < 1e-15);
+
+    let mut kept = board.clone();
+    assert_eq!(ClampPolicy::Keep.apply(&mut kept), Ok(0.0));
+    assert_eq!(kept, board);
+    assert_eq!(
+        ClampPolicy::Error.apply(&mut board.clone()),
+        Err(((0, 0), -1e-15))
+    );
+
+    // a real negative is left for `debug` to catch
+    board[[0, 0]] = -0.5;
+    ClampPolicy::Zero.apply(&mut board).unwrap();
+    assert_eq!(board[[0, 0]], -0.5);
+}
+
+#[test]
+fn reset_goes_back_to_step_zero_and_repeats_the_run() {
+    let config = Config {
+        dims: (12, 12),
+        seed: Some(9),
+        traps: Some(Traps {
+            fraction: 0.2,
+            hold_steps: 3,
+            density: 0.1,
+            mask: None,
+        }),
+        ..Config::default()
+    };
+    let mut sim = Simulation::new(config).unwrap();
+    let initial = sim.board().clone();
+    let first: Vec<_> = sim.by_ref().take(20).map(|frame| frame.board).collect();
+
+    sim.reset();
+    assert_eq!(sim.steps(), 0);
+    assert_eq!(sim.board(), &initial);
+    let again: Vec<_> = sim.by_ref().take(20).map(|frame| frame.board).collect();
+    assert_eq!(first, again);
+}
+
+#[test]
+fn outside_the_focus_the_board_takes_the_average_step() {
+    let mut board = Array2::zeros((16, 16));
+    board[[2, 2]] = 10.0;
+    let runs: Vec<_> = [1, 2]
+        .into_iter()
+        .map(|seed| {
+            let config = Config {
+                seed: Some(seed),
+                focus: Some(Focus {
+                    center: (15, 15),
+                    radius: 1,
+                }),
+                ..Config::default()
+            };
+            let mut sim = Simulation::from_board(config, board.clone()).unwrap();
+            for _ in 0..5 {
+                sim.step();
+            }
+            sim.board().clone()
+        })
+        .collect();
+
+    // the energy hasn't reached the focus yet, so the seed makes no difference
+    assert_eq!(runs[0], runs[1]);
+    assert!((runs[0].sum() - 10.0).abs() < 1e-12);
+
+    let off = Config {
+        dims: (16, 16),
+        focus: Some(Focus {
+            center: (16, 0),
+            radius: 1,
+        }),
+        ..Config::default()
+    };
+    assert!(matches!(off.validate(), Err(ConfigError::FocusOffBoard(_))));
+}
+
+#[test]
+fn video_settings_fill_in_and_reject_empty_recordings() {
+    let config: Config = serde_json::from_str(
+        r#"{"dims": [10, 10], "hotspots": 1, "sleep_interval_ms": 0, "heat": 1.0, "size_factor": 1, "video": {"path": "run.mp4", "seconds": 5}}"#,
+    )
+    .unwrap();
+    assert_eq!(config.video.path, "run.mp4");
+    assert_eq!((config.video.fps, config.video.every), (30, 1));
+    assert!(!config.video.start);
+    assert!(config.validate().is_ok());
+
+    let mut bad = config.clone();
+    bad.video.fps = 0;
+    assert!(matches!(bad.validate(), Err(ConfigError::InvalidVideo)));
+    let mut bad = config;
+    bad.video.seconds = Some(0.0);
+    assert!(matches!(bad.validate(), Err(ConfigError::InvalidVideo)));
+}
+
+#[test]
+fn walls_keep_energy_out_and_on_its_side() {
+    use entropy::Backend;
+
+    // a wall down column 6, with something on it to be cleared at the start
+    let mut board = Array2::zeros((12, 12));
+    board[[5, 2]] = 10.0;
+    board[[3, 6]] = 5.0;
+    let wall = Cells::Rect {
+        rows: (0, 12),
+        cols: (6, 7),
+    };
+    let run = |scheme, backend, mode| {
+        let config = Config {
+            seed: Some(4),
+            scheme,
+            backend,
+            mode,
+            threads: Some(2),
+            walls: Some(Walls::new(vec![wall.clone()], None)),
+            ..Config::default()
+        };
+        let mut sim = Simulation::from_board(config, board.clone()).unwrap();
+        for _ in 0..200 {
+            sim.step();
+        }
+        sim.board().clone()
+    };
+
+    let scalar = run(Scheme::Scatter, Backend::Scalar, Mode::Stochastic);
+    let runs = [
+        run(Scheme::Scatter, Backend::Parallel, Mode::Stochastic),
+        run(Scheme::Gather, Backend::Scalar, Mode::Stochastic),
+        run(Scheme::Scatter, Backend::Scalar, Mode::Deterministic),
+    ];
+    for board in runs.iter().chain([&scalar]) {
+        assert!((board.sum() - 10.0).abs() < 1e-9);
+        assert_eq!(board.column(6).sum(), 0.0);
+        assert_eq!(board.slice(ndarray::s![.., 7..]).sum(), 0.0);
+        // it does spread out on its own side
+        assert!(board[[11, 0]] > 0.0);
+    }
+    assert!((&scalar - &runs[0]).iter().all(|d| d.abs() < 1e-9));
+
+    let with = |config: Config| Config {
+        walls: Some(Walls::new(vec![wall.clone()], None)),
+        ..config
+    };
+    let lbm = with(Config {
+        dims: (12, 12),
+        scheme: Scheme::Lbm,
+        ..Config::default()
+    });
+    assert!(matches!(
+        lbm.validate(),
+        Err(ConfigError::SchemeUnsupported(Scheme::Lbm, "walls"))
+    ));
+    let levy = with(Config {
+        dims: (12, 12),
+        levy: Some(Levy {
+            fraction: 0.1,
+            exponent: 1.5,
+        }),
+        ..Config::default()
+    });
+    assert!(matches!(
+        levy.validate(),
+        Err(ConfigError::WallsUnsupported("levy"))
+    ));
+    let off = with(Config {
+        dims: (12, 6),
+        ..Config::default()
+    });
+    assert!(matches!(off.validate(), Err(ConfigError::CellsOffBoard)));
+}
+
+#[test]
+fn walls_take_nothing_from_baths_noise_sources_or_resets() {
+    use entropy::field::Spectrum;
+    use entropy::{Bath, BathRegion, Source, SourcePath};
+
+    let wall = Cells::Rect {
+        rows: (0, 12),
+        cols: (5, 7),
+    };
+    let config = |spectrum, region| Config {
+        dims: (12, 12),
+        seed: Some(9),
+        walls: Some(Walls::new(vec![wall.clone()], None)),
+        bath: Some(Bath {
+            temperature: 2.0,
+            coupling: 0.3,
+            region,
+        }),
+        thermal_noise: 0.5,
+        noise_spectrum: spectrum,
+        reset_rate: 0.1,
+        fixed_sources: vec![FixedSource {
+            cells: wall.clone(),
+            power: 1.0,
+        }],
+        sources: vec![Source {
+            power: 1.0,
+            path: SourcePath::Fixed { at: (3.0, 5.0) },
+        }],
+        ..Config::default()
+    };
+    let spectra = [None, Some(Spectrum::PowerLaw { exponent: 2.0 })];
+    for (spectrum, region) in itertools::iproduct!(spectra, [BathRegion::All, BathRegion::Boundary])
+    {
+        let mut sim = Simulation::new(config(spectrum, region)).unwrap();
+        for _ in 0..50 {
+            sim.step();
+            // none of what the sources put on the wall counts as added
+            assert_eq!(sim.last_report().source_in, 0.0);
+        }
+        let walls = sim.config().wall_mask().unwrap();
+        assert!(sim
+            .board()
+            .iter()
+            .zip(walls.iter())
+            .all(|(&e, &wall)| !wall || e == 0.0));
+        assert!(sim.board().sum() > 0.0);
+    }
+}
+
+#[test]
+fn wall_masks_follow_changes_to_the_walls() {
+    let mut config = Config {
+        dims: (6, 6),
+        walls: Some(Walls::new(vec![Cells::List(vec![(1, 1)])], None)),
+        ..Config::default()
+    };
+    assert!(config.wall_mask().unwrap()[[1, 1]]);
+
+    config.walls.as_mut().unwrap().cells = vec![Cells::List(vec![(2, 3)])];
+    let mask = config.wall_mask().unwrap();
+    assert!(!mask[[1, 1]] && mask[[2, 3]]);
+
+    config.transform.rotate = 90;
+    config.dims = (8, 6);
+    assert_eq!(config.wall_mask().unwrap().dim(), (8, 6));
+}
+
+#[test]
+fn wall_images_mark_their_dark_pixels() {
+    use image::{GrayImage, Luma};
+
+    let path = std::env::temp_dir().join(format!("entropy-walls-{}.png", std::process::id()));
+    // dark down the middle column, and across the top row
+    GrayImage::from_fn(5, 4, |x, y| {
+        if x == 2 || y == 0 {
+            Luma([20])
+        } else {
+            Luma([235])
+        }
+    })
+    .save(&path)
+    .unwrap();
+    let walls = Walls::new(
+        vec![Cells::List(vec![(0, 0)])],
+        Some(path.to_str().unwrap().to_string()),
+    );
+    let config = Config {
+        dims: (4, 5),
+        walls: Some(walls),
+        ..Config::default()
+    };
+    config.validate().unwrap();
+    let mask = config.wall_mask().unwrap();
+    // the image's top row is the board's last
+    let expected = Array2::from_shape_fn((4, 5), |(i, j)| j == 2 || i == 3 || (i, j) == (0, 0));
+    assert_eq!(*mask, expected);
+
+    let missing = Config {
+        walls: Some(Walls::new(vec![], Some("no-such-walls.png".to_string()))),
+        ..config
+    };
+    assert!(matches!(missing.validate(), Err(ConfigError::BadWalls(_))));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn deposits_add_energy_and_count_as_a_source() {
+    let config = Config {
+        dims: (8, 8),
+        seed: Some(2),
+        walls: Some(Walls::new(vec![Cells::List(vec![(0, 0)])], None)),
+        ..Config::default()
+    };
+    let mut sim = Simulation::new(config).unwrap();
+    let mut budget = EnergyBudget::new(sim.board());
+    let before = sim.board().sum();
+
+    sim.deposit((3, 4), 5.0);
+    assert!((sim.board().sum() - before - 5.0).abs() < 1e-9);
+    // a wall takes none
+    sim.deposit((0, 0), 5.0);
+    assert_eq!(sim.board()[[0, 0]], 0.0);
+    // and neither does a cell off the board
+    sim.deposit((8, 2), 5.0);
+    sim.deposit((2, 100), 5.0);
+
+    sim.step();
+    budget.update(sim.board(), sim.last_report());
+    assert!((sim.last_report().source_in - 5.0).abs() < 1e-12);
+    assert!(budget.drift().abs() < 1e-9);
+    sim.step();
+    assert_eq!(sim.last_report().source_in, 0.0);
+
+    let bad = Config {
+        click_energy: f64::NAN,
+        ..Config::default()
+    };
+    assert!(matches!(
+        bad.validate(),
+        Err(ConfigError::InvalidClickEnergy(_))
+    ));
+}
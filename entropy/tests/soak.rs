@@ -0,0 +1,56 @@
+use entropy::soak::{self, SoakSpec, LOG};
+use entropy::SimulationBuilder;
+
+#[test]
+fn soak_specs_parse_and_reject_nonsense() {
+    let spec: SoakSpec = "hours=0.5 check=200".parse().unwrap();
+    assert_eq!(spec.hours, 0.5);
+    assert_eq!(spec.check, 200);
+    assert_eq!(spec.keep, SoakSpec::default().keep);
+    assert!("hours=-1".parse::<SoakSpec>().is_err());
+    assert!("check=0".parse::<SoakSpec>().is_err());
+    assert!("days=2".parse::<SoakSpec>().is_err());
+}
+
+#[test]
+fn a_soak_checks_rotates_its_log_and_keeps_the_newest_checkpoints() {
+    let dir = std::env::temp_dir().join(format!("entropy-soak-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut sim = SimulationBuilder::new()
+        .dims(12, 12)
+        .hotspots(3)
+        .seed(5)
+        .build()
+        .unwrap();
+    let spec: SoakSpec = "steps=60 check=5 checkpoint=3 keep=2 log_rows=4"
+        .parse()
+        .unwrap();
+    let report = soak::run(&mut sim, &spec, &dir).unwrap();
+    assert!(report.passed(), "{:?}", report.failures);
+    assert_eq!((report.steps, report.checks), (60, 12));
+
+    let mut checkpoints: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.starts_with("checkpoint-"))
+        .collect();
+    checkpoints.sort();
+    assert_eq!(
+        checkpoints,
+        ["checkpoint-0000000045.state", "checkpoint-0000000060.state"]
+    );
+    // twelve rows, four to a log: two rotated out and the last still current
+    let rows = |name: &str| {
+        std::fs::read_to_string(dir.join(name))
+            .unwrap()
+            .lines()
+            .count()
+            - 1
+    };
+    assert_eq!(
+        (rows(LOG), rows("soak.1.tsv"), rows("soak.2.tsv")),
+        (4, 4, 4)
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,99 @@
+use entropy::model::Bath;
+use entropy::sweep::{derive_seed, Sweep, SweepError};
+use entropy::Config;
+use serde_json::json;
+use std::collections::BTreeSet;
+
+fn grid(parameters: serde_json::Value, repeats: usize) -> Sweep {
+    Sweep {
+        seed: 7,
+        steps: 5,
+        repeats,
+        parameters: parameters.as_object().unwrap().clone(),
+    }
+}
+
+#[test]
+fn points_count_the_last_parameter_fastest_and_repeats_innermost() {
+    let sweep = grid(json!({"heat": [1.0, 2.0], "hotspots": [1, 2, 3]}), 2);
+    assert_eq!(sweep.len(), 12);
+
+    let point = sweep.point(0);
+    assert_eq!(
+        (point.repeat, point.parameters["heat"].clone()),
+        (0, json!(1.0))
+    );
+    assert_eq!(point.parameters["hotspots"], json!(1));
+    let point = sweep.point(3);
+    assert_eq!(point.repeat, 1);
+    assert_eq!(point.parameters["hotspots"], json!(2));
+    let point = sweep.point(11);
+    assert_eq!(point.parameters["heat"], json!(2.0));
+    assert_eq!(point.parameters["hotspots"], json!(3));
+    assert_eq!(point.seed, derive_seed(7, 11));
+}
+
+#[test]
+fn tasks_cover_every_point_once_whatever_their_number() {
+    let sweep = grid(json!({"heat": [1.0, 2.0, 3.0], "hotspots": [1, 2]}), 3);
+    let whole = sweep.task(0, 1).unwrap();
+    assert_eq!(whole.len(), 18);
+
+    for tasks in [2, 5, 18, 40] {
+        let mut seen = BTreeSet::new();
+        for index in 0..tasks {
+            for point in sweep.task(index, tasks).unwrap() {
+                // a point is the same whichever task runs it
+                assert_eq!(point, whole[point.index]);
+                assert!(seen.insert(point.index));
+            }
+        }
+        assert_eq!(seen.len(), 18);
+    }
+    let seeds: BTreeSet<_> = whole.iter().map(|p| p.seed).collect();
+    assert_eq!(seeds.len(), 18);
+
+    assert!(matches!(
+        sweep.task(4, 4),
+        Err(SweepError::BadTask { index: 4, tasks: 4 })
+    ));
+}
+
+#[test]
+fn configs_take_nested_fields_and_reject_unknown_ones() {
+    let base = Config {
+        dims: (8, 8),
+        bath: Some(
+            serde_json::from_value::<Bath>(json!({"temperature": 1.0, "coupling": 0.5})).unwrap(),
+        ),
+        ..Config::default()
+    };
+    let sweep = grid(json!({"bath.coupling": [0.1], "heat": [0.5]}), 1);
+    let point = sweep.point(0);
+    let config = sweep.config(&base, &point).unwrap();
+    assert_eq!(config.bath.unwrap().coupling, 0.1);
+    assert_eq!(config.heat, 0.5);
+    assert_eq!(config.seed, Some(point.seed));
+
+    let typo = grid(json!({"haet": [3.0]}), 1);
+    assert!(matches!(
+        typo.config(&base, &typo.point(0)),
+        Err(SweepError::UnknownField(field)) if field == "haet"
+    ));
+}
+
+#[test]
+fn runs_are_repeatable_from_the_sweep_alone() {
+    let base = Config {
+        dims: (8, 8),
+        ..Config::default()
+    };
+    let sweep = grid(json!({"hotspots": [1, 2]}), 2);
+    let all = sweep.run(&base, &sweep.task(0, 1).unwrap()).unwrap();
+    assert_eq!(all.len(), 4);
+
+    let second = sweep.run(&base, &sweep.task(1, 2).unwrap()).unwrap();
+    assert_eq!(second, vec![all[1].clone(), all[3].clone()]);
+    // repeats of a combination get their own seeds
+    assert_ne!(all[0].final_entropy, all[1].final_entropy);
+}
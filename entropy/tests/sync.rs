@@ -0,0 +1,74 @@
+use entropy::session::Key;
+use entropy::sync::{Follower, Leader, Message};
+use entropy::SimulationBuilder;
+use std::time::Duration;
+
+#[test]
+fn followers_joining_late_get_the_whole_log() {
+    let sim = SimulationBuilder::new()
+        .dims(8, 8)
+        .seed(11)
+        .build()
+        .unwrap();
+    let mut leader = Leader::bind("127.0.0.1:0", sim.seed(), sim.config()).unwrap();
+    let addr = leader.local_addr().unwrap();
+    leader.broadcast(Message::Key {
+        step: 3,
+        key: Key::TogglePause,
+    });
+
+    let follower = std::thread::spawn(move || Follower::connect(addr).unwrap());
+    // the leader only takes on followers when it has something to send
+    while !follower.is_finished() {
+        leader.broadcast(Message::Step { step: 5 });
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    let (seed, config, follower) = follower.join().unwrap();
+    assert_eq!(seed, 11);
+    assert_eq!(config.dims, (8, 8));
+
+    // the catch-up ends with the step the leader had reached when it took us on,
+    // so the newest step may come after it
+    let mut messages = Vec::new();
+    while !messages
+        .iter()
+        .any(|m| matches!(m, Message::Step { step: 5 }))
+    {
+        messages.extend(follower.poll());
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert!(matches!(
+        messages[0],
+        Message::Key {
+            step: 3,
+            key: Key::TogglePause
+        }
+    ));
+}
+
+#[test]
+fn followers_that_stop_reading_dont_hold_up_the_leader() {
+    let sim = SimulationBuilder::new()
+        .dims(64, 64)
+        .seed(11)
+        .build()
+        .unwrap();
+    let mut leader = Leader::bind("127.0.0.1:0", sim.seed(), sim.config()).unwrap();
+    // connected, but never reads
+    let _stalled = std::net::TcpStream::connect(leader.local_addr().unwrap()).unwrap();
+    while leader.followers() == 0 {
+        leader.broadcast(Message::Step { step: 0 });
+        std::thread::sleep(Duration::from_millis(5));
+    }
+
+    // far more than the socket buffers hold
+    let started = std::time::Instant::now();
+    for step in 0..20_000 {
+        leader.broadcast(Message::Config {
+            step,
+            config: sim.config().clone(),
+        });
+    }
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(leader.followers(), 0);
+}
@@ -0,0 +1,22 @@
+use entropy::timing::Histogram;
+use std::time::Duration;
+
+#[test]
+fn quantiles_land_within_a_bucket_of_the_durations() {
+    let mut histogram = Histogram::new();
+    assert_eq!(histogram.quantile(0.5), Duration::ZERO);
+
+    // 1ms steps with one 20ms hitch
+    for _ in 0..99 {
+        histogram.record(Duration::from_millis(1));
+    }
+    histogram.record(Duration::from_millis(20));
+
+    let p50 = histogram.quantile(0.5).as_secs_f64();
+    assert!((1e-3..1.1e-3).contains(&p50), "{}", p50);
+    assert!(histogram.quantile(0.99) < Duration::from_millis(2));
+    assert_eq!(histogram.quantile(1.0), Duration::from_millis(20));
+    assert_eq!(histogram.max(), Duration::from_millis(20));
+    assert_eq!(histogram.jitter(), Duration::from_millis(19));
+    assert!(histogram.report().starts_with("p50 "));
+}
@@ -0,0 +1,80 @@
+use entropy::transform::{Mirror, Transform};
+use ndarray::{array, Array2};
+
+#[test]
+fn transforms_agree_with_their_source_cells() {
+    let field: Array2<f64> = array![[0., 1., 2.], [3., 4., 5.]];
+
+    for rotate in [0, 90, 180, 270] {
+        for mirror in [None, Some(Mirror::Horizontal), Some(Mirror::Vertical)] {
+            let transform = Transform { rotate, mirror };
+            let out = transform.apply(field.view());
+            assert_eq!(out.dim(), transform.dims(field.dim()));
+
+            for (cell, &e) in out.indexed_iter() {
+                assert_eq!(
+                    e,
+                    field[transform.source(cell, field.dim())],
+                    "{:?}",
+                    transform
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn quarter_turn_is_counterclockwise() {
+    let field: Array2<f64> = array![[0., 1.], [2., 3.]];
+    let turned = Transform::rotation(1).apply(field.view());
+
+    assert_eq!(turned, array![[1., 3.], [0., 2.]]);
+}
+
+#[test]
+fn images_load_as_luminance_holding_the_energy_asked_for() {
+    use entropy::{Config, InitialCondition, Simulation};
+    use image::{Luma, Rgb, RgbImage};
+
+    let dir = std::env::temp_dir().join(format!("entropy-image-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // a white top row over a black one
+    let path = dir.join("halves.png");
+    RgbImage::from_fn(4, 2, |_, y| if y < 1 { Rgb([255; 3]) } else { Rgb([0; 3]) })
+        .save(&path)
+        .unwrap();
+    let path = path.to_str().unwrap().to_string();
+
+    let field = entropy::transform::load_image(&path, (2, 4), 80.0, &Transform::default()).unwrap();
+    // the window draws row 0 at the bottom
+    assert_eq!(field, array![[0., 0., 0., 0.], [20., 20., 20., 20.]]);
+    let turned =
+        entropy::transform::load_image(&path, (2, 4), 80.0, &Transform::rotation(2)).unwrap();
+    assert_eq!(turned.row(0).sum(), 80.0);
+    // resized, the energy still adds up and the top stays brighter
+    let big = entropy::transform::load_image(&path, (6, 12), 80.0, &Transform::default()).unwrap();
+    assert!((big.sum() - 80.0).abs() < 1e-9);
+    assert!(big.row(5).sum() > big.row(0).sum());
+
+    let config = Config {
+        dims: (2, 4),
+        initial: InitialCondition::Image {
+            path: path.clone(),
+            energy: None,
+        },
+        ..Config::default()
+    };
+    assert_eq!(Simulation::new(config).unwrap().board().sum(), 8.0);
+
+    image::GrayImage::from_pixel(3, 3, Luma([0]))
+        .save(dir.join("black.png"))
+        .unwrap();
+    assert!(entropy::transform::load_image(
+        dir.join("black.png").to_str().unwrap(),
+        (2, 2),
+        1.0,
+        &Transform::default()
+    )
+    .is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,128 @@
+use entropy::upload::{hex, retry, sha256, Target, Upload, CHECKSUMS};
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[test]
+fn sha256_matches_the_standard_vectors() {
+    let cases = [
+        (
+            "",
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        ),
+        (
+            "abc",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        ),
+        // two blocks once padded
+        (
+            "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+        ),
+    ];
+    for (message, digest) in cases {
+        assert_eq!(hex(&sha256(message.as_bytes())), digest, "{:?}", message);
+    }
+}
+
+#[test]
+fn targets_parse_by_scheme() {
+    assert_eq!(
+        "s3://bucket/sweeps/".parse::<Target>().unwrap(),
+        Target::S3("s3://bucket/sweeps".to_string())
+    );
+    assert_eq!(
+        "gs://bucket".parse::<Target>().unwrap().uri("run/a.json"),
+        "gs://bucket/run/a.json"
+    );
+    assert_eq!(
+        "file:///tmp/out".parse::<Target>().unwrap(),
+        Target::File(PathBuf::from("/tmp/out"))
+    );
+    assert!("bucket/sweeps".parse::<Target>().is_err());
+    assert!("ftp://host/dir".parse::<Target>().is_err());
+    assert!("s3://".parse::<Target>().is_err());
+}
+
+#[test]
+fn uploads_put_the_chosen_files_then_their_checksums() {
+    let dir = std::env::temp_dir().join(format!("entropy-upload-test-{}", std::process::id()));
+    let (local, remote) = (dir.join("local"), dir.join("remote"));
+    std::fs::create_dir_all(&local).unwrap();
+    std::fs::write(local.join("manifest.json"), "{}").unwrap();
+    std::fs::write(local.join("report.html"), "abc").unwrap();
+    std::fs::write(local.join("stats.csv"), "skipped").unwrap();
+
+    let upload = Upload {
+        target: Target::File(remote.clone()),
+        retries: 0,
+        backoff: Duration::ZERO,
+        only: vec!["manifest.json".to_string(), "report.html".to_string()],
+    };
+    let files = ["manifest.json", "report.html", "stats.csv", "missing.bin"].map(|f| local.join(f));
+    let uri = upload.run("run-1", &files).unwrap();
+    assert_eq!(
+        uri,
+        format!("file://{}", remote.join("run-1").join(CHECKSUMS).display())
+    );
+
+    let run = remote.join("run-1");
+    assert_eq!(
+        std::fs::read_to_string(run.join("report.html")).unwrap(),
+        "abc"
+    );
+    assert!(!run.join("stats.csv").exists());
+    let sums = std::fs::read_to_string(run.join(CHECKSUMS)).unwrap();
+    assert_eq!(
+        sums,
+        format!(
+            "{}  manifest.json\n{}  report.html\n",
+            hex(&sha256(b"{}")),
+            hex(&sha256(b"abc"))
+        )
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn retries_stop_at_success_or_a_missing_tool() {
+    let mut calls = 0;
+    let result = retry(3, Duration::ZERO, || {
+        calls += 1;
+        if calls < 3 {
+            Err(io::Error::other("flaky"))
+        } else {
+            Ok(calls)
+        }
+    });
+    assert_eq!(result.unwrap(), 3);
+
+    let mut calls = 0;
+    let result: io::Result<()> = retry(3, Duration::ZERO, || {
+        calls += 1;
+        Err(io::Error::other("down"))
+    });
+    assert!(result.is_err());
+    assert_eq!(calls, 4);
+
+    let mut calls = 0;
+    let result: io::Result<()> = retry(3, Duration::ZERO, || {
+        calls += 1;
+        Err(io::ErrorKind::NotFound.into())
+    });
+    assert!(result.is_err());
+    assert_eq!(calls, 1);
+}
+
+#[test]
+fn uploads_with_nothing_to_put_are_refused() {
+    let dir = std::env::temp_dir().join(format!("entropy-upload-empty-{}", std::process::id()));
+    let upload = Upload {
+        target: Target::File(dir.clone()),
+        retries: 0,
+        backoff: Duration::ZERO,
+        only: Vec::new(),
+    };
+    assert!(upload.run("run-1", &[dir.join("missing.bin")]).is_err());
+    assert!(!dir.join("run-1").join(CHECKSUMS).exists());
+}
@@ -0,0 +1,32 @@
+use entropy::vtk::{self, VtkSeries};
+use ndarray::{array, Array2};
+
+#[test]
+fn vtk_series_list_each_step_and_its_cells() {
+    let dir = std::env::temp_dir().join(format!("entropy-vtk-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut series = VtkSeries::create(&dir).unwrap();
+    series
+        .push(0, &array![[1.0, 2.0, 3.0], [4.0, 5.0, 6.5]])
+        .unwrap();
+    series.push(10, &Array2::zeros((2, 3))).unwrap();
+    let pvd = std::fs::read_to_string(series.finish().unwrap()).unwrap();
+    assert!(pvd.contains("<DataSet timestep=\"0\" group=\"\" part=\"0\" file=\"00000000.vti\"/>"));
+    assert!(pvd.contains("timestep=\"10\""));
+
+    let vti = std::fs::read_to_string(dir.join("00000000.vti")).unwrap();
+    // a cell per board cell, so the points run one past the board on each axis
+    assert!(vti.contains("WholeExtent=\"0 3 0 2 0 0\""));
+    let values: Vec<&str> = vti
+        .split("format=\"ascii\">")
+        .nth(1)
+        .unwrap()
+        .split("</DataArray>")
+        .next()
+        .unwrap()
+        .split_whitespace()
+        .collect();
+    assert_eq!(values, ["1", "2", "3", "4", "5", "6.5"]);
+    assert_eq!(vtk::SERIES, "series.pvd");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
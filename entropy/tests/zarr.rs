@@ -0,0 +1,69 @@
+use entropy::zarr::{ZarrSpec, ZarrWriter, ENERGY, STEP};
+use flate2::read::ZlibDecoder;
+use ndarray::Array2;
+use serde_json::Value;
+use std::io::Read;
+
+#[test]
+fn specs_parse_and_default() {
+    assert_eq!("".parse::<ZarrSpec>().unwrap(), ZarrSpec::default());
+    assert_eq!(
+        "chunks=4x8x2, level=none".parse::<ZarrSpec>().unwrap(),
+        ZarrSpec {
+            chunks: (4, 8, 2),
+            level: None,
+        }
+    );
+    assert!("chunks=4x8".parse::<ZarrSpec>().is_err());
+    assert!("chunks=0x8x8".parse::<ZarrSpec>().is_err());
+    assert!("level=10".parse::<ZarrSpec>().is_err());
+}
+
+#[test]
+fn stores_hold_every_frame_in_whole_chunks() {
+    let root = std::env::temp_dir().join(format!("entropy-zarr-{}", std::process::id()));
+    let spec = ZarrSpec {
+        chunks: (2, 2, 8),
+        level: Some(5),
+    };
+    let mut zarr = ZarrWriter::create(&root, (3, 2), spec, serde_json::json!({"seed": 7})).unwrap();
+    let boards: Vec<Array2<f64>> = (0..3)
+        .map(|k| Array2::from_shape_fn((3, 2), |(i, j)| (100 * k + 10 * i + j) as f64))
+        .collect();
+    for (k, board) in boards.iter().enumerate() {
+        zarr.push(10 * k, board).unwrap();
+    }
+    assert_eq!(zarr.finish().unwrap(), 3);
+
+    let json = |path: &str| -> Value {
+        serde_json::from_slice(&std::fs::read(root.join(path)).unwrap()).unwrap()
+    };
+    let array = json(&format!("{}/.zarray", ENERGY));
+    assert_eq!(array["shape"], serde_json::json!([3, 3, 2]));
+    // cut down to the board's width
+    assert_eq!(array["chunks"], serde_json::json!([2, 2, 2]));
+    assert_eq!(json(".zattrs")["seed"], 7);
+    assert_eq!(
+        json(".zmetadata")["metadata"][format!("{}/.zarray", ENERGY)],
+        array
+    );
+
+    // the second slab's lower rows: frame 2, then the zeros past the end
+    let mut raw = Vec::new();
+    ZlibDecoder::new(std::fs::File::open(root.join(ENERGY).join("1.1.0")).unwrap())
+        .read_to_end(&mut raw)
+        .unwrap();
+    let values: Vec<f64> = raw
+        .chunks(8)
+        .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+    assert_eq!(values, [220., 221., 0., 0., 0., 0., 0., 0.]);
+
+    let steps: Vec<u64> = std::fs::read(root.join(STEP).join("0"))
+        .unwrap()
+        .chunks(8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+    assert_eq!(steps, [0, 10, 20]);
+    std::fs::remove_dir_all(&root).unwrap();
+}